@@ -50,7 +50,7 @@ impl State {
         let mut images = Vec::with_capacity(x.len() - 1);
         for i in 0..x.len() - 1 {
             // debug!("idx: {}, {}, {}", i, x[i], x[i+1]);
-            let img = file::data::load_image(path.as_str(), x[i], x[i+1]);
+            let img = file::data::load_image(path.as_str(), x[i], x[i+1]).unwrap_or_default();
             images.push(img);
         }
         self.images = images;
@@ -75,7 +75,7 @@ impl Sandbox for State {
                 self.page = 0;
                 self.index = idx;
                 let path = self.dir.to_string() + "/" + self.files.get(idx - 1).unwrap() + ".idx";
-                self.image_idx = file::data::load_index(path.as_str());
+                self.image_idx = file::data::load_index(path.as_str()).unwrap_or_default();
                 self.select_image_idx = 0;
                 self.load_images();
             },
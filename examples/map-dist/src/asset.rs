@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
-use file::data::ImageData;
+use file::library::{ImageData, ImageLibrary, IndexKind, WzlLibrary};
 use file::map::{MapInfo, Tile};
 use ggez::Context;
 use ggez::glam::vec2;
@@ -27,49 +27,38 @@ pub struct MapAsset {
 pub struct ImageAsset {
     dir: String,
     pub image: HashMap<u64, ImageData>,
-    pub index: HashMap<String, Vec<u32>>,
+    libraries: HashMap<String, Option<WzlLibrary>>,
 }
 
 impl ImageAsset {
 
     pub fn new(dir: &str) -> Self {
-        Self {dir: dir.to_string(), image: HashMap::new(), index: HashMap::new()}
+        Self {dir: dir.to_string(), image: HashMap::new(), libraries: HashMap::new()}
     }
     pub fn load_image_asset(&mut self, name: &str, file: u8, idx: u32) -> Option<&ImageData> {
-        let key = self.convert_file_name(self.dir.as_str(), name, file, "wzx");
-        let f = self.convert_file_name(self.dir.as_str(), name, file, "wzl");
-
-            if !self.index.contains_key(&key) {
-                let index = file::data::read_wzx(key.as_str());
-                self.index.insert(key.clone(), index);
-            }
-            if let Some(index) = self.index.get(key.as_str()) {
-                if idx as usize >= index.len() {
-                    warn!("idx:{}, len: {}, key: {}", idx, index.len(), key);
-                    return None;
-                }
-                let i = index[idx as usize];
-                let h = name.as_bytes()[0] as u64;
-                let c = file as u64;
-                let k = h << 40 | c << 32 | i as u64;
-                if !self.image.contains_key(&k) {
-                    // debug!("image:{} idx: {}, file: {}, key: {}", idx, i, f, key);
-                    let image_data = file::data::load_image(f.as_str(), i, i + 16);
-                    self.image.insert(k, image_data);
-                    // return Some(image_data);
-                    //self.image.insert(key.clone(), image_data);
-                }
-                return self.image.get(&k);
+        let path = self.convert_file_name(self.dir.as_str(), name, file);
+        let h = name.as_bytes()[0] as u64;
+        let c = file as u64;
+        let k = h << 40 | c << 32 | idx as u64;
+        if !self.image.contains_key(&k) {
+            let library = self.libraries.entry(path.clone())
+                .or_insert_with(|| WzlLibrary::open(path.as_str(), IndexKind::Wzx).map_err(|e| warn!("open: {}, {}", path, e)).ok())
+                .as_ref()?;
+            if idx as usize >= library.len() {
+                warn!("idx:{}, len: {}, file: {}", idx, library.len(), path);
+                return None;
             }
-
-        None
+            let image_data = library.load_image(idx).unwrap_or_default();
+            self.image.insert(k, image_data);
+        }
+        self.image.get(&k)
     }
 
-    fn convert_file_name(&self, dir: &str, name: &str, file: u8, suffix: &str) -> String {
+    fn convert_file_name(&self, dir: &str, name: &str, file: u8) -> String {
         if file <= 1 {
-            format!("{}{}.{}", dir, name, suffix)
+            format!("{}{}", dir, name)
         } else {
-            format!("{}{}{}.{}", dir, name, file, suffix)
+            format!("{}{}{}", dir, name, file)
         }
     }
 }
@@ -77,7 +66,7 @@ impl ImageAsset {
 impl MapAsset {
     pub fn new(dir: &str, file: &str, width: u32, height: u32) -> Self {
 
-        let info = file::map::read_map_file(String::from(dir.to_string() + file).as_str());
+        let info = file::map::read_map_file(String::from(dir.to_string() + file).as_str()).unwrap();
         let mut this = Self {
             base_dir: dir.to_string(),
             map_file_name: file.to_string(),
@@ -111,7 +100,7 @@ impl MapAsset {
         self.y_point = y;
         self.map_file_name = name.to_string();
 
-        self.map_info = file::map::read_map_file(String::from(self.base_dir.clone() + name).as_str());
+        self.map_info = file::map::read_map_file(String::from(self.base_dir.clone() + name).as_str()).unwrap();
 
         let start_x = x;
         let start_y = y;
@@ -176,7 +165,7 @@ impl MapAsset {
 
     fn load_image(&mut self, x: i32, y: i32, idx: usize, back_canvas: &mut Canvas, sm_canvas: &mut Canvas, obj_canvas: &mut Canvas, ctx: &mut Context) {
         let tile = &self.map_info.tiles[idx].clone();
-        let ann = tile.frame > 0;
+        let ann = tile.animation().is_some();
         let back = tile.back;
         let middle = tile.middle;
         let objects = tile.objects;
@@ -212,7 +201,7 @@ impl MapAsset {
             } else {
                 debug!("ann: x: {:03}, y: {:03}, idx: {:05}, file: {}, {:?}", x, y, idx, file_idx, tile);
             }
-            if tile.door_idx > 0 {
+            if tile.door().is_some() {
                 debug!("door: x: {:03}, y: {:03}, idx: {:05}, file: {}, {:?}", x, y, idx, file_idx, tile);
             }
             // debug!("objects: x: {:03}, y: {:03}, idx: {:05}, {:?}", x, y, idx, tile);
@@ -37,7 +37,13 @@ impl App {
 
         let x = self.files.get(idx).unwrap();
 
-        let map = map::read_map_file(x);
+        let map = match map::read_map_file(x) {
+            Ok(map) => map,
+            Err(e) => {
+                self.title = format!("idx: {}, file: {}, error: {}", idx, x, e);
+                return;
+            }
+        };
         self.title = format!("idx: {}, w: {}, h: {}, step: {}, size: {}, name: {}", self.index, map.width, map.height, map.step, map.size, map.name.clone());
         // self.map = Some(map);
         self.array.clear();
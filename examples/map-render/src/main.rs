@@ -0,0 +1,147 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::Instant;
+use clap::{Parser, ValueEnum};
+use file::asset::{create_image_asset, create_map_from, FileDescType};
+use file::error::{Error, Result};
+use file::manifest::LibraryManifest;
+use file::map::MapLayer;
+use file::render::{MapRenderer, RenderOptions, TileFormat};
+use serde::Serialize;
+
+/// 把整张地图按图块渲染到磁盘, 不需要整张地图的内存
+#[derive(Parser)]
+#[command(name = "map-render")]
+struct Args {
+    /// 资源目录, 包含data和map
+    dir: PathBuf,
+    /// 地图名称, 不含扩展名, 不区分大小写
+    map: String,
+    /// 输出目录
+    #[arg(short, long, default_value = "save")]
+    out: PathBuf,
+    #[arg(short, long, value_enum, default_value_t = Format::Webp)]
+    format: Format,
+    /// 图块边长
+    #[arg(long, default_value_t = 2048)]
+    tile_size: u32,
+    /// 缩放比例
+    #[arg(long, default_value_t = 1.0)]
+    zoom: f64,
+    /// 绘制的层, 默认全部
+    #[arg(long, value_enum, value_delimiter = ',')]
+    layers: Vec<Layer>,
+    /// 图片库清单(json, toml或ron), 默认使用tiles/smTiles/objects
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// 图片库的索引类型
+    #[arg(long, value_enum, default_value_t = Index::Wzx)]
+    index: Index,
+    /// 保存完全透明的图块
+    #[arg(long)]
+    keep_empty: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Png,
+    Webp,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Layer {
+    Back,
+    Middle,
+    Objects,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Index {
+    Wzl,
+    Wzx,
+    Idx,
+}
+
+/// 图块索引, 用于查看器拼接
+#[derive(Serialize)]
+struct TileIndex {
+    map: String,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    zoom: f64,
+    columns: u32,
+    rows: u32,
+    tiles: Vec<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(&args) {
+        eprintln!("render failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    let manifest = match args.manifest.as_ref() {
+        Some(path) => LibraryManifest::load(path)?,
+        None => LibraryManifest::default(),
+    };
+    let id = |name: &str| manifest.id_of(name).ok_or_else(|| Error::BadManifest(format!("{} not in manifest", name)));
+    let asset = create_image_asset(&args.dir.to_string_lossy(), &manifest);
+    let map = create_map_from(asset.vfs(), &args.map)?;
+
+    let mut options = RenderOptions {
+        tile_size: args.tile_size,
+        zoom: args.zoom,
+        back_file: id("tiles")?,
+        middle_file: id("smTiles")?,
+        objects_file: id("objects")?,
+        file_type: match args.index {
+            Index::Wzl => FileDescType::WZL,
+            Index::Wzx => FileDescType::WZX,
+            Index::Idx => FileDescType::IDX,
+        },
+        ..Default::default()
+    };
+    if !args.layers.is_empty() {
+        options.layers = args.layers.iter().map(|x| match x {
+            Layer::Back => MapLayer::Back,
+            Layer::Middle => MapLayer::Middle,
+            Layer::Objects => MapLayer::Objects,
+        }).collect();
+    }
+    let renderer = MapRenderer::from_tiles(&asset, map.width, map.height, &map.tiles, options)?;
+    let (width, height) = renderer.size();
+    let (columns, rows) = renderer.grid();
+    let format = match args.format {
+        Format::Png => TileFormat::Png,
+        Format::Webp => TileFormat::Webp,
+    };
+    let dir = args.out.join(&map.name);
+    fs::create_dir_all(&dir)?;
+    println!("{}: {}x{} cells, {}x{} px, {}x{} tiles", map.name, map.width, map.height, width, height, columns, rows);
+
+    let now = Instant::now();
+    let mut tiles = Vec::new();
+    renderer.render_each(|tile| {
+        if !args.keep_empty && tile.is_empty() {
+            return Ok(());
+        }
+        let name = tile.file_name(format);
+        tile.save(dir.join(&name), format)?;
+        tiles.push(name);
+        if tiles.len() % 10 == 0 {
+            println!("{}: {}/{} tiles, {:?}", map.name, tiles.len(), columns * rows, now.elapsed());
+        }
+        Ok(())
+    })?;
+
+    let index = TileIndex { map: map.name.clone(), width, height, tile_size: args.tile_size, zoom: args.zoom, columns, rows, tiles };
+    let writer = BufWriter::new(File::create(dir.join("index.json"))?);
+    serde_json::to_writer_pretty(writer, &index).map_err(|e| Error::Encode(e.to_string()))?;
+    println!("{}: {} tiles saved in {:?}", map.name, index.tiles.len(), now.elapsed());
+    Ok(())
+}
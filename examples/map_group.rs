@@ -35,7 +35,7 @@ const DATA_DIR: &str = "data";
 
 pub fn test_map_group(base_dir: &str, map_file: &str) {
     let file = String::from(base_dir) + map_file;
-    let map_info = file::map::read_map_file(&file);
+    let map_info = file::map::read_map_file(&file).unwrap();
     let mut hash: HashMap<u64, u32> = HashMap::new();
     let mut wzx_hash: HashMap<u16, Vec<u32>> = HashMap::new();
 
@@ -126,7 +126,7 @@ fn put_map0(hash: &mut HashMap<u64, u32>, wzx: &mut HashMap<u16, Vec<u32>>, file
             convert_file_name(path.as_str(), "objects", file as u8, "wzx")
         };
 
-        let wzx_vec = file::data::read_wzx(&file);
+        let wzx_vec = file::data::read_wzx(&file).unwrap_or_else(|_| vec![48]);
         wzx.insert(wzx_key, wzx_vec);
     }
 
@@ -1,5 +1,6 @@
 use std::f32::consts::PI;
 use file::asset::{FileDesc, FileDescType};
+use file::manifest::LibraryManifest;
 
 pub fn main() {
     let x1 = 0.0_f32;
@@ -24,10 +25,10 @@ pub fn main() {
     println!("(0, -1) angle: {}", angle(&point_x, &Point::new(0.0, -1.0)));
     println!("(1, -1) angle: {}", angle(&point_x, &Point::new(1.0, -1.0)));
 
-    let mut asset = file::asset::create_default_image_asset("/Users/vt/Documents/LegendOfMir");
-
-    asset.put_file_map(5, "hair", true);
-    let data = asset.load_image(FileDesc::ZONE {file: 5, number: 0, index: 833}, FileDescType::IDX).unwrap();
+    let manifest = LibraryManifest::from_json(include_str!("libraries.json")).unwrap();
+    let asset = file::asset::create_image_asset("/Users/vt/Documents/LegendOfMir", &manifest);
+    let hair = manifest.id_of("hair").unwrap();
+    let data = asset.load_image(FileDesc::ZONE {file: hair, number: 0, index: 833}, FileDescType::IDX).unwrap();
 
 }
 
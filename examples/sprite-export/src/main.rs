@@ -0,0 +1,182 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use clap::{Parser, ValueEnum};
+use file::atlas::TextureAtlas;
+use file::error::{Error, Result};
+use file::library::{ImageData, ImageLibrary, IndexKind, WzlLibrary};
+use file::mlib::MLibrary;
+use file::wil::WilLibrary;
+use image::{ExtendedColorType, ImageFormat};
+use serde::Serialize;
+
+/// 批量导出图片库中的图片, 同时生成记录宽高和偏移的索引文件
+#[derive(Parser)]
+#[command(name = "sprite-export")]
+struct Args {
+    /// 图片库路径, 支持.wzl/.wil/.Lib, wzl有idx时优先使用idx
+    library: PathBuf,
+    /// 输出目录
+    #[arg(short, long, default_value = "save")]
+    out: PathBuf,
+    #[arg(short, long, value_enum, default_value_t = Format::Png)]
+    format: Format,
+    /// 起始序号(包含)
+    #[arg(long, default_value_t = 0)]
+    start: u32,
+    /// 结束序号(不包含), 默认到最后一张
+    #[arg(long)]
+    end: Option<u32>,
+    #[arg(long, value_enum, default_value_t = Sidecar::Json)]
+    sidecar: Sidecar,
+    /// 合并为图集而不是逐张导出, 值为每页边长
+    #[arg(long)]
+    sheet: Option<u32>,
+    /// wzl使用wzx索引
+    #[arg(long)]
+    wzx: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Png,
+    Webp,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sidecar {
+    Json,
+    Csv,
+}
+
+/// 每张图片一条记录, 图集模式下记录所在页和位置
+#[derive(Serialize)]
+struct Record {
+    index: u32,
+    width: u16,
+    height: u16,
+    offset_x: i16,
+    offset_y: i16,
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    y: Option<u32>,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(&args) {
+        eprintln!("export failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    let library = open_library(&args.library, args.wzx)?;
+    let name = args.library.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+    let end = args.end.unwrap_or(u32::MAX).min(library.len() as u32);
+    let ext = match args.format {
+        Format::Png => "png",
+        Format::Webp => "webp",
+    };
+    let dir = args.out.join(&name);
+    fs::create_dir_all(&dir)?;
+
+    let mut atlas = args.sheet.map(|x| TextureAtlas::new(x, x).with_padding(1));
+    let mut records = Vec::new();
+    let mut skipped = 0;
+    for index in args.start..end {
+        let image = match library.load_image(index) {
+            Ok(x) => x,
+            Err(Error::EmptyImage) => continue,
+            Err(e) => {
+                eprintln!("{}#{}: {}", name, index, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        let record = match atlas.as_mut() {
+            Some(atlas) => match atlas.insert(index as u64, &image) {
+                Ok(entry) => Some(Record {
+                    x: Some(entry.x),
+                    y: Some(entry.y),
+                    ..record(index, &image, format!("{}_{}.{}", name, entry.page, ext))
+                }),
+                Err(e) => {
+                    eprintln!("{}#{}: {}", name, index, e);
+                    None
+                }
+            },
+            None => {
+                let file = format!("{:06}.{}", index, ext);
+                save_image(&dir.join(&file), image.width as u32, image.height as u32, &image.bytes, args.format)?;
+                Some(record(index, &image, file))
+            }
+        };
+        match record {
+            Some(x) => {
+                records.push(x);
+                if records.len() % 1000 == 0 {
+                    println!("{}: {} exported", name, records.len());
+                }
+            }
+            None => skipped += 1,
+        }
+    }
+
+    if let Some(atlas) = atlas.as_ref() {
+        for (i, page) in atlas.pages().iter().enumerate() {
+            save_image(&dir.join(format!("{}_{}.{}", name, i, ext)), page.width, page.height, &page.pixels, args.format)?;
+        }
+    }
+    let sidecar = match args.sidecar {
+        Sidecar::Json => write_json(&dir.join(format!("{}.json", name)), &records)?,
+        Sidecar::Csv => write_csv(&dir.join(format!("{}.csv", name)), &records)?,
+    };
+    println!("{}: {} exported, {} skipped, sidecar: {}", name, records.len(), skipped, sidecar.display());
+    Ok(())
+}
+
+fn record(index: u32, image: &ImageData, file: String) -> Record {
+    Record { index, width: image.width, height: image.height, offset_x: image.offset_x, offset_y: image.offset_y, file, x: None, y: None }
+}
+
+/// 按扩展名选择图片库, 扩展名不区分大小写
+fn open_library(path: &Path, wzx: bool) -> Result<Box<dyn ImageLibrary>> {
+    let ext = path.extension().map(|x| x.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    Ok(match ext.as_str() {
+        "lib" => Box::new(MLibrary::open(path)?),
+        "wil" | "wix" => Box::new(WilLibrary::open(path)?),
+        _ => {
+            let kind = if !wzx && path.with_extension("idx").exists() { IndexKind::Idx } else { IndexKind::Wzx };
+            Box::new(WzlLibrary::open(path, kind)?)
+        }
+    })
+}
+
+fn save_image(path: &Path, width: u32, height: u32, pixels: &[u8], format: Format) -> Result<()> {
+    let format = match format {
+        Format::Png => ImageFormat::Png,
+        Format::Webp => ImageFormat::WebP,
+    };
+    image::save_buffer_with_format(path, pixels, width, height, ExtendedColorType::Rgba8, format)
+        .map_err(|e| Error::Encode(e.to_string()))
+}
+
+fn write_json(path: &Path, records: &[Record]) -> Result<PathBuf> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, records).map_err(|e| Error::Encode(e.to_string()))?;
+    Ok(path.to_path_buf())
+}
+
+fn write_csv(path: &Path, records: &[Record]) -> Result<PathBuf> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "index,width,height,offset_x,offset_y,file,x,y")?;
+    for r in records {
+        let position = |x: Option<u32>| x.map(|x| x.to_string()).unwrap_or_default();
+        writeln!(writer, "{},{},{},{},{},{},{},{}", r.index, r.width, r.height, r.offset_x, r.offset_y, r.file, position(r.x), position(r.y))?;
+    }
+    writer.flush()?;
+    Ok(path.to_path_buf())
+}
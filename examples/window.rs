@@ -1,5 +1,7 @@
 use std::{env, path};
 use file::asset::{FileDesc, FileDescType, ImageAsset};
+use file::key::FileKey;
+use file::manifest::LibraryManifest;
 use ggez::conf::{WindowMode, WindowSetup};
 use ggez::{Context, event, GameError, GameResult};
 use ggez::audio::AudioContext;
@@ -69,6 +71,9 @@ fn main() -> GameResult {
 
 pub struct App {
     asset: ImageAsset,
+    hair: u16,
+    weapon: u16,
+    effect: u16,
     scale_factor: f32,
     animation: animation::PlayerAnimation,
 }
@@ -83,19 +88,16 @@ impl App {
             scale_factor = monitor.scale_factor();
             debug!("monitor: {:?}, scale: {}", monitor.size(), scale_factor);
         }
-        let mut asset = file::asset::create_default_image_asset("/Users/vinter/Dev/Mir2");
-        asset.put_file_map(4, "hum", true);
-        asset.put_file_map(5, "hair", true);
-        asset.put_file_map(6, "weapon", true);
-        asset.put_file_map(7, "humeffect", true);
-
-        let data = asset.load_image(FileDesc::KEY(9007216434610196), FileDescType::IDX).unwrap();
-        //9007216434610196
+        let manifest = LibraryManifest::from_json(include_str!("libraries.json")).unwrap();
+        let asset = file::asset::create_image_asset("/Users/vinter/Dev/Mir2", &manifest);
+        let id = |name: &str| manifest.id_of(name).unwrap();
+
+        let data = asset.load_image(FileKey::new(id("hum"), 0, 20).unwrap().into(), FileDescType::IDX).unwrap();
         // let image = RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.to_vec()).unwrap();
         let image = ggez::graphics::Image::from_pixels(ctx, data.bytes.as_ref(), ImageFormat::Rgba8UnormSrgb, data.width as u32, data.height as u32);
 
-        let animation = PlayerAnimation::new(4, 0, 1, PlayerAction::Stand, Direction::North);
-        App { asset, scale_factor: scale_factor as f32 + 0.5, animation}
+        let animation = PlayerAnimation::new(id("hum"), 0, 1, PlayerAction::Stand, Direction::North);
+        App { asset, hair: id("hair"), weapon: id("weapon"), effect: id("humeffect"), scale_factor: scale_factor as f32 + 0.5, animation}
     }
 }
 
@@ -114,7 +116,7 @@ impl EventHandler for App {
         // println!("frame: {}", frame);
         // for i in 0..4 {
         let hum_image = self.asset.load_image(FileDesc::ZONE { file: self.animation.file, number: self.animation.number, index: self.animation.now() as u32 }, FileDescType::IDX);
-        if let Some(hum) = hum_image {
+        if let Ok(hum) = hum_image {
             // println!("H:{},W:{},Len: {}", img.height, img.width, img.bytes.len());
             let image = ggez::graphics::Image::from_pixels(ctx, hum.bytes.as_ref(), ImageFormat::Rgba8UnormSrgb, hum.width as u32, hum.height as u32);
             canvas.draw(&image, DrawParam::new()
@@ -129,8 +131,8 @@ impl EventHandler for App {
             //     // .dest(vec2(500.0, 500.0)));
             //     .dest(vec2(  500.0 + hair_image.offset_x as f32 * self.scale_factor, 500.0 + hair_image.offset_y as f32 * self.scale_factor )));
         }
-        let hair_image = self.asset.load_image(FileDesc::ZONE { file: 5, number: 0, index: self.animation.hair() as u32 + 1248 }, FileDescType::IDX);
-        if let Some(hair) = hair_image {
+        let hair_image = self.asset.load_image(FileDesc::ZONE { file: self.hair, number: 0, index: self.animation.hair() as u32 + 1248 }, FileDescType::IDX);
+        if let Ok(hair) = hair_image {
             // println!("H:{},W:{},Len: {}", img.height, img.width, img.bytes.len());
             let image = ggez::graphics::Image::from_pixels(ctx, hair.bytes.as_ref(), ImageFormat::Rgba8UnormSrgb, hair.width as u32, hair.height as u32);
             // image.encode()
@@ -156,8 +158,8 @@ impl EventHandler for App {
         // });
         canvas.set_blend_mode(BlendMode::ADD);
 
-        let weapon_image = self.asset.load_image(FileDesc::ZONE { file: 6, number: 0, index: self.animation.hair() as u32 + 2496 + 416 }, FileDescType::IDX);
-        if let Some(weapon) = weapon_image {
+        let weapon_image = self.asset.load_image(FileDesc::ZONE { file: self.weapon, number: 0, index: self.animation.hair() as u32 + 2496 + 416 }, FileDescType::IDX);
+        if let Ok(weapon) = weapon_image {
             // println!("H:{},W:{},Len: {}", img.height, img.width, img.bytes.len());
             let image = ggez::graphics::Image::from_pixels(ctx, weapon.bytes.as_ref(), ImageFormat::Rgba8UnormSrgb, weapon.width as u32, weapon.height as u32);
             canvas.draw(&image, DrawParam::new()
@@ -169,8 +171,8 @@ impl EventHandler for App {
         }
 
 
-        let effect_image = self.asset.load_image(FileDesc::ZONE { file: 7, number: 0, index: self.animation.effect() as u32 + 448 }, FileDescType::IDX);
-        if let Some(effect) = effect_image {
+        let effect_image = self.asset.load_image(FileDesc::ZONE { file: self.effect, number: 0, index: self.animation.effect() as u32 + 448 }, FileDescType::IDX);
+        if let Ok(effect) = effect_image {
             // println!("H:{},W:{},Len: {}", img.height, img.width, img.bytes.len());
             let image = ggez::graphics::Image::from_pixels(ctx, effect.bytes.as_ref(), ImageFormat::Rgba8UnormSrgb, effect.width as u32, effect.height as u32);
             // let (wgpu, view) = image.wgpu();
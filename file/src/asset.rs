@@ -1,13 +1,24 @@
-use std::{fs, io};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
-use std::iter::Map;
 use std::path::{Path, PathBuf};
-use bytes::{Buf, Bytes, BytesMut};
-use flate2::{FlushDecompress, Status};
-use moka::sync::{Cache, CacheBuilder};
-use tracing::warn;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+use moka::sync::Cache;
+use tracing::{debug, warn};
+pub use crate::cache::{CacheBudget, CacheStats, ImageClass};
+use crate::cache::ImageCache;
+use crate::disk::DiskCache;
+use crate::error::{Error, Result};
+use crate::handle::{LibraryHandles, DEFAULT_HANDLE_CAPACITY};
+pub use crate::library::{ImageData, ImageLibrary};
+use crate::key::{FileKey, CACHE_KEY_MASK, FILE_KEY_MASK, MAX_FILE, MAX_NUMBER, TYPE_SHIFT};
+use crate::library::{IndexKind, WzlLibrary};
+use crate::manifest::LibraryManifest;
+use crate::map::parse_map;
+pub use crate::map::{MapInfo, Tile};
+use crate::mlib::MLibrary;
+use crate::pack::Pack;
+use crate::vfs::{not_found, Vfs, VfsLayer};
+use crate::wil::WilLibrary;
 
 
 const IMAGE_DIR: &str = "data";
@@ -15,69 +26,92 @@ const MAP_DIR: &str = "map";
 const IMAGE_FILE_SUFFIX: &str = "wzl";
 const IMAGE_IDX_SUFFIX: &str = "idx";
 const IMAGE_WZX_SUFFIX: &str = "wzx";
+const IMAGE_WIL_SUFFIX: &str = "wil";
+const IMAGE_WIX_SUFFIX: &str = "wix";
+const IMAGE_LIB_SUFFIX: &str = "Lib";
 
 const MAP_FILE_SUFFIX: &str = "map";
 
 
-/// 数据KEY格式
-/// 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000 0000
-///     |8位文件类型|8位序号   | 12位文件名称   | 32位数据文件索引
-/// 60-63位: 缓存级别
-/// 52-59位: 文件类型
-///          0: wzl
-///          1: wzx
-///          2: idx
-///          3: map
-///          4: wav
-/// 44-51位: 8位序号
-/// 32-43位: 12位文件名称编号
-/// 00-31位: 32位数据文件索引
+/// 图片请求, KEY的格式见`FileKey`, file和number超出范围时截断, 需要检查时使用`FileKey::new`
+/// KEYS, ZONES, RANGE为批量请求, 使用`ImageAsset::load_images`按请求顺序返回
+/// 批量请求作为单张使用时取第一张
 pub enum FileDesc {
     KEY (u64),
-    // KEYS (Vec<u64>),
+    KEYS (Vec<u64>),
     ZONE {file: u16, number: u16, index: u32 },
-    // ZONES {file: u16, number: u16, index: Vec<u32> },
-    ORDER {file: u16, number: u16, index: u32, count: u32 }
-
+    ZONES {file: u16, number: u16, index: Vec<u32> },
+    ORDER {file: u16, number: u16, index: u32, count: u32 },
+    /// 从start开始的连续count张, 如一个方向的全部动画帧
+    RANGE {file: u16, number: u16, start: u32, count: u32 },
 }
 
 impl FileDesc {
 
     pub fn get_file_key(&self) -> u32 {
-        self.get_map_key() & 0xFFFFF
+        self.get_map_key() & FILE_KEY_MASK
     }
 
     pub fn get_cache_key(&self) -> u64 {
         match self {
-            FileDesc::KEY(k) => {*k & ((1 << 52) -1)}
-            FileDesc::ZONE { file, number, index } => {
+            FileDesc::KEY(k) => {*k & CACHE_KEY_MASK}
+            FileDesc::KEYS(keys) => { keys.first().map(|k| *k & CACHE_KEY_MASK).unwrap_or(0) }
+            FileDesc::ZONE { index, .. } => {
                 (self.get_map_key() as u64) << 32 | *index as u64
             }
-            FileDesc::ORDER { file, number, index, count } => {
+            FileDesc::ZONES { index, .. } => {
+                (self.get_map_key() as u64) << 32 | index.first().copied().unwrap_or(0) as u64
+            }
+            FileDesc::ORDER { index, count, .. } => {
                 (self.get_map_key() as u64) << 32 | (*index + *count) as u64
             }
+            FileDesc::RANGE { start, .. } => {
+                (self.get_map_key() as u64) << 32 | *start as u64
+            }
+        }
+    }
+
+    /// 包含的全部图片的缓存KEY, 按请求顺序
+    pub fn get_cache_keys(&self) -> Vec<u64> {
+        let map_key = (self.get_map_key() as u64) << 32;
+        match self {
+            FileDesc::KEYS(keys) => keys.iter().map(|k| *k & CACHE_KEY_MASK).collect(),
+            FileDesc::ZONES { index, .. } => index.iter().map(|i| map_key | *i as u64).collect(),
+            FileDesc::RANGE { start, count, .. } => (*start..start.saturating_add(*count)).map(|i| map_key | i as u64).collect(),
+            _ => vec![self.get_cache_key()],
         }
     }
 
     fn get_map_key(&self) -> u32 {
         match self {
             FileDesc::KEY(k) => { (*k >> 32) as u32 }
-            // FileDesc::KEYS(_) => { 0 }
-            FileDesc::ZONE { file, number, .. } => { (*number as u32) << 12 | *file as u32 }
-            // FileDesc::ZONES { file, number, .. } => { (*number as u32) << 12 | *file as u32 }
-            FileDesc::ORDER { file, number, .. } => { (*number as u32) << 12 | *file as u32 }
+            FileDesc::KEYS(keys) => { keys.first().map(|k| (*k >> 32) as u32).unwrap_or(0) }
+            FileDesc::ZONE { file, number, .. } => { map_key(*file, *number) }
+            FileDesc::ZONES { file, number, .. } => { map_key(*file, *number) }
+            FileDesc::ORDER { file, number, .. } => { map_key(*file, *number) }
+            FileDesc::RANGE { file, number, .. } => { map_key(*file, *number) }
         }
     }
 
 }
 
-/// 52-59位: 文件类型
+impl From<FileKey> for FileDesc {
+    fn from(key: FileKey) -> Self {
+        FileDesc::KEY(key.raw())
+    }
+}
+
+fn map_key(file: u16, number: u16) -> u32 {
+    ((number & MAX_NUMBER) as u32) << 12 | (file & MAX_FILE) as u32
+}
+
+/// 56-59位: 文件类型
 ///          0: wzl
 ///          1: wzx
 ///          2: idx
 ///          3: map
 ///          4: wav
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FileDescType {
     WZL,
     WZX,
@@ -87,180 +121,309 @@ pub enum FileDescType {
 }
 
 impl FileDescType {
-    fn get_value(&self) -> u64 {
-        match self {
+    pub(crate) fn get_value(&self) -> u64 {
+        let value: u64 = match self {
             FileDescType::WZL => {0}
-            FileDescType::WZX => {1 << 52}
-            FileDescType::IDX => {2 << 52}
-            FileDescType::MAP => {3 << 52}
-            FileDescType::WAV => {4 << 52}
-        }
+            FileDescType::WZX => {1}
+            FileDescType::IDX => {2}
+            FileDescType::MAP => {3}
+            FileDescType::WAV => {4}
+        };
+        value << TYPE_SHIFT
     }
-}
 
-#[derive(Clone, Default)]
-pub struct ImageData {
-    pub width: u16,
-    pub height: u16,
-    pub offset_x: i16,
-    pub offset_y: i16,
-    pub bytes: Bytes,
-}
-
-impl ImageData {
-    pub fn from(src: &[u8]) -> Self {
-        Self::from_head_data(&src[..16], &src[16..])
-    }
-
-    pub fn from_head_data(head: &[u8], data: &[u8]) -> Self {
-        let mut body = head;
-        let pixel = body.get_u8();
-        let _compress = body.get_u8();
-        let _reserve = body.get_u8();
-        let _compress_level = body.get_u8();
-
-        let width = body.get_u16_le();
-        let height = body.get_u16_le();
-        let offset_x = body.get_i16_le();
-        let offset_y = body.get_i16_le();
-        let length = body.get_u32_le();
-
-        // if length == 0 && data.len() > 0 {
-        //     length = data.len() as u32;
-        // }
-
-        if length == 0 {
-            let bytes = if data.len() > 0 {
-                Bytes::from(byte_to_rgba(pixel, width as usize, height as usize, data))
-            } else { Bytes::new() };
-            Self { width, height, offset_x, offset_y, bytes }
-        } else {
-            let x = deflate_image(data, width as u32 * height as u32);
-            let data = byte_to_rgba(pixel, width as usize, height as usize, &x[..]);
-            Self { width, height, offset_x, offset_y, bytes: Bytes::from(data) }
+    pub(crate) fn from_value(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(FileDescType::WZL),
+            1 => Some(FileDescType::WZX),
+            2 => Some(FileDescType::IDX),
+            3 => Some(FileDescType::MAP),
+            4 => Some(FileDescType::WAV),
+            _ => None,
         }
     }
 }
 
+/// 图片资源, 所有方法只需要&self, 可以用`Arc<ImageAsset>`在渲染线程和加载线程之间共享
 pub struct ImageAsset {
-    dir: String,
-    file_map: HashMap<u32, String>,
-    index_map: HashMap<u32, Vec<u32>>,
-    wzx_map: HashMap<u32, Vec<u32>>,
-    image_cache: Cache<u64, ImageData>
+    vfs: Vfs,
+    file_map: RwLock<HashMap<u32, String>>,
+    /// 按文件和索引类型打开的图片库, 只缓存打开成功的图片库, 失败时下次请求重新打开
+    /// 多个线程同时请求同一文件时只打开一次, 其他线程等待结果
+    library_map: Cache<u32, Arc<dyn ImageLibrary>>,
+    /// 解码失败的结果也会缓存(IO错误除外), 避免每帧重复读取
+    image_cache: ImageCache,
+    /// 已映射的wzl文件, 所有wzl图片库共用
+    handles: Arc<LibraryHandles>,
+    /// 内存缓存未命中时先查找磁盘缓存, 解码成功的图片写入磁盘缓存
+    disk_cache: Option<DiskCache>,
 }
 
 impl ImageAsset {
 
     pub fn new(dir: String) -> Self {
+        Self::with_handle_capacity(dir, DEFAULT_HANDLE_CAPACITY)
+    }
+
+    /// handle_capacity为同时保持映射的wzl文件数量
+    pub fn with_handle_capacity(dir: String, handle_capacity: usize) -> Self {
+        Self::with_vfs(Vfs::new(dir), handle_capacity)
+    }
+
+    /// 从分层目录中查找图片库, 补丁目录中的文件优先
+    pub fn with_vfs(vfs: Vfs, handle_capacity: usize) -> Self {
 
         ImageAsset {
-            dir,
-            file_map: HashMap::with_capacity(1024),
-            index_map: HashMap::with_capacity(1024),
-            wzx_map: HashMap::with_capacity(1024),
-            image_cache: Cache::new(10_000)
+            vfs,
+            file_map: RwLock::new(HashMap::with_capacity(1024)),
+            library_map: Cache::builder().initial_capacity(1024).build(),
+            image_cache: ImageCache::new(CacheBudget::default()),
+            handles: Arc::new(LibraryHandles::new(handle_capacity)),
+            disk_cache: None,
         }
     }
 
-    pub fn put_file_map(&mut self, key: u32, value: &str, preload_index: bool) {
-        self.file_map.insert(key, value.to_string());
-        if preload_index {
-            self.index_map.insert(key, self.load_idx(value, 0, IMAGE_IDX_SUFFIX));
-            self.wzx_map.insert(key, self.load_idx(value, 12, IMAGE_WZX_SUFFIX));
+    /// 替换缓存预算, 已缓存的图片和统计会清除, 文件分类保留
+    pub fn with_cache_budget(mut self, budget: CacheBudget) -> Self {
+        let cache = ImageCache::new(budget);
+        for (key, class) in self.image_cache.classes() {
+            cache.set_class(key, class);
         }
+        self.image_cache = cache;
+        self
     }
 
-    fn load_idx(&self, name: &str, skip: usize, extension: &str) -> Vec<u32> {
-        let path = Path::new(self.dir.as_str()).join(IMAGE_DIR).join(name).with_extension(extension);
-        if let Ok(p) = fs::read(path) {
-            p.chunks(4).skip(skip).map(|x| {
-                let t: [u8; 4] = [x[0], x[1], x[2], x[3]];
-                u32::from_le_bytes(t)
-            }).collect()
-        } else {
-            Vec::new()
-        }
+    /// 使用磁盘缓存, 只缓存有源文件的图片库, 资源包和自定义图片库不使用
+    pub fn with_disk_cache(mut self, cache: DiskCache) -> Self {
+        self.disk_cache = Some(cache);
+        self
+    }
 
+    pub fn disk_cache(&self) -> Option<&DiskCache> {
+        self.disk_cache.as_ref()
     }
 
-    pub fn load_image(&mut self, desc: FileDesc, typ: FileDescType) -> Option<ImageData> {
-        let type_value = typ.get_value();
-        let file_key = desc.get_file_key();
-        let key = desc.get_cache_key() | type_value;
-        if let Some(value) = self.image_cache.get(&key) {
-            if value.bytes.len() <= 0 { return None }
-            return Some(value);
+    /// 设置文件的缓存分类, 未设置的文件使用`ImageClass::Other`
+    /// 带序号的文件(如objects2)未单独设置时使用不带序号的分类, 分类改变时已缓存的图片会清除
+    pub fn set_file_class(&self, key: u32, class: ImageClass) {
+        self.image_cache.set_class(key, class);
+    }
+
+    /// 指定分类的缓存统计
+    pub fn cache_stats(&self, class: ImageClass) -> CacheStats {
+        self.image_cache.stats(class)
+    }
+
+    /// 所有分类合计的缓存统计
+    pub fn total_cache_stats(&self) -> CacheStats {
+        self.image_cache.total_stats()
+    }
+
+    /// preload_index为true时立即打开idx和wzx索引
+    /// 该文件已打开的图片库和已缓存的图片会清除, 之后按新的文件名重新打开
+    /// 不带序号的文件还会清除由原文件名生成的带序号文件名, 以及未单独注册的带序号文件的缓存
+    pub fn put_file_map(&self, key: u32, value: &str, preload_index: bool) {
+        let base = key & MAX_FILE as u32 == key;
+        let (derived, registered) = {
+            let mut file_map = write(&self.file_map);
+            let old = file_map.insert(key, value.to_string());
+            let derived: Vec<u32> = match old {
+                Some(old) if base => file_map.iter()
+                    .filter(|(k, v)| **k != key && **k & MAX_FILE as u32 == key && **v == format!("{}{}", old, **k >> 12))
+                    .map(|(k, _)| *k).collect(),
+                _ => Vec::new(),
+            };
+            for k in &derived {
+                file_map.remove(k);
+            }
+            let registered: Vec<u32> = file_map.keys().copied().filter(|k| *k != key).collect();
+            (derived, registered)
+        };
+        for file_key in derived.iter().chain([&key]) {
+            for typ in [FileDescType::WZL, FileDescType::WZX, FileDescType::IDX] {
+                self.library_map.invalidate(&library_key(*file_key, typ));
+            }
+        }
+        self.image_cache.invalidate_files(|k| k == key || (base && k & MAX_FILE as u32 == key && !registered.contains(&k)));
+        if preload_index {
+            let _ = self.get_library(key, FileDescType::IDX);
+            let _ = self.get_library(key, FileDescType::WZX);
         }
+    }
 
-        let idx_key = file_key | (type_value >> 32) as u32;
-        self.load_index_and_image(file_key, idx_key, key, typ);
-        if let Some(value) = self.image_cache.get(&key) {
-            if value.bytes.len() <= 0 { return None }
-            return Some(value);
+    /// 注册清单中的所有图片库和缓存分类, 带序号的文件按清单中的范围注册
+    pub fn put_manifest(&self, manifest: &LibraryManifest) {
+        for entry in &manifest.libraries {
+            for (number, name) in entry.files() {
+                let key = entry.file_key(number);
+                self.set_file_class(key, entry.category.cache_class());
+                self.put_file_map(key, &name, entry.preload);
+            }
         }
-        self.image_cache.insert(key, ImageData::default());
+    }
 
-        None
+    /// 注册自定义图片库, 替换该文件已打开的图片库, 已缓存的图片不会清除
+    pub fn put_library(&self, key: u32, typ: FileDescType, library: Arc<dyn ImageLibrary>) {
+        self.library_map.insert(library_key(key, typ), library);
     }
 
-    fn load_index_and_image(&mut self, file_key: u32, index_key: u32, cache_key: u64, typ: FileDescType) {
-        if let Some(file_name) = self.get_file_name(file_key) {
-            let map = if typ == FileDescType::IDX { &self.index_map} else { &self.wzx_map };
-            let suffix = if typ == FileDescType::IDX { IMAGE_IDX_SUFFIX} else { IMAGE_WZX_SUFFIX };
-            if !map.contains_key(&index_key) {
-                let file_idx_value = self.load_idx(file_name.as_str(), 0, suffix);
-                if typ == FileDescType::IDX {
-                    self.index_map.insert(index_key, file_idx_value);
-                } else {
-                    self.wzx_map.insert(index_key, file_idx_value);
-                }
+    /// 图片不存在或无法解码时返回原因, 空图片返回`Error::EmptyImage`
+    /// 多个线程同时请求同一张未缓存的图片时只解码一次
+    pub fn load_image(&self, desc: FileDesc, typ: FileDescType) -> Result<ImageData> {
+        let key = desc.get_cache_key() | typ.get_value();
+        if let Some(value) = self.image_cache.get(key, true) {
+            return value;
+        }
+
+        let library = match self.get_library(desc.get_file_key(), typ) {
+            Ok(x) => x,
+            Err(e) => return self.finish(key, Err(e)),
+        };
+        let counters = self.image_cache.counters(key);
+        let (disk_key, index) = (library_key(desc.get_file_key(), typ), (key & 0xFFFFFFFF) as u32);
+        self.image_cache.get_with(key, || {
+            let sources = self.disk_sources(library.as_ref());
+            if let Some(image) = self.read_disk(disk_key, index, &sources) {
+                counters.record_disk_hits(1);
+                return Ok(image);
             }
-            let map = if typ == FileDescType::IDX { &self.index_map} else { &self.wzx_map };
-                let image_key = if let Some(ik) = map.get(&index_key) {
-                    if let Some(v) = ik.get((cache_key & 0xFFFFFFFF) as usize) {
-                        if let Some(len) = ik.get(((cache_key + 1) & 0xFFFFFFFF) as usize) {
-                            Some((*v, *len - *v))
-                        } else {
-                            Some((*v, 16))
+            let start = Instant::now();
+            let image = library.load_image(index);
+            counters.record_decode(1, start.elapsed());
+            let image = image?;
+            debug!("key: {}, w: {}, h: {}, x: {}, y: {}", key, image.width, image.height, image.offset_x, image.offset_y);
+            self.write_disk(disk_key, index, &sources, &image);
+            Ok(image)
+        })
+    }
+
+    /// 批量加载, 结果与`FileDesc::get_cache_keys`的顺序一致
+    /// 未缓存的图片按文件分组, 每个图片库按数据位置顺序读取一遍
+    pub fn load_images(&self, desc: FileDesc, typ: FileDescType) -> Vec<Result<ImageData>> {
+        let type_value = typ.get_value();
+        let keys = desc.get_cache_keys();
+        let mut result: Vec<Option<Result<ImageData>>> = keys.iter().map(|k| self.image_cache.get(k | type_value, true)).collect();
+
+        let mut groups: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, key) in keys.iter().enumerate().filter(|(i, _)| result[*i].is_none()) {
+            groups.entry(((key >> 32) as u32) & FILE_KEY_MASK).or_default().push(i);
+        }
+        for (file_key, positions) in groups {
+            let values: Vec<Result<ImageData>> = match self.get_library(file_key, typ) {
+                Ok(library) => {
+                    let (disk_key, counters) = (library_key(file_key, typ), self.image_cache.counters(keys[positions[0]]));
+                    let sources = self.disk_sources(library.as_ref());
+                    let indices: Vec<u32> = positions.iter().map(|i| (keys[*i] & 0xFFFFFFFF) as u32).collect();
+                    let mut values: Vec<Option<Result<ImageData>>> = indices.iter().map(|x| self.read_disk(disk_key, *x, &sources).map(Ok)).collect();
+                    let missing: Vec<usize> = (0..indices.len()).filter(|j| values[*j].is_none()).collect();
+                    counters.record_disk_hits((indices.len() - missing.len()) as u64);
+
+                    let start = Instant::now();
+                    let decoded = library.load_images(&missing.iter().map(|j| indices[*j]).collect::<Vec<_>>());
+                    counters.record_decode(missing.len() as u64, start.elapsed());
+                    for (j, value) in missing.into_iter().zip(decoded) {
+                        if let Ok(image) = value.as_ref() {
+                            self.write_disk(disk_key, indices[j], &sources, image);
                         }
-                    } else { None }
-                } else { None };
+                        values[j] = Some(value);
+                    }
+                    values.into_iter().map(|x| x.unwrap_or(Err(Error::EmptyImage))).collect()
+                }
+                Err(e) => positions.iter().map(|_| Err(e.clone())).collect(),
+            };
+            for (i, value) in positions.into_iter().zip(values) {
+                result[i] = Some(self.finish(keys[i] | type_value, value));
+            }
+        }
+        result.into_iter().map(|x| x.unwrap_or(Err(Error::EmptyImage))).collect()
+    }
 
-                self.get_bytes_by_image_key(image_key, file_name.as_str(), cache_key);
+    /// 只统计命中, 未命中时由之后的load_image统计
+    pub(crate) fn get_cached(&self, desc: &FileDesc, typ: FileDescType) -> Option<Result<ImageData>> {
+        self.image_cache.get(desc.get_cache_key() | typ.get_value(), false)
+    }
+
+    /// 没有磁盘缓存时不读取源文件列表
+    fn disk_sources(&self, library: &dyn ImageLibrary) -> Vec<PathBuf> {
+        if self.disk_cache.is_some() { library.sources() } else { Vec::new() }
+    }
+
+    fn read_disk(&self, library: u32, index: u32, sources: &[PathBuf]) -> Option<ImageData> {
+        self.disk_cache.as_ref()?.get(library, sources, index)
+    }
+
+    fn write_disk(&self, library: u32, index: u32, sources: &[PathBuf], image: &ImageData) {
+        if let Some(cache) = self.disk_cache.as_ref() {
+            cache.put(library, sources, index, image);
         }
     }
 
-    fn get_bytes_by_image_key(&mut self, image_key: Option<(u32, u32)>, file_name: &str, key: u64) {
-        if let Some(v) = image_key {
-            if let Some(x) = self.get_file_bytes(file_name, v.0, v.1) {
-                println!("idx: {}, {}", v.0, v.1);
-                println!("name: {}, key: {}, w: {}, h: {}, x: {}, y: {}", file_name, key, x.width, x.height, x.offset_x, x.offset_y);
-                self.image_cache.insert(key, x);
-            }
+    /// 缓存解码结果, IO错误不缓存
+    fn finish(&self, key: u64, value: Result<ImageData>) -> Result<ImageData> {
+        if !matches!(value, Err(Error::Io(_))) {
+            self.image_cache.insert(key, value.clone());
         }
+        value
     }
 
-    fn get_file_bytes(&self, file_name: &str, seek: u32, length: u32) -> Option<ImageData> {
-        if seek == 0 { return None }
-        let path = Path::new(self.dir.as_str()).join(IMAGE_DIR).join(file_name).with_extension(IMAGE_FILE_SUFFIX);
-        // println!("file: {}, seek:{}", file_name, seek);
-        if let Ok(f) = File::open(path) {
-            let mut buf = BufReader::new(f);
-            return read_image_data(&mut buf, seek, length);
+    /// 返回文件对应的图片库, 未打开时按文件类型打开
+    pub fn get_library(&self, file_key: u32, typ: FileDescType) -> Result<Arc<dyn ImageLibrary>> {
+        self.library_map.try_get_with(library_key(file_key, typ), || {
+            self.get_file_name(file_key).ok_or(Error::UnknownFile(file_key))
+                .and_then(|name| self.open_library(name.as_str(), typ))
+        }).map_err(|e| (*e).clone())
+    }
+
+    pub fn vfs(&self) -> &Vfs {
+        &self.vfs
+    }
+
+    /// 文件名以.Lib结尾时使用Crystal图片库, 否则在同一层中依次查找wzl, wil和Lib
+    /// 索引和数据文件必须来自同一层, 上层的任意一种格式都会覆盖下层, 资源包和目录的查找顺序相同
+    fn open_library(&self, file_name: &str, typ: FileDescType) -> Result<Arc<dyn ImageLibrary>> {
+        let name = Path::new(IMAGE_DIR).join(file_name);
+        if name.extension().is_some_and(|x| x.eq_ignore_ascii_case(IMAGE_LIB_SUFFIX)) {
+            let entry = self.vfs.locate(&name).ok_or_else(|| not_found(&name))?;
+            return match &self.vfs.layers()[entry.layer] {
+                VfsLayer::Dir(_) => Ok(Arc::new(MLibrary::open(entry.path)?)),
+                VfsLayer::Pack(pack) => Ok(Arc::new(MLibrary::from_bytes(pack.read(entry.path)?)?)),
+            };
         }
-        None
+        let (kind, index_suffix) = if typ == FileDescType::IDX { (IndexKind::Idx, IMAGE_IDX_SUFFIX) } else { (IndexKind::Wzx, IMAGE_WZX_SUFFIX) };
+        for (layer, source) in self.vfs.layers().iter().enumerate() {
+            if let VfsLayer::Pack(pack) = source {
+                if let Some(library) = open_pack_library(pack, &name, kind, index_suffix) {
+                    debug!("library: {}, pack: {:?}", file_name, pack.path());
+                    return library;
+                }
+                continue;
+            }
+            let resolve = |suffix: &str| self.vfs.resolve_in(layer, name.with_extension(suffix));
+            if let Some(wzl) = resolve(IMAGE_FILE_SUFFIX) {
+                let index = resolve(index_suffix).ok_or_else(|| not_found(&name.with_extension(index_suffix)))?;
+                debug!("library: {}, layer: {}", file_name, layer);
+                return Ok(Arc::new(WzlLibrary::open_files(wzl, index, kind, self.handles.clone())?));
+            }
+            if let Some(wil) = resolve(IMAGE_WIL_SUFFIX) {
+                let wix = resolve(IMAGE_WIX_SUFFIX).ok_or_else(|| not_found(&name.with_extension(IMAGE_WIX_SUFFIX)))?;
+                return Ok(Arc::new(WilLibrary::open_files(wil, wix)?));
+            }
+            if let Some(lib) = resolve(IMAGE_LIB_SUFFIX) {
+                return Ok(Arc::new(MLibrary::open(lib)?));
+            }
+        }
+        Err(not_found(&name.with_extension(IMAGE_FILE_SUFFIX)).into())
     }
 
-    fn get_file_name(&mut self, file_key: u32) -> Option<String> {
-        if let Some(v) = self.file_map.get(&file_key) {
+    fn get_file_name(&self, file_key: u32) -> Option<String> {
+        if let Some(v) = read(&self.file_map).get(&file_key) {
              return Some(v.to_string());
         }
 
-        let file_key1 = file_key & 0xFFF;
-        if let Some(v) = self.file_map.get(&file_key1) {
-            let v = format!("{}{}", v, file_key >> 12);
-            self.file_map.insert(file_key, v.clone());
+        let file_key1 = file_key & MAX_FILE as u32;
+        let v = read(&self.file_map).get(&file_key1).map(|v| format!("{}{}", v, file_key >> 12));
+        if let Some(v) = v {
+            write(&self.file_map).insert(file_key, v.clone());
             return Some(v);
         }
 
@@ -269,203 +432,234 @@ impl ImageAsset {
 
 }
 
-pub fn create_map(base_dir: &str, name: &str) -> Option<MapInfo> {
-    // println!("read_map_file: {}", path);
-    let path = Path::new(base_dir).join(MAP_DIR).join(name).with_extension(MAP_FILE_SUFFIX);
-    println!("path: {:?}", path);
-    if let Ok(file) = fs::read(path) {
-        let mut buf = &file[..52];
-        let width = buf.get_u16_le() as u32;
-        let height = buf.get_u16_le() as u32;
-
-        let mut buf = &file[52..];
-        let file_size = buf.len();
-        let step = ((file_size as u32) / (width * height));
-        let tiles: Vec<Tile> = buf.chunks(step as usize).map(|x| {
-            Tile::from(x)
-        }).collect();
-        return Some(MapInfo {width, height, name: name.to_string(), tiles})
-    }
-    None
-    // let name = path.file_name().unwrap().to_str().unwrap().to_string();
-    // let file_size = path.metadata().unwrap().len();
-    // let mut file = File::open(path).unwrap();
-    // let mut header = [0u8; 52];
-    // file.read(&mut header).unwrap();
-    // let mut header = &header[..];
-    // let width = header.get_u16_le() as u32;
-    // let height = header.get_u16_le() as u32;
-    // let length = ((file_size as u32 - 52) / (width * height)) as usize;
-    // let mut body = Vec::with_capacity(file_size as usize -52);
-    // file.read_to_end(&mut body).unwrap();
-    // // let mut reader = BufReader::new(file);
-    // let mut tiles = Vec::with_capacity((width * height) as usize);
-    // for i in 0..width * height {
-    //     let start = i as usize * length;
-    //     let end = start + length;
-    //     let tile = Tile::from(&body[start..end]);
-    //     tiles.push(tile);
-    // }
+/// 在资源包中依次查找wzl, wil和Lib, 都不存在时返回None
+fn open_pack_library(pack: &Pack, name: &Path, kind: IndexKind, index_suffix: &str) -> Option<Result<Arc<dyn ImageLibrary>>> {
+    let wzl = name.with_extension(IMAGE_FILE_SUFFIX);
+    let wil = name.with_extension(IMAGE_WIL_SUFFIX);
+    let lib = name.with_extension(IMAGE_LIB_SUFFIX);
+    let open = || -> Result<Arc<dyn ImageLibrary>> {
+        if pack.contains(&wzl) {
+            let index = pack.read(name.with_extension(index_suffix))?;
+            Ok(Arc::new(WzlLibrary::from_bytes(pack.read(&wzl)?, &index, kind)?))
+        } else if pack.contains(&wil) {
+            let wix = pack.read(name.with_extension(IMAGE_WIX_SUFFIX))?;
+            Ok(Arc::new(WilLibrary::from_bytes(pack.read(&wil)?, &wix)?))
+        } else {
+            Ok(Arc::new(MLibrary::from_bytes(pack.read(&lib)?)?))
+        }
+    };
+    [&wzl, &wil, &lib].iter().any(|x| pack.contains(x)).then(open)
+}
 
+/// 同一文件的idx和wzx索引分别打开
+fn library_key(file_key: u32, typ: FileDescType) -> u32 {
+    file_key | (typ.get_value() >> 32) as u32
 }
 
-pub struct MapInfo {
-    pub width: u32,
-    pub height: u32,
-    pub name: String,
-    pub tiles: Vec<Tile>
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
 }
 
-#[derive(Debug, Clone)]
-pub struct Tile {
-    pub back: u16,
-    pub middle: u16,
-    pub objects: u16,
-    pub door_idx: u8,
-    pub door_offset: u8,
-    pub frame: u8,
-    pub tick: u8,
-    pub file_idx: u8,
-    pub light: u8,
-    pub tile_idx: u8,
-    pub middle_idx: u8,
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
 }
 
-impl Tile {
-    pub fn from(bytes: &[u8]) -> Self {
-        let len = bytes.len();
-        let mut bytes = bytes;
-        let back = bytes.get_u16_le();
-        let middle = bytes.get_u16_le();
-        let objects = bytes.get_u16_le();
-        let door_idx = bytes.get_u8();
-        let door_offset = bytes.get_u8();
-        let frame = bytes.get_u8();
-        let tick = bytes.get_u8();
-        let file_idx = bytes.get_u8();
-        let light = bytes.get_u8();
-        let tile_idx = if len > 12 { bytes.get_u8() } else { 0 };
-        let middle_idx = if len > 13 { bytes.get_u8() } else { 0 };
-
-        Tile { back, middle, objects, door_idx, door_offset, frame, tick, file_idx, light, tile_idx, middle_idx }
-    }
+pub fn create_map(base_dir: &str, name: &str) -> Result<MapInfo> {
+    create_map_from(&Vfs::new(base_dir), name)
+}
+
+/// 从分层目录中读取地图, 补丁目录中的地图优先
+pub fn create_map_from(vfs: &Vfs, name: &str) -> Result<MapInfo> {
+    let path = Path::new(MAP_DIR).join(name).with_extension(MAP_FILE_SUFFIX);
+    debug!("path: {:?}", vfs.locate(&path));
+    let file = vfs.read(path)?;
+    parse_map(name.to_string(), &file, None)
 }
 
 
 pub fn create_default_image_asset(dir: &str) -> ImageAsset {
-    let mut asset = ImageAsset::new(dir.to_string());
-    asset.put_file_map(1, "tiles", true);
-    asset.put_file_map(2, "smTiles", true);
-    asset.put_file_map(3, "objects", true);
+    create_image_asset(dir, &LibraryManifest::default())
+}
+
+/// 按清单注册图片库, 缺少的文件只记录日志
+pub fn create_image_asset(dir: &str, manifest: &LibraryManifest) -> ImageAsset {
+    let asset = ImageAsset::new(dir.to_string());
+    for name in manifest.check(asset.vfs()) {
+        warn!("library not found: {}", name);
+    }
+    asset.put_manifest(manifest);
 
     asset
 }
 
-fn read_image_data(reader: &mut BufReader<File>, seek: u32, length: u32) -> Option<ImageData> {
-    if reader.seek(SeekFrom::Start(seek as u64)).is_err() {
-        return None;
-    }
-
-    let mut data = vec![0;length as usize];
-    reader.seek(SeekFrom::Start(seek as u64)).unwrap();
-    read_buffer(reader, &mut data[..]);
-    println!("{:X?}", data.as_slice());
-    if length <= 16 {
-        let mut x = &data[12..];
-        let x = x.get_u32_le();
-        if x > 0 {
-            let mut i = vec![0; x as usize];
-            read_buffer(reader, &mut i[..]);
-            return Some(ImageData::from_head_data(&data[..], &i[..]))
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use bytes::{BufMut, Bytes};
+    use flate2::write::GzEncoder;
+    use crate::library::MemoryLibrary;
+    use crate::pack::{Pack, PackBuilder};
+    use crate::pixel::PALETTE_RGBA;
+    use crate::writer::{Pixels, PixelFormat, SourceImage, WzlWriter};
+    use super::*;
+
+    /// 在dir/data下写入只有一张图片的wzl, 图片宽度为width
+    fn write_library(dir: &Path, name: &str, width: u16) {
+        let data = dir.join(IMAGE_DIR);
+        fs::create_dir_all(&data).unwrap();
+        let mut writer = WzlWriter::new();
+        let image = SourceImage { width, height: 1, offset_x: 0, offset_y: 0, pixels: Pixels::Rgba(vec![255; width as usize * 4]) };
+        writer.push(&image, PixelFormat::Argb32).unwrap();
+        writer.write(data.join(name).to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn open_errors_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let asset = ImageAsset::new(dir.path().to_str().unwrap().to_string());
+        assert!(matches!(asset.get_library(1, FileDescType::WZX), Err(Error::UnknownFile(1))));
+        asset.put_file_map(1, "tiles", false);
+        assert!(matches!(asset.get_library(1, FileDescType::WZX), Err(Error::Io(_))));
+
+        write_library(dir.path(), "tiles", 2);
+        assert_eq!(asset.get_library(1, FileDescType::WZX).unwrap().len(), 1);
+        let image = asset.load_image(FileDesc::ZONE { file: 1, number: 0, index: 0 }, FileDescType::WZX).unwrap();
+        assert_eq!(image.width, 2);
+    }
+
+    #[test]
+    fn put_file_map_reopens_library() {
+        let dir = tempfile::tempdir().unwrap();
+        write_library(dir.path(), "tiles", 2);
+        write_library(dir.path(), "smtiles", 3);
+        write_library(dir.path(), "tiles3", 4);
+        write_library(dir.path(), "smtiles3", 5);
+        write_library(dir.path(), "objects", 6);
+        let asset = ImageAsset::new(dir.path().to_str().unwrap().to_string());
+        let width = |file: u16, number: u16| asset.load_image(FileDesc::ZONE { file, number, index: 0 }, FileDescType::WZX).map(|x| x.width);
+        // 映射之前的错误结果已缓存
+        assert!(matches!(width(1, 0), Err(Error::UnknownFile(1))));
+        assert!(matches!(width(1, 3), Err(Error::UnknownFile(_))));
+        asset.put_library(1, FileDescType::WZX, Arc::new(MemoryLibrary::new()));
+        assert_eq!(asset.get_library(1, FileDescType::WZX).unwrap().len(), 0);
+
+        asset.put_file_map(1, "tiles", true);
+        assert_eq!(asset.get_library(1, FileDescType::WZX).unwrap().header(0).unwrap().width, 2);
+        assert_eq!((width(1, 0).unwrap(), width(1, 3).unwrap()), (2, 4));
+        // 单独注册的带序号文件不受不带序号文件的影响
+        asset.put_file_map(2 << 12 | 1, "objects", false);
+        assert_eq!(width(1, 2).unwrap(), 6);
+
+        asset.put_file_map(1, "smtiles", false);
+        for typ in [FileDescType::WZX, FileDescType::IDX] {
+            assert_eq!(asset.get_library(1, typ).unwrap().header(0).unwrap().width, 3);
         }
+        assert_eq!((width(1, 0).unwrap(), width(1, 3).unwrap(), width(1, 2).unwrap()), (3, 5, 6));
+        assert_eq!(asset.get_file_name(3 << 12 | 1).unwrap(), "smtiles3");
     }
-    Some(ImageData::from(&data[..]))
-}
 
-fn read_buffer(reader: &mut BufReader<File>, buffer: &mut [u8]) {
-    let length = buffer.len();
-    let len = reader.read(buffer).unwrap();
-    if len < length {
-        reader.read(&mut buffer[len..]).unwrap();
+    /// 一张2x1的8位图片, 使用内置调色板
+    fn wil_files() -> (Vec<u8>, Vec<u8>) {
+        let mut wil = vec![0u8; 44];
+        wil.put_u32_le(1);
+        wil.put_u32_le(256);
+        wil.put_u32_le(0);
+        wil.put_slice(&[2, 0, 1, 0, 0, 0, 0, 0, 5, 6, 0, 0]);
+        let mut wix = vec![0u8; 44];
+        wix.put_u32_le(1);
+        wix.put_u32_le(56);
+        (wil, wix)
     }
-}
 
-fn deflate_image(input: &[u8], size: u32) -> Vec<u8> {
-    let capacity = size * 4;
-    let mut rs: Vec<u8> = Vec::with_capacity(capacity as usize);
-    let status = flate2::Decompress::new(true).decompress_vec(input, &mut rs, FlushDecompress::Finish).unwrap();
-    if status != Status::StreamEnd {
-        warn!("input: {}, output: {}, status: {:?}, size: {}, size*2: {}", input.len(), rs.len(), status, size, size *2);
-        // return deflate_image(input, size * 2);
+    /// 一张1x1的Crystal图片
+    fn lib_file() -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[10, 20, 30, 255]).unwrap();
+        let body = encoder.finish().unwrap();
+        let mut lib = Vec::new();
+        lib.put_u32_le(2);
+        lib.put_u32_le(1);
+        lib.put_u32_le(12);
+        lib.put_slice(&[1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        lib.put_u32_le(body.len() as u32);
+        lib.put_slice(&body);
+        lib
     }
-    rs
-}
 
-fn byte_to_rgba(pixel: u8, width: usize, height: usize, bytes: &[u8]) -> Vec<u8> {
-
-    if pixel == 3 {
-        let mut result = Vec::with_capacity(bytes.len() * 4);
-        let new_width = bytes.len() / height;
-        let n_width = (width + 3) / 4 * 4;
-        let n_width = if n_width > new_width { new_width } else { n_width };
-        let n_width = if new_width > n_width { width } else { n_width };
-        for i in 0..height {
-            for j in 0..width {
-                let x = bytes[(height - i - 1) * n_width + j] as usize * 4;
-                result.extend_from_slice(&PALETTE_RGBA[x..x+4]);
-            }
+    #[test]
+    fn pack_layer_probes_all_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        write_library(dir.path(), "wil", 5);
+        let (wil, wix) = wil_files();
+        let mut builder = PackBuilder::new();
+        builder.add_bytes("data/wil.wil", wil);
+        builder.add_bytes("data/wil.wix", wix);
+        builder.add_bytes("data/crystal.lib", lib_file());
+        let path = dir.path().join("test.icmir");
+        builder.write(&path).unwrap();
+
+        let vfs = Vfs::new(dir.path()).with_pack(Pack::open(&path).unwrap());
+        let asset = ImageAsset::with_vfs(vfs, 4);
+        asset.put_file_map(1, "wil", false);
+        asset.put_file_map(2, "crystal", false);
+        asset.put_file_map(3, "Crystal.Lib", false);
+
+        // 资源包中的wil覆盖下层目录中的wzl
+        let image = asset.load_image(FileDesc::ZONE { file: 1, number: 0, index: 0 }, FileDescType::WZX).unwrap();
+        assert_eq!(image.width, 2);
+        assert_eq!(&image.bytes[..], &[&PALETTE_RGBA[20..24], &PALETTE_RGBA[24..28]].concat()[..]);
+        assert!(asset.get_library(1, FileDescType::WZX).unwrap().sources().is_empty());
+        for file in [2, 3] {
+            let image = asset.load_image(FileDesc::ZONE { file, number: 0, index: 0 }, FileDescType::WZX).unwrap();
+            assert_eq!(&image.bytes[..], &[30, 20, 10, 255]);
         }
-        return result;
-    } else {
-        let mut result = Vec::with_capacity(bytes.len() * 2);
-        let new_width = bytes.len() / height / 2;
-        let n_width = (width + 3) / 4 * 4;
-        let n_width = if n_width > new_width { new_width } else { n_width };
-        let n_width = if new_width > n_width { width } else { n_width };
-        for i in 0..height {
-            for j in 0..width {
-                let p = (height - i - 1) * n_width * 2 + j * 2;
-                result.push(bytes[p + 1] & 0xF8);
-                result.push((((bytes[p + 1] & 0x7) << 3) | (bytes[p] >> 5)) * 4);
-                result.push((bytes[p] & 0x1F) * 8);
-                result.push(if bytes[p] == 0 && bytes[p + 1] == 0 { 0 } else { 255 });
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        const FILES: u16 = 4;
+        const IMAGES: u32 = 16;
+        let dir = tempfile::tempdir().unwrap();
+        let asset = Arc::new(ImageAsset::new(dir.path().to_str().unwrap().to_string()));
+        for file in 1..=FILES {
+            let images = (0..IMAGES).map(|i| (i % 5 != 4).then(|| ImageData {
+                width: file * 100 + i as u16, height: 1, offset_x: 0, offset_y: 0, bytes: Bytes::from(vec![file as u8; 4]),
+            })).collect::<Vec<_>>();
+            asset.put_library(file as u32, FileDescType::WZX, Arc::new(MemoryLibrary::from(images)));
+        }
+
+        let threads: Vec<_> = (0..8u32).map(|t| {
+            let asset = asset.clone();
+            std::thread::spawn(move || {
+                asset.put_file_map(100 + t, &format!("missing{}", t), t % 2 == 0);
+                for round in 0..IMAGES {
+                    let (file, index) = ((round + t) as u16 % FILES + 1, (round * 7 + t) % IMAGES);
+                    assert_eq!(asset.get_library(file as u32, FileDescType::WZX).unwrap().len(), IMAGES as usize);
+                    match asset.load_image(FileDesc::ZONE { file, number: 0, index }, FileDescType::WZX) {
+                        Ok(image) => assert_eq!(image.width, file * 100 + index as u16),
+                        Err(e) => assert!(index % 5 == 4 && matches!(e, Error::EmptyImage), "{}", e),
+                    }
+                }
+                assert!(matches!(asset.get_library(100 + t, FileDescType::WZX), Err(Error::Io(_))));
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for t in 0..8 {
+            assert_eq!(asset.get_file_name(100 + t).unwrap(), format!("missing{}", t));
+        }
+        let stats = asset.total_cache_stats();
+        assert_eq!(stats.hits + stats.misses, 8 * IMAGES as u64);
+        // 同一张图片只解码一次
+        assert_eq!(stats.decodes, stats.entries);
+        for file in 1..=FILES {
+            for index in 0..IMAGES {
+                let image = asset.load_image(FileDesc::ZONE { file, number: 0, index }, FileDescType::WZX);
+                assert_eq!(image.map(|x| x.width).ok(), (index % 5 != 4).then_some(file * 100 + index as u16));
             }
         }
-        return result;
     }
 }
-
-const PALETTE_RGBA: [u8; 1024] = [
-    0x00,0x00,0x00,0x00,0x80,0x00,0x00,0xFF,0x00,0x80,0x00,0xFF,0x80,0x80,0x00,0xFF,0x00,0x00,0x80,0xFF,0x80,0x00,0x80,0xFF,0x00,0x80,0x80,0xFF,0xC0,0xC0,0xC0,0xFF,
-    0x55,0x80,0x97,0xFF,0x9D,0xB9,0xC8,0xFF,0x7B,0x73,0x73,0xFF,0x2D,0x29,0x29,0xFF,0x5A,0x52,0x52,0xFF,0x63,0x5A,0x5A,0xFF,0x42,0x39,0x39,0xFF,0x1D,0x18,0x18,0xFF,
-    0x18,0x10,0x10,0xFF,0x29,0x18,0x18,0xFF,0x10,0x08,0x08,0xFF,0xF2,0x79,0x71,0xFF,0xE1,0x67,0x5F,0xFF,0xFF,0x5A,0x5A,0xFF,0xFF,0x31,0x31,0xFF,0xD6,0x5A,0x52,0xFF,
-    0x94,0x10,0x00,0xFF,0x94,0x29,0x18,0xFF,0x39,0x08,0x00,0xFF,0x73,0x10,0x00,0xFF,0xB5,0x18,0x00,0xFF,0xBD,0x63,0x52,0xFF,0x42,0x18,0x10,0xFF,0xFF,0xAA,0x99,0xFF,
-    0x5A,0x10,0x00,0xFF,0x73,0x39,0x29,0xFF,0xA5,0x4A,0x31,0xFF,0x94,0x7B,0x73,0xFF,0xBD,0x52,0x31,0xFF,0x52,0x21,0x10,0xFF,0x7B,0x31,0x18,0xFF,0x2D,0x18,0x10,0xFF,
-    0x8C,0x4A,0x31,0xFF,0x94,0x29,0x00,0xFF,0xBD,0x31,0x00,0xFF,0xC6,0x73,0x52,0xFF,0x6B,0x31,0x18,0xFF,0xC6,0x6B,0x42,0xFF,0xCE,0x4A,0x00,0xFF,0xA5,0x63,0x39,0xFF,
-    0x5A,0x31,0x18,0xFF,0x2A,0x10,0x00,0xFF,0x15,0x08,0x00,0xFF,0x3A,0x18,0x00,0xFF,0x08,0x00,0x00,0xFF,0x29,0x00,0x00,0xFF,0x4A,0x00,0x00,0xFF,0x9D,0x00,0x00,0xFF,
-    0xDC,0x00,0x00,0xFF,0xDE,0x00,0x00,0xFF,0xFB,0x00,0x00,0xFF,0x9C,0x73,0x52,0xFF,0x94,0x6B,0x4A,0xFF,0x73,0x4A,0x29,0xFF,0x52,0x31,0x18,0xFF,0x8C,0x4A,0x18,0xFF,
-    0x88,0x44,0x11,0xFF,0x4A,0x21,0x00,0xFF,0x21,0x18,0x10,0xFF,0xD6,0x94,0x5A,0xFF,0xC6,0x6B,0x21,0xFF,0xEF,0x6B,0x00,0xFF,0xFF,0x77,0x00,0xFF,0xA5,0x94,0x84,0xFF,
-    0x42,0x31,0x21,0xFF,0x18,0x10,0x08,0xFF,0x29,0x18,0x08,0xFF,0x21,0x10,0x00,0xFF,0x39,0x29,0x18,0xFF,0x8C,0x63,0x39,0xFF,0x42,0x29,0x10,0xFF,0x6B,0x42,0x18,0xFF,
-    0x7B,0x4A,0x18,0xFF,0x94,0x4A,0x00,0xFF,0x8C,0x84,0x7B,0xFF,0x6B,0x63,0x5A,0xFF,0x4A,0x42,0x39,0xFF,0x29,0x21,0x18,0xFF,0x46,0x39,0x29,0xFF,0xB5,0xA5,0x94,0xFF,
-    0x7B,0x6B,0x5A,0xFF,0xCE,0xB1,0x94,0xFF,0xA5,0x8C,0x73,0xFF,0x8C,0x73,0x5A,0xFF,0xB5,0x94,0x73,0xFF,0xD6,0xA5,0x73,0xFF,0xEF,0xA5,0x4A,0xFF,0xEF,0xC6,0x8C,0xFF,
-    0x7B,0x63,0x42,0xFF,0x6B,0x56,0x39,0xFF,0xBD,0x94,0x5A,0xFF,0x63,0x39,0x00,0xFF,0xD6,0xC6,0xAD,0xFF,0x52,0x42,0x29,0xFF,0x94,0x63,0x18,0xFF,0xEF,0xD6,0xAD,0xFF,
-    0xA5,0x8C,0x63,0xFF,0x63,0x5A,0x4A,0xFF,0xBD,0xA5,0x7B,0xFF,0x5A,0x42,0x18,0xFF,0xBD,0x8C,0x31,0xFF,0x35,0x31,0x29,0xFF,0x94,0x84,0x63,0xFF,0x7B,0x6B,0x4A,0xFF,
-    0xA5,0x8C,0x5A,0xFF,0x5A,0x4A,0x29,0xFF,0x9C,0x7B,0x39,0xFF,0x42,0x31,0x10,0xFF,0xEF,0xAD,0x21,0xFF,0x18,0x10,0x00,0xFF,0x29,0x21,0x00,0xFF,0x9C,0x6B,0x00,0xFF,
-    0x94,0x84,0x5A,0xFF,0x52,0x42,0x18,0xFF,0x6B,0x5A,0x29,0xFF,0x7B,0x63,0x21,0xFF,0x9C,0x7B,0x21,0xFF,0xDE,0xA5,0x00,0xFF,0x5A,0x52,0x39,0xFF,0x31,0x29,0x10,0xFF,
-    0xCE,0xBD,0x7B,0xFF,0x63,0x5A,0x39,0xFF,0x94,0x84,0x4A,0xFF,0xC6,0xA5,0x29,0xFF,0x10,0x9C,0x18,0xFF,0x42,0x8C,0x4A,0xFF,0x31,0x8C,0x42,0xFF,0x10,0x94,0x29,0xFF,
-    0x08,0x18,0x10,0xFF,0x08,0x18,0x18,0xFF,0x08,0x29,0x10,0xFF,0x18,0x42,0x29,0xFF,0xA5,0xB5,0xAD,0xFF,0x6B,0x73,0x73,0xFF,0x18,0x29,0x29,0xFF,0x18,0x42,0x4A,0xFF,
-    0x31,0x42,0x4A,0xFF,0x63,0xC6,0xDE,0xFF,0x44,0xDD,0xFF,0xFF,0x8C,0xD6,0xEF,0xFF,0x73,0x6B,0x39,0xFF,0xF7,0xDE,0x39,0xFF,0xF7,0xEF,0x8C,0xFF,0xF7,0xE7,0x00,0xFF,
-    0x6B,0x6B,0x5A,0xFF,0x5A,0x8C,0xA5,0xFF,0x39,0xB5,0xEF,0xFF,0x4A,0x9C,0xCE,0xFF,0x31,0x84,0xB5,0xFF,0x31,0x52,0x6B,0xFF,0xDE,0xDE,0xD6,0xFF,0xBD,0xBD,0xB5,0xFF,
-    0x8C,0x8C,0x84,0xFF,0xF7,0xF7,0xDE,0xFF,0x00,0x08,0x18,0xFF,0x08,0x18,0x39,0xFF,0x08,0x10,0x29,0xFF,0x08,0x18,0x00,0xFF,0x08,0x29,0x00,0xFF,0x00,0x52,0xA5,0xFF,
-    0x00,0x7B,0xDE,0xFF,0x10,0x29,0x4A,0xFF,0x10,0x39,0x6B,0xFF,0x10,0x52,0x8C,0xFF,0x21,0x5A,0xA5,0xFF,0x10,0x31,0x5A,0xFF,0x10,0x42,0x84,0xFF,0x31,0x52,0x84,0xFF,
-    0x18,0x21,0x31,0xFF,0x4A,0x5A,0x7B,0xFF,0x52,0x6B,0xA5,0xFF,0x29,0x39,0x63,0xFF,0x10,0x4A,0xDE,0xFF,0x29,0x29,0x21,0xFF,0x4A,0x4A,0x39,0xFF,0x29,0x29,0x18,0xFF,
-    0x4A,0x4A,0x29,0xFF,0x7B,0x7B,0x42,0xFF,0x9C,0x9C,0x4A,0xFF,0x5A,0x5A,0x29,0xFF,0x42,0x42,0x14,0xFF,0x39,0x39,0x00,0xFF,0x59,0x59,0x00,0xFF,0xCA,0x35,0x2C,0xFF,
-    0x6B,0x73,0x21,0xFF,0x29,0x31,0x00,0xFF,0x31,0x39,0x10,0xFF,0x31,0x39,0x18,0xFF,0x42,0x4A,0x00,0xFF,0x52,0x63,0x18,0xFF,0x5A,0x73,0x29,0xFF,0x31,0x4A,0x18,0xFF,
-    0x18,0x21,0x00,0xFF,0x18,0x31,0x00,0xFF,0x18,0x39,0x10,0xFF,0x63,0x84,0x4A,0xFF,0x6B,0xBD,0x4A,0xFF,0x63,0xB5,0x4A,0xFF,0x63,0xBD,0x4A,0xFF,0x5A,0x9C,0x4A,0xFF,
-    0x4A,0x8C,0x39,0xFF,0x63,0xC6,0x4A,0xFF,0x63,0xD6,0x4A,0xFF,0x52,0x84,0x4A,0xFF,0x31,0x73,0x29,0xFF,0x63,0xC6,0x5A,0xFF,0x52,0xBD,0x4A,0xFF,0x10,0xFF,0x00,0xFF,
-    0x18,0x29,0x18,0xFF,0x4A,0x88,0x4A,0xFF,0x4A,0xE7,0x4A,0xFF,0x00,0x5A,0x00,0xFF,0x00,0x88,0x00,0xFF,0x00,0x94,0x00,0xFF,0x00,0xDE,0x00,0xFF,0x00,0xEE,0x00,0xFF,
-    0x00,0xFB,0x00,0xFF,0x4A,0x5A,0x94,0xFF,0x63,0x73,0xB5,0xFF,0x7B,0x8C,0xD6,0xFF,0x6B,0x7B,0xD6,0xFF,0x77,0x88,0xFF,0xFF,0xC6,0xC6,0xCE,0xFF,0x94,0x94,0x9C,0xFF,
-    0x9C,0x94,0xC6,0xFF,0x31,0x31,0x39,0xFF,0x29,0x18,0x84,0xFF,0x18,0x00,0x84,0xFF,0x4A,0x42,0x52,0xFF,0x52,0x42,0x7B,0xFF,0x63,0x5A,0x73,0xFF,0xCE,0xB5,0xF7,0xFF,
-    0x8C,0x7B,0x9C,0xFF,0x77,0x22,0xCC,0xFF,0xDD,0xAA,0xFF,0xFF,0xF0,0xB4,0x2A,0xFF,0xDF,0x00,0x9F,0xFF,0xE3,0x17,0xB3,0xFF,0xFF,0xFB,0xF0,0xFF,0xA0,0xA0,0xA4,0xFF,
-    0x80,0x80,0x80,0xFF,0xFF,0x00,0x00,0xFF,0x00,0xFF,0x00,0xFF,0xFF,0xFF,0x00,0xFF,0x00,0x00,0xFF,0xFF,0xFF,0x00,0xFF,0xFF,0x00,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,
-];
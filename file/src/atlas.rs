@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::library::ImageData;
+use crate::error::{Error, Result};
+
+/// 图片在图集中的位置, key一般为FileDesc::get_cache_key
+/// uv为(u0, v0, u1, v1), offset_x/offset_y为原图片的绘制偏移
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+    pub key: u64,
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub uv: [f32; 4],
+}
+
+/// 图集的一页, RGBA像素
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    shelves: Vec<Shelf>,
+    count: usize,
+}
+
+/// 按行(shelf)分配, 每行记录已释放的区间以便重复使用
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor: u32,
+    free: Vec<(u32, u32)>,
+}
+
+/// 图集元数据, 与页面图片一起保存
+#[derive(Serialize, Deserialize)]
+struct AtlasMeta {
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+    pages: Vec<String>,
+    entries: Vec<AtlasEntry>,
+}
+
+/// 纹理图集, 把多张图片合并到若干页中以便批量绘制
+/// 支持逐张插入和移除, 修改过的页通过take_dirty取得后重新上传
+pub struct TextureAtlas {
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+    max_pages: Option<usize>,
+    pages: Vec<AtlasPage>,
+    entries: HashMap<u64, AtlasEntry>,
+    dirty: Vec<bool>,
+}
+
+impl TextureAtlas {
+
+    pub fn new(page_width: u32, page_height: u32) -> Self {
+        Self {
+            page_width, page_height,
+            padding: 1,
+            max_pages: None,
+            pages: Vec::new(),
+            entries: HashMap::new(),
+            dirty: Vec::new(),
+        }
+    }
+
+    /// 图片之间的间隔像素, 避免采样时混入相邻图片, 默认为1
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// 页数上限, 超出时插入返回`Error::AtlasFull`
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn pages(&self) -> &[AtlasPage] {
+        &self.pages
+    }
+
+    pub fn get(&self, key: u64) -> Option<&AtlasEntry> {
+        self.entries.get(&key)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &AtlasEntry> {
+        self.entries.values()
+    }
+
+    /// 插入图片, key已存在时直接返回原位置
+    pub fn insert(&mut self, key: u64, image: &ImageData) -> Result<AtlasEntry> {
+        if let Some(entry) = self.entries.get(&key) {
+            return Ok(*entry);
+        }
+        let (width, height) = (image.width as u32, image.height as u32);
+        if width == 0 || height == 0 {
+            return Err(Error::EmptyImage);
+        }
+        let expected = width as usize * height as usize * 4;
+        if image.bytes.len() < expected {
+            return Err(Error::TruncatedData { expected, actual: image.bytes.len() });
+        }
+        let (pw, ph) = (width.saturating_add(self.padding), height.saturating_add(self.padding));
+        if pw > self.page_width || ph > self.page_height {
+            return Err(Error::ImageTooLarge { width: image.width, height: image.height });
+        }
+
+        let found = self.pages.iter_mut().enumerate().find_map(|(i, p)| p.allocate(pw, ph).map(|(x, y)| (i, x, y)));
+        let (page, x, y) = match found {
+            Some(x) => x,
+            None => {
+                if self.max_pages.is_some_and(|x| self.pages.len() >= x) {
+                    return Err(Error::AtlasFull);
+                }
+                let mut page = AtlasPage::new(self.page_width, self.page_height)?;
+                let (x, y) = page.allocate(pw, ph).ok_or(Error::AtlasFull)?;
+                self.pages.push(page);
+                self.dirty.push(true);
+                (self.pages.len() - 1, x, y)
+            }
+        };
+
+        let p = &mut self.pages[page];
+        p.count += 1;
+        p.blit(x, y, width, height, &image.bytes);
+        self.dirty[page] = true;
+        let uv = [
+            x as f32 / p.width as f32,
+            y as f32 / p.height as f32,
+            (x + width) as f32 / p.width as f32,
+            (y + height) as f32 / p.height as f32,
+        ];
+        let entry = AtlasEntry { key, page, x, y, width, height, offset_x: image.offset_x, offset_y: image.offset_y, uv };
+        self.entries.insert(key, entry);
+        Ok(entry)
+    }
+
+    /// 移除图片并清空对应区域, 空出的位置可以被之后的图片使用
+    pub fn remove(&mut self, key: u64) -> Option<AtlasEntry> {
+        let entry = self.entries.remove(&key)?;
+        let page = &mut self.pages[entry.page];
+        page.blit(entry.x, entry.y, entry.width, entry.height, &[]);
+        page.release(entry.x, entry.y, entry.width + self.padding);
+        self.dirty[entry.page] = true;
+        Some(entry)
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.entries.clear();
+        self.dirty.clear();
+    }
+
+    /// 返回上次调用之后修改过的页序号
+    pub fn take_dirty(&mut self) -> Vec<usize> {
+        let dirty = self.dirty.iter().enumerate().filter(|(_, x)| **x).map(|(i, _)| i).collect();
+        self.dirty.iter_mut().for_each(|x| *x = false);
+        dirty
+    }
+
+    /// 保存为dir/name_序号.png和dir/name.json
+    pub fn save<P: AsRef<Path>>(&self, dir: P, name: &str) -> Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut pages = Vec::with_capacity(self.pages.len());
+        for (i, page) in self.pages.iter().enumerate() {
+            let file_name = format!("{}_{}.png", name, i);
+            write_png(&dir.join(&file_name), page.width, page.height, &page.pixels)?;
+            pages.push(file_name);
+        }
+        let mut entries: Vec<AtlasEntry> = self.entries.values().copied().collect();
+        entries.sort_by_key(|x| x.key);
+        let meta = AtlasMeta { page_width: self.page_width, page_height: self.page_height, padding: self.padding, pages, entries };
+        let json = serde_json::to_vec_pretty(&meta).map_err(|e| Error::Encode(e.to_string()))?;
+        fs::write(dir.join(name).with_extension("json"), json)?;
+        Ok(())
+    }
+
+    /// 读取save保存的图集, 读取后仍然可以继续插入和移除
+    pub fn load<P: AsRef<Path>>(dir: P, name: &str) -> Result<Self> {
+        let dir = dir.as_ref();
+        let json = fs::read(dir.join(name).with_extension("json"))?;
+        let meta: AtlasMeta = serde_json::from_slice(&json).map_err(|e| Error::Decode(e.to_string()))?;
+        let mut atlas = Self::new(meta.page_width, meta.page_height).with_padding(meta.padding);
+        for file_name in &meta.pages {
+            let (width, height, pixels) = read_png(&dir.join(file_name))?;
+            if width != meta.page_width || height != meta.page_height {
+                return Err(Error::Decode(format!("page size mismatch: {}", file_name)));
+            }
+            let mut page = AtlasPage::new(width, height)?;
+            page.pixels = pixels;
+            atlas.pages.push(page);
+            atlas.dirty.push(true);
+        }
+        // 按位置重建每页的分配情况
+        let mut entries = meta.entries;
+        entries.sort_by_key(|x| (x.page, x.y, x.x));
+        for entry in entries {
+            let page = atlas.pages.get_mut(entry.page).ok_or(Error::BadIndex { index: entry.page as u32, len: meta.pages.len() })?;
+            page.reserve(entry.x, entry.y, entry.width + meta.padding, entry.height + meta.padding);
+            page.count += 1;
+            atlas.entries.insert(entry.key, entry);
+        }
+        Ok(atlas)
+    }
+}
+
+impl AtlasPage {
+
+    /// 页面像素大小溢出时返回`Error::BadImage`
+    fn new(width: u32, height: u32) -> Result<Self> {
+        let size = (width as usize).checked_mul(height as usize).and_then(|x| x.checked_mul(4))
+            .ok_or_else(|| Error::BadImage(format!("atlas page too large: {}x{}", width, height)))?;
+        Ok(Self { width, height, pixels: vec![0; size], shelves: Vec::new(), count: 0 })
+    }
+
+    /// 选择高度最接近的行, 优先使用已释放的区间, 没有时在底部新建一行
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let page_width = self.width;
+        let best = self.shelves.iter().enumerate()
+            .filter(|(_, s)| s.height >= height && (s.cursor + width <= page_width || s.free.iter().any(|x| x.1 >= width)))
+            .min_by_key(|(_, s)| s.height - height)
+            .map(|(i, _)| i);
+        // 新建一行比浪费一半以上的高度更好
+        let best = best.filter(|i| self.shelves[*i].height < height.saturating_mul(2) || !self.can_add_shelf(height));
+        if let Some(i) = best {
+            let shelf = &mut self.shelves[i];
+            if let Some(j) = shelf.free.iter().position(|x| x.1 >= width) {
+                let (x, w) = shelf.free[j];
+                if w > width { shelf.free[j] = (x + width, w - width) } else { shelf.free.remove(j); }
+                return Some((x, shelf.y));
+            }
+            let x = shelf.cursor;
+            shelf.cursor += width;
+            return Some((x, shelf.y));
+        }
+        if !self.can_add_shelf(height) { return None }
+        let y = self.shelves.last().map(|x| x.y + x.height).unwrap_or(0);
+        self.shelves.push(Shelf { y, height, cursor: width, free: Vec::new() });
+        Some((0, y))
+    }
+
+    fn can_add_shelf(&self, height: u32) -> bool {
+        let y = self.shelves.last().map(|x| x.y + x.height).unwrap_or(0);
+        y + height <= self.height
+    }
+
+    /// 释放区间并与相邻区间合并, 整页为空时重置
+    fn release(&mut self, x: u32, y: u32, width: u32) {
+        self.count = self.count.saturating_sub(1);
+        if self.count == 0 {
+            self.shelves.clear();
+            return;
+        }
+        let Some(shelf) = self.shelves.iter_mut().find(|s| s.y == y) else { return };
+        shelf.free.push((x, width));
+        shelf.free.sort_unstable();
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(shelf.free.len());
+        for (x, w) in shelf.free.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.0 + last.1 == x => last.1 += w,
+                _ => merged.push((x, w)),
+            }
+        }
+        if let Some(last) = merged.last() {
+            if last.0 + last.1 == shelf.cursor {
+                shelf.cursor = last.0;
+                merged.pop();
+            }
+        }
+        shelf.free = merged;
+    }
+
+    /// 读取时按已保存的位置占用空间, 行之间的空隙不再使用
+    fn reserve(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        match self.shelves.iter_mut().find(|s| s.y == y) {
+            Some(shelf) => {
+                if x > shelf.cursor { shelf.free.push((shelf.cursor, x - shelf.cursor)) }
+                shelf.height = shelf.height.max(height);
+                shelf.cursor = shelf.cursor.max(x + width);
+            }
+            None => {
+                let free = if x > 0 { vec![(0, x)] } else { Vec::new() };
+                self.shelves.push(Shelf { y, height, cursor: x + width, free });
+            }
+        }
+    }
+
+    /// 复制RGBA像素到(x, y), src为空时清空该区域
+    fn blit(&mut self, x: u32, y: u32, width: u32, height: u32, src: &[u8]) {
+        let row = (width * 4) as usize;
+        for j in 0..height as usize {
+            let start = ((y as usize + j) * self.width as usize + x as usize) * 4;
+            let dst = &mut self.pixels[start..start + row];
+            if src.is_empty() {
+                dst.fill(0);
+            } else {
+                dst.copy_from_slice(&src[j * row..(j + 1) * row]);
+            }
+        }
+    }
+}
+
+pub(crate) fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| Error::Encode(e.to_string()))?;
+    writer.write_image_data(pixels).map_err(|e| Error::Encode(e.to_string()))?;
+    Ok(())
+}
+
+fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info().map_err(|e| Error::Decode(e.to_string()))?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(|e| Error::Decode(e.to_string()))?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(Error::Decode(format!("unsupported png format: {:?}", info.color_type)));
+    }
+    pixels.truncate(info.buffer_size());
+    Ok((info.width, info.height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use super::*;
+
+    fn image(width: u16, height: u16, seed: u8) -> ImageData {
+        let bytes = (0..width as usize * height as usize * 4).map(|x| (x as u8).wrapping_add(seed) | 1).collect::<Vec<u8>>();
+        ImageData { width, height, offset_x: 2, offset_y: -4, bytes: Bytes::from(bytes) }
+    }
+
+    /// 读取页面中entry对应区域的像素
+    fn region(atlas: &TextureAtlas, entry: &AtlasEntry) -> Vec<u8> {
+        let page = &atlas.pages()[entry.page];
+        (0..entry.height).flat_map(|j| {
+            let start = (((entry.y + j) * page.width + entry.x) * 4) as usize;
+            page.pixels[start..start + entry.width as usize * 4].to_vec()
+        }).collect()
+    }
+
+    #[test]
+    fn shelf_allocation() {
+        let mut atlas = TextureAtlas::new(64, 64);
+        let a = atlas.insert(1, &image(10, 10, 1)).unwrap();
+        let b = atlas.insert(2, &image(10, 10, 2)).unwrap();
+        assert_eq!((a.x, a.y, b.x, b.y), (0, 0, 11, 0));
+        // 更高的图片新建一行, 较矮的图片放回高度接近的行
+        let c = atlas.insert(3, &image(20, 20, 3)).unwrap();
+        assert_eq!((c.x, c.y), (0, 11));
+        let d = atlas.insert(4, &image(5, 5, 4)).unwrap();
+        assert_eq!((d.x, d.y), (22, 0));
+        assert_eq!(atlas.insert(1, &image(3, 3, 9)).unwrap(), a);
+        assert_eq!(region(&atlas, &c), &image(20, 20, 3).bytes[..]);
+        assert_eq!(c.uv, [0.0, 11.0 / 64.0, 20.0 / 64.0, 31.0 / 64.0]);
+        assert_eq!(atlas.take_dirty(), vec![0]);
+        assert!(atlas.take_dirty().is_empty());
+
+        assert!(matches!(atlas.insert(5, &image(64, 1, 0)), Err(Error::ImageTooLarge { .. })));
+        assert!(matches!(atlas.insert(5, &image(0, 1, 0)), Err(Error::EmptyImage)));
+        let mut short = image(2, 2, 0);
+        short.bytes.truncate(15);
+        assert!(matches!(atlas.insert(5, &short), Err(Error::TruncatedData { expected: 16, actual: 15 })));
+
+        let mut atlas = TextureAtlas::new(16, 16).with_max_pages(1);
+        atlas.insert(1, &image(15, 15, 0)).unwrap();
+        assert!(matches!(atlas.insert(2, &image(1, 1, 0)), Err(Error::AtlasFull)));
+        assert_eq!(atlas.pages().len(), 1);
+    }
+
+    #[test]
+    fn free_range_reuse() {
+        let mut atlas = TextureAtlas::new(64, 64);
+        for key in 0..4 {
+            atlas.insert(key, &image(10, 10, key as u8)).unwrap();
+        }
+        let removed = atlas.remove(1).unwrap();
+        assert_eq!(region(&atlas, &removed), vec![0; 400]);
+        assert!(atlas.remove(1).is_none());
+        atlas.remove(2).unwrap();
+        // 相邻的两个区间合并后可以放下更宽的图片
+        let wide = atlas.insert(5, &image(21, 8, 5)).unwrap();
+        assert_eq!((wide.x, wide.y), (11, 0));
+        assert_eq!(region(&atlas, &wide), &image(21, 8, 5).bytes[..]);
+        assert_eq!(atlas.get(3).unwrap().x, 33);
+
+        // 行尾的区间回退cursor, 整页为空时重置
+        atlas.remove(3).unwrap();
+        assert_eq!((atlas.insert(6, &image(30, 10, 6)).unwrap().x), 33);
+        for key in [0, 5, 6] {
+            atlas.remove(key).unwrap();
+        }
+        assert!(atlas.is_empty());
+        let big = atlas.insert(7, &image(40, 40, 7)).unwrap();
+        assert_eq!((big.page, big.x, big.y), (0, 0, 0));
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut atlas = TextureAtlas::new(32, 32).with_padding(2);
+        for key in 0..6 {
+            atlas.insert(key, &image(12, 9 + key as u16, key as u8)).unwrap();
+        }
+        atlas.remove(2).unwrap();
+        assert!(atlas.pages().len() > 1);
+        atlas.save(dir.path(), "atlas").unwrap();
+        assert!(dir.path().join("atlas.json").exists());
+        assert!(dir.path().join("atlas_1.png").exists());
+
+        let mut loaded = TextureAtlas::load(dir.path(), "atlas").unwrap();
+        assert_eq!(loaded.len(), atlas.len());
+        assert_eq!(loaded.take_dirty(), (0..atlas.pages().len()).collect::<Vec<_>>());
+        for entry in atlas.entries() {
+            assert_eq!(loaded.get(entry.key), Some(entry));
+            assert_eq!(region(&loaded, entry), region(&atlas, entry));
+        }
+        for (a, b) in atlas.pages().iter().zip(loaded.pages()) {
+            assert_eq!(a.pixels, b.pixels);
+        }
+
+        // 读取后新插入的图片不能覆盖已有的图片
+        let entry = loaded.insert(9, &image(12, 9, 9)).unwrap();
+        for other in loaded.entries().filter(|x| x.key != 9 && x.page == entry.page) {
+            let overlap = entry.x < other.x + other.width && other.x < entry.x + entry.width
+                && entry.y < other.y + other.height && other.y < entry.y + entry.height;
+            assert!(!overlap, "{:?} overlaps {:?}", entry, other);
+        }
+        assert!(matches!(TextureAtlas::load(dir.path(), "missing"), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn page_size_overflow() {
+        let mut atlas = TextureAtlas::new(u32::MAX, u32::MAX);
+        assert!(matches!(atlas.insert(1, &image(1, 1, 0)), Err(Error::BadImage(_))));
+    }
+}
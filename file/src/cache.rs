@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use moka::sync::{Cache, ConcurrentCacheExt};
+use crate::error::{Error, Result};
+use crate::key::{FILE_KEY_MASK, MAX_FILE};
+use crate::library::ImageData;
+
+/// 每条缓存除像素外的估计占用, 空图片和错误结果也会占用预算
+const ENTRY_OVERHEAD: usize = 64;
+
+/// 图片库分类, 每类使用单独的内存预算, 避免大的特效帧挤掉地图图块
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageClass {
+    Tile,
+    Actor,
+    Effect,
+    Other,
+}
+
+impl ImageClass {
+    pub const ALL: [ImageClass; 4] = [ImageClass::Tile, ImageClass::Actor, ImageClass::Effect, ImageClass::Other];
+
+    fn slot(self) -> usize {
+        self as usize
+    }
+}
+
+/// 各类图片缓存的字节上限, 按解码后的像素大小计算
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheBudget {
+    pub tile: u64,
+    pub actor: u64,
+    pub effect: u64,
+    pub other: u64,
+}
+
+impl CacheBudget {
+    pub fn get(&self, class: ImageClass) -> u64 {
+        match class {
+            ImageClass::Tile => self.tile,
+            ImageClass::Actor => self.actor,
+            ImageClass::Effect => self.effect,
+            ImageClass::Other => self.other,
+        }
+    }
+}
+
+impl Default for CacheBudget {
+    fn default() -> Self {
+        const MB: u64 = 1024 * 1024;
+        Self { tile: 64 * MB, actor: 128 * MB, effect: 128 * MB, other: 32 * MB }
+    }
+}
+
+/// 缓存统计, 用于调试信息显示
+/// hits和misses按图片计算, 批量请求中的每张图片各计一次
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// 内存未命中但从磁盘缓存读取的数量, 包含在misses中
+    pub disk_hits: u64,
+    /// 超出预算被淘汰的数量, 不包括替换和手动清除
+    pub evictions: u64,
+    pub entries: u64,
+    pub bytes: u64,
+    pub budget: u64,
+    pub decodes: u64,
+    pub decode_time: Duration,
+}
+
+impl CacheStats {
+    /// 命中率, 没有请求时为0
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0.0 } else { self.hits as f64 / total as f64 }
+    }
+
+    fn merge(mut self, other: CacheStats) -> Self {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.disk_hits += other.disk_hits;
+        self.evictions += other.evictions;
+        self.entries += other.entries;
+        self.bytes += other.bytes;
+        self.budget += other.budget;
+        self.decodes += other.decodes;
+        self.decode_time += other.decode_time;
+        self
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    disk_hits: AtomicU64,
+    evictions: AtomicU64,
+    decodes: AtomicU64,
+    decode_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn record_disk_hits(&self, count: u64) {
+        self.disk_hits.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_decode(&self, count: u64, elapsed: Duration) {
+        self.decodes.fetch_add(count, Ordering::Relaxed);
+        self.decode_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+struct ClassCache {
+    cache: Cache<u64, Result<ImageData>>,
+    counters: Arc<Counters>,
+    budget: u64,
+}
+
+impl ClassCache {
+    fn new(budget: u64) -> Self {
+        let counters = Arc::new(Counters::default());
+        let evictions = counters.clone();
+        let cache = Cache::builder()
+            .max_capacity(budget)
+            .weigher(|_, v: &Result<ImageData>| weight(v))
+            .eviction_listener(move |_, _, cause| {
+                if cause.was_evicted() {
+                    evictions.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .build();
+        Self { cache, counters, budget }
+    }
+}
+
+/// 按图片库分类的解码缓存, KEY为`FileDesc`的缓存KEY
+pub(crate) struct ImageCache {
+    classes: [ClassCache; 4],
+    /// 文件编号对应的分类, 带序号的文件未设置时使用不带序号的分类
+    file_class: RwLock<HashMap<u32, ImageClass>>,
+}
+
+impl ImageCache {
+
+    pub(crate) fn new(budget: CacheBudget) -> Self {
+        Self { classes: ImageClass::ALL.map(|x| ClassCache::new(budget.get(x))), file_class: RwLock::new(HashMap::new()) }
+    }
+
+    /// 分类改变时清除该文件在原分类中的缓存, 避免继续占用原分类的预算
+    pub(crate) fn set_class(&self, file_key: u32, class: ImageClass) {
+        let old = self.file_class.write().unwrap_or_else(|e| e.into_inner()).insert(file_key, class);
+        if old == Some(class) {
+            return;
+        }
+        for (slot, cache) in self.classes.iter().enumerate() {
+            let keys: Vec<u64> = cache.cache.iter().map(|(k, _)| *k)
+                .filter(|k| self.class_of(*k).slot() != slot).collect();
+            for key in keys {
+                cache.cache.invalidate(&key);
+            }
+        }
+    }
+
+    /// 清除文件编号满足条件的所有缓存, 包括缓存的错误结果
+    pub(crate) fn invalidate_files(&self, f: impl Fn(u32) -> bool) {
+        for slot in &self.classes {
+            let keys: Vec<u64> = slot.cache.iter().map(|(k, _)| *k)
+                .filter(|k| f(((*k >> 32) as u32) & FILE_KEY_MASK)).collect();
+            for key in keys {
+                slot.cache.invalidate(&key);
+            }
+        }
+    }
+
+    pub(crate) fn classes(&self) -> Vec<(u32, ImageClass)> {
+        self.file_class.read().unwrap_or_else(|e| e.into_inner()).iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    pub(crate) fn class_of(&self, key: u64) -> ImageClass {
+        let file_key = ((key >> 32) as u32) & FILE_KEY_MASK;
+        let file_class = self.file_class.read().unwrap_or_else(|e| e.into_inner());
+        file_class.get(&file_key).or_else(|| file_class.get(&(file_key & MAX_FILE as u32)))
+            .copied().unwrap_or(ImageClass::Other)
+    }
+
+    pub(crate) fn counters(&self, key: u64) -> Arc<Counters> {
+        self.slot(key).counters.clone()
+    }
+
+    /// 查找缓存, count_miss为false时只统计命中
+    pub(crate) fn get(&self, key: u64, count_miss: bool) -> Option<Result<ImageData>> {
+        let slot = self.slot(key);
+        let value = slot.cache.get(&key);
+        match value {
+            Some(_) => slot.counters.hits.fetch_add(1, Ordering::Relaxed),
+            None if count_miss => slot.counters.misses.fetch_add(1, Ordering::Relaxed),
+            None => 0,
+        };
+        value
+    }
+
+    pub(crate) fn insert(&self, key: u64, value: Result<ImageData>) {
+        self.slot(key).cache.insert(key, value);
+    }
+
+    /// 未缓存时调用init, 同一KEY的并发调用只执行一次, IO错误不缓存
+    pub(crate) fn get_with(&self, key: u64, init: impl FnOnce() -> Result<ImageData>) -> Result<ImageData> {
+        let slot = self.slot(key);
+        let value = slot.cache.get_with(key, init);
+        if matches!(value, Err(Error::Io(_))) {
+            slot.cache.invalidate(&key);
+        }
+        value
+    }
+
+    pub(crate) fn stats(&self, class: ImageClass) -> CacheStats {
+        let slot = &self.classes[class.slot()];
+        slot.cache.sync();
+        let counters = &slot.counters;
+        CacheStats {
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            disk_hits: counters.disk_hits.load(Ordering::Relaxed),
+            evictions: counters.evictions.load(Ordering::Relaxed),
+            entries: slot.cache.entry_count(),
+            bytes: slot.cache.weighted_size(),
+            budget: slot.budget,
+            decodes: counters.decodes.load(Ordering::Relaxed),
+            decode_time: Duration::from_nanos(counters.decode_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub(crate) fn total_stats(&self) -> CacheStats {
+        ImageClass::ALL.iter().fold(CacheStats::default(), |acc, x| acc.merge(self.stats(*x)))
+    }
+
+    fn slot(&self, key: u64) -> &ClassCache {
+        &self.classes[self.class_of(key).slot()]
+    }
+}
+
+fn weight(value: &Result<ImageData>) -> u32 {
+    let bytes = value.as_ref().map(|x| x.bytes.len()).unwrap_or(0);
+    (bytes + ENTRY_OVERHEAD).min(u32::MAX as usize) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use super::*;
+
+    fn image(size: usize) -> Result<ImageData> {
+        Ok(ImageData { width: 1, height: 1, offset_x: 0, offset_y: 0, bytes: Bytes::from(vec![0; size]) })
+    }
+
+    #[test]
+    fn class_change_moves_entries() {
+        let cache = ImageCache::new(CacheBudget::default());
+        // 文件1和它的带序号文件(1 | 2 << 12), 以及文件2
+        let keys = [1u64 << 32, (1 | 2 << 12) << 32 | 5, 2 << 32];
+        for key in keys {
+            cache.insert(key, image(1000));
+        }
+        assert_eq!(cache.stats(ImageClass::Other).entries, 3);
+
+        cache.set_class(1, ImageClass::Tile);
+        assert_eq!(cache.class_of(keys[1]), ImageClass::Tile);
+        let other = cache.stats(ImageClass::Other);
+        assert_eq!((other.entries, other.bytes), (1, 1000 + ENTRY_OVERHEAD as u64));
+        assert_eq!(other.evictions, 0);
+        assert!(cache.get(keys[0], true).is_none());
+        assert!(cache.get(keys[2], true).is_some());
+
+        cache.insert(keys[0], image(10));
+        assert_eq!(cache.stats(ImageClass::Tile).entries, 1);
+        // 分类不变时保留缓存
+        cache.set_class(1, ImageClass::Tile);
+        cache.set_class(1 | 2 << 12, ImageClass::Tile);
+        assert_eq!(cache.stats(ImageClass::Tile).entries, 1);
+        cache.set_class(1 | 2 << 12, ImageClass::Effect);
+        assert_eq!(cache.stats(ImageClass::Tile).entries, 1);
+        cache.set_class(1, ImageClass::Actor);
+        assert_eq!(cache.stats(ImageClass::Tile).entries, 0);
+        assert_eq!(cache.total_stats().entries, 1);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use crate::map::{Door, MapInfo, Tile};
+
+/// 格子标记
+const BLOCK_WALK: u8 = 0x01;
+const BLOCK_FLY: u8 = 0x02;
+const DOOR: u8 = 0x04;
+const DOOR_OPEN: u8 = 0x08;
+
+/// 地图格子的通行信息, 由MapInfo生成, 按x * height + y存储
+/// 关闭的门不可行走也不可飞越, 打开后按格子本身的标记判断
+pub struct CollisionGrid {
+    width: u32,
+    height: u32,
+    flags: Vec<u8>,
+    doors: Vec<(u8, u8)>,
+    lights: Vec<u8>,
+    door_cells: HashMap<u8, Vec<(u32, u32)>>,
+    light_cells: Vec<(u32, u32, u8)>,
+}
+
+impl CollisionGrid {
+
+    pub fn from_map(map: &MapInfo) -> Self {
+        let (width, height) = (map.width, map.height);
+        let count = width as usize * height as usize;
+        let mut grid = Self {
+            width, height,
+            flags: vec![BLOCK_WALK | BLOCK_FLY; count],
+            doors: vec![(0, 0); count],
+            lights: vec![0; count],
+            door_cells: HashMap::new(),
+            light_cells: Vec::new(),
+        };
+        for (i, tile) in map.tiles.iter().take(count).enumerate() {
+            let (x, y) = ((i as u32) / height, (i as u32) % height);
+            grid.flags[i] = cell_flags(tile);
+            if let Some(door) = tile.door() {
+                grid.doors[i] = (door.index, door.offset);
+                grid.door_cells.entry(door.index).or_default().push((x, y));
+            }
+            let light = tile.light_radius();
+            if light > 0 {
+                grid.lights[i] = light;
+                grid.light_cells.push((x, y, light));
+            }
+        }
+        grid
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height { return None }
+        Some(x as usize * self.height as usize + y as usize)
+    }
+
+    fn flags(&self, x: u32, y: u32) -> u8 {
+        self.index(x, y).map(|i| self.flags[i]).unwrap_or(BLOCK_WALK | BLOCK_FLY)
+    }
+
+    /// 地图外视为不可行走
+    pub fn is_walkable(&self, x: u32, y: u32) -> bool {
+        let flags = self.flags(x, y);
+        flags & BLOCK_WALK == 0 && !door_closed(flags)
+    }
+
+    /// 地图外视为阻挡
+    pub fn blocks_projectiles(&self, x: u32, y: u32) -> bool {
+        let flags = self.flags(x, y);
+        flags & BLOCK_FLY != 0 || door_closed(flags)
+    }
+
+    pub fn is_door(&self, x: u32, y: u32) -> bool {
+        self.flags(x, y) & DOOR != 0
+    }
+
+    pub fn door(&self, x: u32, y: u32) -> Option<Door> {
+        let i = self.index(x, y)?;
+        let flags = self.flags[i];
+        if flags & DOOR == 0 { return None }
+        let (index, offset) = self.doors[i];
+        Some(Door { index, offset, open: flags & DOOR_OPEN != 0 })
+    }
+
+    /// 同一编号的门占用的所有格子
+    pub fn door_cells(&self, index: u8) -> &[(u32, u32)] {
+        self.door_cells.get(&index).map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    /// 设置门的开关状态, 同一编号的所有格子一起改变
+    pub fn set_door_open(&mut self, index: u8, open: bool) {
+        let Some(cells) = self.door_cells.get(&index) else { return };
+        for (x, y) in cells {
+            let i = *x as usize * self.height as usize + *y as usize;
+            if open { self.flags[i] |= DOOR_OPEN } else { self.flags[i] &= !DOOR_OPEN }
+        }
+    }
+
+    /// 光源强度, 0为无光源
+    pub fn light(&self, x: u32, y: u32) -> u8 {
+        self.index(x, y).map(|i| self.lights[i]).unwrap_or(0)
+    }
+
+    /// 所有光源格子(x, y, 强度)
+    pub fn lights(&self) -> &[(u32, u32, u8)] {
+        &self.light_cells
+    }
+}
+
+fn cell_flags(tile: &Tile) -> u8 {
+    let mut flags = 0;
+    if !tile.can_walk_ignoring_door() { flags |= BLOCK_WALK }
+    if !tile.can_fly_ignoring_door() { flags |= BLOCK_FLY }
+    if let Some(door) = tile.door() {
+        flags |= DOOR;
+        if door.open { flags |= DOOR_OPEN }
+    }
+    flags
+}
+
+fn door_closed(flags: u8) -> bool {
+    flags & DOOR != 0 && flags & DOOR_OPEN == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use crate::map::MapFormat;
+    use super::*;
+
+    /// 3x2格子: (0, 0)不可行走, (0, 1)不可飞越, 第2列是编号为2的关闭的门, (2, 0)有光源
+    fn grid() -> CollisionGrid {
+        let mut tiles = vec![Tile::default(); 6];
+        tiles[0].back = 0x8000;
+        tiles[1].objects = 0x8000;
+        for tile in &mut tiles[2..4] {
+            tile.door_idx = 0x82;
+            tile.door_offset = 0x03;
+        }
+        tiles[4].light = 4;
+        let map = MapInfo {
+            width: 3, height: 2, step: 12, size: 0, name: String::new(),
+            format: MapFormat::Classic, header: Bytes::new(), tiles,
+        };
+        CollisionGrid::from_map(&map)
+    }
+
+    #[test]
+    fn walk_and_fly() {
+        let grid = grid();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert!(!grid.is_walkable(0, 0) && !grid.blocks_projectiles(0, 0));
+        assert!(!grid.is_walkable(0, 1) && grid.blocks_projectiles(0, 1));
+        assert!(grid.is_walkable(2, 1) && !grid.blocks_projectiles(2, 1));
+        // 地图外
+        for (x, y) in [(3, 0), (0, 2), (u32::MAX, u32::MAX)] {
+            assert!(!grid.is_walkable(x, y));
+            assert!(grid.blocks_projectiles(x, y));
+            assert!(!grid.is_door(x, y));
+            assert_eq!(grid.door(x, y), None);
+            assert_eq!(grid.light(x, y), 0);
+        }
+    }
+
+    #[test]
+    fn open_and_close_door() {
+        let mut grid = grid();
+        assert_eq!(grid.door_cells(2), &[(1, 0), (1, 1)]);
+        assert!(grid.door_cells(1).is_empty());
+        assert_eq!(grid.door(1, 1), Some(Door { index: 2, offset: 3, open: false }));
+        assert!(grid.is_door(1, 0) && !grid.is_door(2, 0));
+        assert!(!grid.is_walkable(1, 0) && grid.blocks_projectiles(1, 1));
+
+        grid.set_door_open(2, true);
+        for (x, y) in [(1, 0), (1, 1)] {
+            assert_eq!(grid.door(x, y), Some(Door { index: 2, offset: 3, open: true }));
+            assert!(grid.is_walkable(x, y));
+            assert!(!grid.blocks_projectiles(x, y));
+        }
+        grid.set_door_open(1, true);
+        grid.set_door_open(2, false);
+        assert!(!grid.is_walkable(1, 1) && grid.blocks_projectiles(1, 0));
+    }
+
+    #[test]
+    fn light_cells() {
+        let grid = grid();
+        assert_eq!(grid.light(2, 0), 4);
+        assert_eq!(grid.light(2, 1), 0);
+        assert_eq!(grid.lights(), &[(2, 0, 4)]);
+    }
+}
@@ -1,476 +1,83 @@
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
-use std::path::Path;
-use bytes::{Buf, Bytes};
-use flate2::{FlushDecompress, Status};
-use tracing::{debug, info, warn};
-use tracing::field::debug;
-use crate::data::color::PALETTE;
+use std::io::Read;
+use bytes::Buf;
+use memmap2::Mmap;
+use tracing::info;
+use crate::error::{Error, Result};
+use crate::library::read_image_data;
+pub use crate::library::ImageData;
+pub use crate::pixel::PALETTE_RGBA;
 
-#[derive(Default)]
-pub struct ImageData {
-    pub width: u16,
-    pub height: u16,
-    pub offset_x: i16,
-    pub offset_y: i16,
-    pub bytes: Bytes,
+/// 读取wzl文件中start到end之间的图片, 与`WzlLibrary`使用相同的解码方式
+/// end - start不超过图片头长度时按图片头中的数据长度读取
+pub fn load_image(path: &str, start: u32, end: u32) -> Result<ImageData> {
+    let file = File::open(path)?;
+    let map = unsafe { Mmap::map(&file)? };
+    read_image_data(&map, start, end.saturating_sub(start))
 }
 
-impl ImageData {
-    pub fn from(src: &[u8]) -> Self {
-        // let mut body = src;
-        // let pixel = body.get_u8();
-        // let _compress = body.get_u8();
-        // let _reserve = body.get_u8();
-        // let _compress_level = body.get_u8();
-        //
-        // let width = body.get_u16_le();
-        // let height = body.get_u16_le();
-        // let offset_x = body.get_i16_le();
-        // let offset_y = body.get_i16_le();
-        // let length = body.get_u32_le();
-        //
-        // if length == 0 {
-        //     Self { width, height, offset_x, offset_y, bytes: Bytes::new() }
-        // } else {
-        //     let x = &body[..length as usize];
-        //     let x = deflate_image(x, width as u32 * height as u32);
-        //     let data = byte_to_rgb(pixel, width as usize, height as usize, &x[..]);
-        //     debug!("S3 length: {}, w: {}, h: {}, pixel: {}, x: {}, bytes: {}", length, width, height, pixel, x.len(), data.len());
-        //     Self { width, height, offset_x, offset_y, bytes: Bytes::from(data) }
-        // }
-        Self::from_head_data(&src[..16], &src[16..])
-    }
-
-    pub fn from_head_data(head: &[u8], data: &[u8]) -> Self {
-        let mut body = head;
-        let pixel = body.get_u8();
-        let _compress = body.get_u8();
-        let _reserve = body.get_u8();
-        let _compress_level = body.get_u8();
-
-        let width = body.get_u16_le();
-        let height = body.get_u16_le();
-        let offset_x = body.get_i16_le();
-        let offset_y = body.get_i16_le();
-        let length = body.get_u32_le();
-
-        if length == 0 {
-            let bytes = if data.len() > 0 {
-                Bytes::from(byte_to_rgb(pixel, width as usize, height as usize, data))
-            } else { Bytes::new() };
-            Self { width, height, offset_x, offset_y, bytes }
-        } else {
-            // debug!("S3 length: {}, w: {}, h: {}, pixel: {}", length, width, height, pixel);
-            let x = deflate_image(data, width as u32 * height as u32);
-            let data = byte_to_rgb(pixel, width as usize, height as usize, &x[..]);
-            Self { width, height, offset_x, offset_y, bytes: Bytes::from(data) }
-        }
-    }
-}
-
-pub fn load_image(path: &str, start: u32, end: u32) -> ImageData {
-    // debug!("S1 start: {}, end: {}, len: , path: {}", start, end, path);
-    let file = File::open(path).unwrap();
-    let mut reader = BufReader::new(file);
-    let mut data = vec![0;(end - start) as usize];
-    reader.seek(SeekFrom::Start(start as u64)).unwrap();
-    reader.read(&mut data[..]).unwrap();
-    if end - start == 16 {
-        let mut x = &data[12..];
-        let x = x.get_u32_le();
-        if x > 0 {
-            let mut i = vec![0; x as usize];
-            reader.seek(SeekFrom::Start(start as u64 + 16)).unwrap();
-            reader.read(&mut i[..]).unwrap();
-            return ImageData::from_head_data(&data[..], &i[..]);
-        }
-    }
-    ImageData::from(&data[..])
-}
-
-pub fn load_index(path: &str) -> Vec<u32> {
+pub fn load_index(path: &str) -> Result<Vec<u32>> {
     info!("load_index {}", path);
-    let mut file = File::open(path).unwrap();
-    let len = file.metadata().unwrap().len();
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
     let mut data = Vec::with_capacity(len as usize);
-    file.read_to_end(&mut data).unwrap();
+    file.read_to_end(&mut data)?;
     let mut data = &data[..];
     let len = data.len() / 4;
     let mut result = Vec::with_capacity(len);
     for _ in 0..len {
         result.push(data.get_u32_le());
     }
-    result
+    Ok(result)
 }
 
-pub fn read_wzx(path: &str) -> Vec<u32> {
+pub fn read_wzx(path: &str) -> Result<Vec<u32>> {
     // println!("read_wzx {}", path);
-    let file = File::open(path);
-    if !file.is_ok() {
-        return vec![48];
-    }
-    let mut file = file.unwrap();
-    let len = file.metadata().unwrap().len();
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
     let mut data = Vec::with_capacity(len as usize);
-    file.read_to_end(&mut data).unwrap();
+    file.read_to_end(&mut data)?;
+    if data.len() < 48 {
+        return Err(Error::TruncatedHeader { expected: 48, actual: data.len() });
+    }
     let mut data = &data[48..];
     let len = data.len() / 4;
     let mut result = Vec::with_capacity(len);
     for _ in 0..len {
         result.push(data.get_u32_le());
     }
-    result
-}
-
-fn deflate_image(input: &[u8], size: u32) -> Vec<u8> {
-    let capacity = size * 4;
-    let mut rs: Vec<u8> = Vec::with_capacity(capacity as usize);
-    let status = flate2::Decompress::new(true).decompress_vec(input, &mut rs, FlushDecompress::Finish).unwrap();
-    if status != Status::StreamEnd {
-        warn!("input: {}, output: {}, status: {:?}, size: {}, size*2: {}", input.len(), rs.len(), status, size, size *2);
-        // return deflate_image(input, size * 2);
-    }
-    rs
+    Ok(result)
 }
 
-fn byte_to_rgb(pixel: u8, width: usize, height: usize, bytes: &[u8]) -> Vec<u8> {
+#[cfg(test)]
+mod tests {
+    use crate::library::{ImageLibrary, IndexKind, WzlLibrary};
+    use crate::writer::{Pixels, PixelFormat, SourceImage, WzlWriter};
+    use super::*;
 
-    if pixel == 3 {
-        let mut result = Vec::with_capacity(bytes.len() * 4);
-        let new_width = bytes.len() / height;
-        let n_width = (width + 3) / 4 * 4;
-        let n_width = if n_width > new_width { new_width } else { n_width };
-        let n_width = if new_width > n_width { width } else { n_width };
-        for i in 0..height {
-            for j in 0..width {
-                let x = bytes[(height - i - 1) * n_width + j] as usize * 4;
-                // let c = &PALETTE[x as usize];
-                // data.push()
-                // result.push(c.2);
-                // result.push(c.1);
-                // result.push(c.0);
-                // result.push(if x == 0 {0} else { 255 });
-                result.extend_from_slice(&PALETTE_RGBA[x..x+4]);
-            }
+    #[test]
+    fn load_image_matches_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test");
+        let path = path.to_str().unwrap();
+        let mut writer = WzlWriter::new();
+        for width in [3, 4] {
+            let image = SourceImage { width, height: 2, offset_x: 1, offset_y: 2, pixels: Pixels::Indexed(vec![7; width as usize * 2]) };
+            writer.push(&image, PixelFormat::Palette8).unwrap();
         }
-        return result;
-    } else {
-        let mut result = Vec::with_capacity(bytes.len() * 2);
-        let new_width = bytes.len() / height / 2;
-        let n_width = (width + 3) / 4 * 4;
-        let n_width = if n_width > new_width { new_width } else { n_width };
-        let n_width = if new_width > n_width { width } else { n_width };
-        for i in 0..height {
-            for j in 0..width {
-                let p = (height - i - 1) * n_width * 2 + j * 2;
-                let r = bytes[p + 1] & 0xF8;
-                let g = (((bytes[p + 1] & 0x7) << 3) | (bytes[p] >> 5)) * 4;
-                let b = (bytes[p] & 0x1F) * 8;
-                result.push(r);
-                result.push(g);
-                result.push(b);
-                result.push(if bytes[p] == 0 && bytes[p + 1] == 0 { 0 } else { 255 });
-            }
+        writer.write(path).unwrap();
+
+        let library = WzlLibrary::open(path, IndexKind::Wzx).unwrap();
+        let index = load_index(&format!("{}.idx", path)).unwrap();
+        let wzl = format!("{}.wzl", path);
+        for i in 0..2 {
+            let image = load_image(&wzl, index[i], index[i + 1]).unwrap();
+            assert_eq!(image.bytes, library.load_image(i as u32).unwrap().bytes);
+            // 只给出图片头时按图片头中的长度读取
+            let image = load_image(&wzl, index[i], index[i] + 16).unwrap();
+            assert_eq!(image.bytes, library.load_image(i as u32).unwrap().bytes);
         }
-        return result;
+        assert!(matches!(load_image(&wzl, index[2], index[2] + 16), Err(Error::TruncatedData { .. })));
+        assert_eq!(read_wzx(&format!("{}.wzx", path)).unwrap(), &index[..2]);
     }
 }
-
-pub const PALETTE_RGBA: [u8; 1024] = [
-    0x00,0x00,0x00,0x00,0x80,0x00,0x00,0xFF,0x00,0x80,0x00,0xFF,0x80,0x80,0x00,0xFF,0x00,0x00,0x80,0xFF,0x80,0x00,0x80,0xFF,0x00,0x80,0x80,0xFF,0xC0,0xC0,0xC0,0xFF,
-    0x55,0x80,0x97,0xFF,0x9D,0xB9,0xC8,0xFF,0x7B,0x73,0x73,0xFF,0x2D,0x29,0x29,0xFF,0x5A,0x52,0x52,0xFF,0x63,0x5A,0x5A,0xFF,0x42,0x39,0x39,0xFF,0x1D,0x18,0x18,0xFF,
-    0x18,0x10,0x10,0xFF,0x29,0x18,0x18,0xFF,0x10,0x08,0x08,0xFF,0xF2,0x79,0x71,0xFF,0xE1,0x67,0x5F,0xFF,0xFF,0x5A,0x5A,0xFF,0xFF,0x31,0x31,0xFF,0xD6,0x5A,0x52,0xFF,
-    0x94,0x10,0x00,0xFF,0x94,0x29,0x18,0xFF,0x39,0x08,0x00,0xFF,0x73,0x10,0x00,0xFF,0xB5,0x18,0x00,0xFF,0xBD,0x63,0x52,0xFF,0x42,0x18,0x10,0xFF,0xFF,0xAA,0x99,0xFF,
-    0x5A,0x10,0x00,0xFF,0x73,0x39,0x29,0xFF,0xA5,0x4A,0x31,0xFF,0x94,0x7B,0x73,0xFF,0xBD,0x52,0x31,0xFF,0x52,0x21,0x10,0xFF,0x7B,0x31,0x18,0xFF,0x2D,0x18,0x10,0xFF,
-    0x8C,0x4A,0x31,0xFF,0x94,0x29,0x00,0xFF,0xBD,0x31,0x00,0xFF,0xC6,0x73,0x52,0xFF,0x6B,0x31,0x18,0xFF,0xC6,0x6B,0x42,0xFF,0xCE,0x4A,0x00,0xFF,0xA5,0x63,0x39,0xFF,
-    0x5A,0x31,0x18,0xFF,0x2A,0x10,0x00,0xFF,0x15,0x08,0x00,0xFF,0x3A,0x18,0x00,0xFF,0x08,0x00,0x00,0xFF,0x29,0x00,0x00,0xFF,0x4A,0x00,0x00,0xFF,0x9D,0x00,0x00,0xFF,
-    0xDC,0x00,0x00,0xFF,0xDE,0x00,0x00,0xFF,0xFB,0x00,0x00,0xFF,0x9C,0x73,0x52,0xFF,0x94,0x6B,0x4A,0xFF,0x73,0x4A,0x29,0xFF,0x52,0x31,0x18,0xFF,0x8C,0x4A,0x18,0xFF,
-    0x88,0x44,0x11,0xFF,0x4A,0x21,0x00,0xFF,0x21,0x18,0x10,0xFF,0xD6,0x94,0x5A,0xFF,0xC6,0x6B,0x21,0xFF,0xEF,0x6B,0x00,0xFF,0xFF,0x77,0x00,0xFF,0xA5,0x94,0x84,0xFF,
-    0x42,0x31,0x21,0xFF,0x18,0x10,0x08,0xFF,0x29,0x18,0x08,0xFF,0x21,0x10,0x00,0xFF,0x39,0x29,0x18,0xFF,0x8C,0x63,0x39,0xFF,0x42,0x29,0x10,0xFF,0x6B,0x42,0x18,0xFF,
-    0x7B,0x4A,0x18,0xFF,0x94,0x4A,0x00,0xFF,0x8C,0x84,0x7B,0xFF,0x6B,0x63,0x5A,0xFF,0x4A,0x42,0x39,0xFF,0x29,0x21,0x18,0xFF,0x46,0x39,0x29,0xFF,0xB5,0xA5,0x94,0xFF,
-    0x7B,0x6B,0x5A,0xFF,0xCE,0xB1,0x94,0xFF,0xA5,0x8C,0x73,0xFF,0x8C,0x73,0x5A,0xFF,0xB5,0x94,0x73,0xFF,0xD6,0xA5,0x73,0xFF,0xEF,0xA5,0x4A,0xFF,0xEF,0xC6,0x8C,0xFF,
-    0x7B,0x63,0x42,0xFF,0x6B,0x56,0x39,0xFF,0xBD,0x94,0x5A,0xFF,0x63,0x39,0x00,0xFF,0xD6,0xC6,0xAD,0xFF,0x52,0x42,0x29,0xFF,0x94,0x63,0x18,0xFF,0xEF,0xD6,0xAD,0xFF,
-    0xA5,0x8C,0x63,0xFF,0x63,0x5A,0x4A,0xFF,0xBD,0xA5,0x7B,0xFF,0x5A,0x42,0x18,0xFF,0xBD,0x8C,0x31,0xFF,0x35,0x31,0x29,0xFF,0x94,0x84,0x63,0xFF,0x7B,0x6B,0x4A,0xFF,
-    0xA5,0x8C,0x5A,0xFF,0x5A,0x4A,0x29,0xFF,0x9C,0x7B,0x39,0xFF,0x42,0x31,0x10,0xFF,0xEF,0xAD,0x21,0xFF,0x18,0x10,0x00,0xFF,0x29,0x21,0x00,0xFF,0x9C,0x6B,0x00,0xFF,
-    0x94,0x84,0x5A,0xFF,0x52,0x42,0x18,0xFF,0x6B,0x5A,0x29,0xFF,0x7B,0x63,0x21,0xFF,0x9C,0x7B,0x21,0xFF,0xDE,0xA5,0x00,0xFF,0x5A,0x52,0x39,0xFF,0x31,0x29,0x10,0xFF,
-    0xCE,0xBD,0x7B,0xFF,0x63,0x5A,0x39,0xFF,0x94,0x84,0x4A,0xFF,0xC6,0xA5,0x29,0xFF,0x10,0x9C,0x18,0xFF,0x42,0x8C,0x4A,0xFF,0x31,0x8C,0x42,0xFF,0x10,0x94,0x29,0xFF,
-    0x08,0x18,0x10,0xFF,0x08,0x18,0x18,0xFF,0x08,0x29,0x10,0xFF,0x18,0x42,0x29,0xFF,0xA5,0xB5,0xAD,0xFF,0x6B,0x73,0x73,0xFF,0x18,0x29,0x29,0xFF,0x18,0x42,0x4A,0xFF,
-    0x31,0x42,0x4A,0xFF,0x63,0xC6,0xDE,0xFF,0x44,0xDD,0xFF,0xFF,0x8C,0xD6,0xEF,0xFF,0x73,0x6B,0x39,0xFF,0xF7,0xDE,0x39,0xFF,0xF7,0xEF,0x8C,0xFF,0xF7,0xE7,0x00,0xFF,
-    0x6B,0x6B,0x5A,0xFF,0x5A,0x8C,0xA5,0xFF,0x39,0xB5,0xEF,0xFF,0x4A,0x9C,0xCE,0xFF,0x31,0x84,0xB5,0xFF,0x31,0x52,0x6B,0xFF,0xDE,0xDE,0xD6,0xFF,0xBD,0xBD,0xB5,0xFF,
-    0x8C,0x8C,0x84,0xFF,0xF7,0xF7,0xDE,0xFF,0x00,0x08,0x18,0xFF,0x08,0x18,0x39,0xFF,0x08,0x10,0x29,0xFF,0x08,0x18,0x00,0xFF,0x08,0x29,0x00,0xFF,0x00,0x52,0xA5,0xFF,
-    0x00,0x7B,0xDE,0xFF,0x10,0x29,0x4A,0xFF,0x10,0x39,0x6B,0xFF,0x10,0x52,0x8C,0xFF,0x21,0x5A,0xA5,0xFF,0x10,0x31,0x5A,0xFF,0x10,0x42,0x84,0xFF,0x31,0x52,0x84,0xFF,
-    0x18,0x21,0x31,0xFF,0x4A,0x5A,0x7B,0xFF,0x52,0x6B,0xA5,0xFF,0x29,0x39,0x63,0xFF,0x10,0x4A,0xDE,0xFF,0x29,0x29,0x21,0xFF,0x4A,0x4A,0x39,0xFF,0x29,0x29,0x18,0xFF,
-    0x4A,0x4A,0x29,0xFF,0x7B,0x7B,0x42,0xFF,0x9C,0x9C,0x4A,0xFF,0x5A,0x5A,0x29,0xFF,0x42,0x42,0x14,0xFF,0x39,0x39,0x00,0xFF,0x59,0x59,0x00,0xFF,0xCA,0x35,0x2C,0xFF,
-    0x6B,0x73,0x21,0xFF,0x29,0x31,0x00,0xFF,0x31,0x39,0x10,0xFF,0x31,0x39,0x18,0xFF,0x42,0x4A,0x00,0xFF,0x52,0x63,0x18,0xFF,0x5A,0x73,0x29,0xFF,0x31,0x4A,0x18,0xFF,
-    0x18,0x21,0x00,0xFF,0x18,0x31,0x00,0xFF,0x18,0x39,0x10,0xFF,0x63,0x84,0x4A,0xFF,0x6B,0xBD,0x4A,0xFF,0x63,0xB5,0x4A,0xFF,0x63,0xBD,0x4A,0xFF,0x5A,0x9C,0x4A,0xFF,
-    0x4A,0x8C,0x39,0xFF,0x63,0xC6,0x4A,0xFF,0x63,0xD6,0x4A,0xFF,0x52,0x84,0x4A,0xFF,0x31,0x73,0x29,0xFF,0x63,0xC6,0x5A,0xFF,0x52,0xBD,0x4A,0xFF,0x10,0xFF,0x00,0xFF,
-    0x18,0x29,0x18,0xFF,0x4A,0x88,0x4A,0xFF,0x4A,0xE7,0x4A,0xFF,0x00,0x5A,0x00,0xFF,0x00,0x88,0x00,0xFF,0x00,0x94,0x00,0xFF,0x00,0xDE,0x00,0xFF,0x00,0xEE,0x00,0xFF,
-    0x00,0xFB,0x00,0xFF,0x4A,0x5A,0x94,0xFF,0x63,0x73,0xB5,0xFF,0x7B,0x8C,0xD6,0xFF,0x6B,0x7B,0xD6,0xFF,0x77,0x88,0xFF,0xFF,0xC6,0xC6,0xCE,0xFF,0x94,0x94,0x9C,0xFF,
-    0x9C,0x94,0xC6,0xFF,0x31,0x31,0x39,0xFF,0x29,0x18,0x84,0xFF,0x18,0x00,0x84,0xFF,0x4A,0x42,0x52,0xFF,0x52,0x42,0x7B,0xFF,0x63,0x5A,0x73,0xFF,0xCE,0xB5,0xF7,0xFF,
-    0x8C,0x7B,0x9C,0xFF,0x77,0x22,0xCC,0xFF,0xDD,0xAA,0xFF,0xFF,0xF0,0xB4,0x2A,0xFF,0xDF,0x00,0x9F,0xFF,0xE3,0x17,0xB3,0xFF,0xFF,0xFB,0xF0,0xFF,0xA0,0xA0,0xA4,0xFF,
-    0x80,0x80,0x80,0xFF,0xFF,0x00,0x00,0xFF,0x00,0xFF,0x00,0xFF,0xFF,0xFF,0x00,0xFF,0x00,0x00,0xFF,0xFF,0xFF,0x00,0xFF,0xFF,0x00,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,
-];
-
-mod color {
-    pub struct Color(pub u8, pub u8, pub u8);
-
-    pub const PALETTE: [Color; 256] = [
-        Color(0x00, 0x00, 0x00),
-        Color(0x00, 0x00, 0x80),
-        Color(0x00, 0x80, 0x00),
-        Color(0x00, 0x80, 0x80),
-        Color(0x80, 0x00, 0x00),
-        Color(0x80, 0x00, 0x80),
-        Color(0x80, 0x80, 0x00),
-        Color(0xC0, 0xC0, 0xC0),
-        Color(0x97, 0x80, 0x55),
-        Color(0xC8, 0xB9, 0x9D),
-        Color(0x73, 0x73, 0x7B),
-        Color(0x29, 0x29, 0x2D),
-        Color(0x52, 0x52, 0x5A),
-        Color(0x5A, 0x5A, 0x63),
-        Color(0x39, 0x39, 0x42),
-        Color(0x18, 0x18, 0x1D),
-        Color(0x10, 0x10, 0x18),
-        Color(0x18, 0x18, 0x29),
-        Color(0x08, 0x08, 0x10),
-        Color(0x71, 0x79, 0xF2),
-        Color(0x5F, 0x67, 0xE1),
-        Color(0x5A, 0x5A, 0xFF),
-        Color(0x31, 0x31, 0xFF),
-        Color(0x52, 0x5A, 0xD6),
-        Color(0x00, 0x10, 0x94),
-        Color(0x18, 0x29, 0x94),
-        Color(0x00, 0x08, 0x39),
-        Color(0x00, 0x10, 0x73),
-        Color(0x00, 0x18, 0xB5),
-        Color(0x52, 0x63, 0xBD),
-        Color(0x10, 0x18, 0x42),
-        Color(0x99, 0xAA, 0xFF),
-        Color(0x00, 0x10, 0x5A),
-        Color(0x29, 0x39, 0x73),
-        Color(0x31, 0x4A, 0xA5),
-        Color(0x73, 0x7B, 0x94),
-        Color(0x31, 0x52, 0xBD),
-        Color(0x10, 0x21, 0x52),
-        Color(0x18, 0x31, 0x7B),
-        Color(0x10, 0x18, 0x2D),
-        Color(0x31, 0x4A, 0x8C),
-        Color(0x00, 0x29, 0x94),
-        Color(0x00, 0x31, 0xBD),
-        Color(0x52, 0x73, 0xC6),
-        Color(0x18, 0x31, 0x6B),
-        Color(0x42, 0x6B, 0xC6),
-        Color(0x00, 0x4A, 0xCE),
-        Color(0x39, 0x63, 0xA5),
-        Color(0x18, 0x31, 0x5A),
-        Color(0x00, 0x10, 0x2A),
-        Color(0x00, 0x08, 0x15),
-        Color(0x00, 0x18, 0x3A),
-        Color(0x00, 0x00, 0x08),
-        Color(0x00, 0x00, 0x29),
-        Color(0x00, 0x00, 0x4A),
-        Color(0x00, 0x00, 0x9D),
-        Color(0x00, 0x00, 0xDC),
-        Color(0x00, 0x00, 0xDE),
-        Color(0x00, 0x00, 0xFB),
-        Color(0x52, 0x73, 0x9C),
-        Color(0x4A, 0x6B, 0x94),
-        Color(0x29, 0x4A, 0x73),
-        Color(0x18, 0x31, 0x52),
-        Color(0x18, 0x4A, 0x8C),
-        Color(0x11, 0x44, 0x88),
-        Color(0x00, 0x21, 0x4A),
-        Color(0x10, 0x18, 0x21),
-        Color(0x5A, 0x94, 0xD6),
-        Color(0x21, 0x6B, 0xC6),
-        Color(0x00, 0x6B, 0xEF),
-        Color(0x00, 0x77, 0xFF),
-        Color(0x84, 0x94, 0xA5),
-        Color(0x21, 0x31, 0x42),
-        Color(0x08, 0x10, 0x18),
-        Color(0x08, 0x18, 0x29),
-        Color(0x00, 0x10, 0x21),
-        Color(0x18, 0x29, 0x39),
-        Color(0x39, 0x63, 0x8C),
-        Color(0x10, 0x29, 0x42),
-        Color(0x18, 0x42, 0x6B),
-        Color(0x18, 0x4A, 0x7B),
-        Color(0x00, 0x4A, 0x94),
-        Color(0x7B, 0x84, 0x8C),
-        Color(0x5A, 0x63, 0x6B),
-        Color(0x39, 0x42, 0x4A),
-        Color(0x18, 0x21, 0x29),
-        Color(0x29, 0x39, 0x46),
-        Color(0x94, 0xA5, 0xB5),
-        Color(0x5A, 0x6B, 0x7B),
-        Color(0x94, 0xB1, 0xCE),
-        Color(0x73, 0x8C, 0xA5),
-        Color(0x5A, 0x73, 0x8C),
-        Color(0x73, 0x94, 0xB5),
-        Color(0x73, 0xA5, 0xD6),
-        Color(0x4A, 0xA5, 0xEF),
-        Color(0x8C, 0xC6, 0xEF),
-        Color(0x42, 0x63, 0x7B),
-        Color(0x39, 0x56, 0x6B),
-        Color(0x5A, 0x94, 0xBD),
-        Color(0x00, 0x39, 0x63),
-        Color(0xAD, 0xC6, 0xD6),
-        Color(0x29, 0x42, 0x52),
-        Color(0x18, 0x63, 0x94),
-        Color(0xAD, 0xD6, 0xEF),
-        Color(0x63, 0x8C, 0xA5),
-        Color(0x4A, 0x5A, 0x63),
-        Color(0x7B, 0xA5, 0xBD),
-        Color(0x18, 0x42, 0x5A),
-        Color(0x31, 0x8C, 0xBD),
-        Color(0x29, 0x31, 0x35),
-        Color(0x63, 0x84, 0x94),
-        Color(0x4A, 0x6B, 0x7B),
-        Color(0x5A, 0x8C, 0xA5),
-        Color(0x29, 0x4A, 0x5A),
-        Color(0x39, 0x7B, 0x9C),
-        Color(0x10, 0x31, 0x42),
-        Color(0x21, 0xAD, 0xEF),
-        Color(0x00, 0x10, 0x18),
-        Color(0x00, 0x21, 0x29),
-        Color(0x00, 0x6B, 0x9C),
-        Color(0x5A, 0x84, 0x94),
-        Color(0x18, 0x42, 0x52),
-        Color(0x29, 0x5A, 0x6B),
-        Color(0x21, 0x63, 0x7B),
-        Color(0x21, 0x7B, 0x9C),
-        Color(0x00, 0xA5, 0xDE),
-        Color(0x39, 0x52, 0x5A),
-        Color(0x10, 0x29, 0x31),
-        Color(0x7B, 0xBD, 0xCE),
-        Color(0x39, 0x5A, 0x63),
-        Color(0x4A, 0x84, 0x94),
-        Color(0x29, 0xA5, 0xC6),
-        Color(0x18, 0x9C, 0x10),
-        Color(0x4A, 0x8C, 0x42),
-        Color(0x42, 0x8C, 0x31),
-        Color(0x29, 0x94, 0x10),
-        Color(0x10, 0x18, 0x08),
-        Color(0x18, 0x18, 0x08),
-        Color(0x10, 0x29, 0x08),
-        Color(0x29, 0x42, 0x18),
-        Color(0xAD, 0xB5, 0xA5),
-        Color(0x73, 0x73, 0x6B),
-        Color(0x29, 0x29, 0x18),
-        Color(0x4A, 0x42, 0x18),
-        Color(0x4A, 0x42, 0x31),
-        Color(0xDE, 0xC6, 0x63),
-        Color(0xFF, 0xDD, 0x44),
-        Color(0xEF, 0xD6, 0x8C),
-        Color(0x39, 0x6B, 0x73),
-        Color(0x39, 0xDE, 0xF7),
-        Color(0x8C, 0xEF, 0xF7),
-        Color(0x00, 0xE7, 0xF7),
-        Color(0x5A, 0x6B, 0x6B),
-        Color(0xA5, 0x8C, 0x5A),
-        Color(0xEF, 0xB5, 0x39),
-        Color(0xCE, 0x9C, 0x4A),
-        Color(0xB5, 0x84, 0x31),
-        Color(0x6B, 0x52, 0x31),
-        Color(0xD6, 0xDE, 0xDE),
-        Color(0xB5, 0xBD, 0xBD),
-        Color(0x84, 0x8C, 0x8C),
-        Color(0xDE, 0xF7, 0xF7),
-        Color(0x18, 0x08, 0x00),
-        Color(0x39, 0x18, 0x08),
-        Color(0x29, 0x10, 0x08),
-        Color(0x00, 0x18, 0x08),
-        Color(0x00, 0x29, 0x08),
-        Color(0xA5, 0x52, 0x00),
-        Color(0xDE, 0x7B, 0x00),
-        Color(0x4A, 0x29, 0x10),
-        Color(0x6B, 0x39, 0x10),
-        Color(0x8C, 0x52, 0x10),
-        Color(0xA5, 0x5A, 0x21),
-        Color(0x5A, 0x31, 0x10),
-        Color(0x84, 0x42, 0x10),
-        Color(0x84, 0x52, 0x31),
-        Color(0x31, 0x21, 0x18),
-        Color(0x7B, 0x5A, 0x4A),
-        Color(0xA5, 0x6B, 0x52),
-        Color(0x63, 0x39, 0x29),
-        Color(0xDE, 0x4A, 0x10),
-        Color(0x21, 0x29, 0x29),
-        Color(0x39, 0x4A, 0x4A),
-        Color(0x18, 0x29, 0x29),
-        Color(0x29, 0x4A, 0x4A),
-        Color(0x42, 0x7B, 0x7B),
-        Color(0x4A, 0x9C, 0x9C),
-        Color(0x29, 0x5A, 0x5A),
-        Color(0x14, 0x42, 0x42),
-        Color(0x00, 0x39, 0x39),
-        Color(0x00, 0x59, 0x59),
-        Color(0x2C, 0x35, 0xCA),
-        Color(0x21, 0x73, 0x6B),
-        Color(0x00, 0x31, 0x29),
-        Color(0x10, 0x39, 0x31),
-        Color(0x18, 0x39, 0x31),
-        Color(0x00, 0x4A, 0x42),
-        Color(0x18, 0x63, 0x52),
-        Color(0x29, 0x73, 0x5A),
-        Color(0x18, 0x4A, 0x31),
-        Color(0x00, 0x21, 0x18),
-        Color(0x00, 0x31, 0x18),
-        Color(0x10, 0x39, 0x18),
-        Color(0x4A, 0x84, 0x63),
-        Color(0x4A, 0xBD, 0x6B),
-        Color(0x4A, 0xB5, 0x63),
-        Color(0x4A, 0xBD, 0x63),
-        Color(0x4A, 0x9C, 0x5A),
-        Color(0x39, 0x8C, 0x4A),
-        Color(0x4A, 0xC6, 0x63),
-        Color(0x4A, 0xD6, 0x63),
-        Color(0x4A, 0x84, 0x52),
-        Color(0x29, 0x73, 0x31),
-        Color(0x5A, 0xC6, 0x63),
-        Color(0x4A, 0xBD, 0x52),
-        Color(0x00, 0xFF, 0x10),
-        Color(0x18, 0x29, 0x18),
-        Color(0x4A, 0x88, 0x4A),
-        Color(0x4A, 0xE7, 0x4A),
-        Color(0x00, 0x5A, 0x00),
-        Color(0x00, 0x88, 0x00),
-        Color(0x00, 0x94, 0x00),
-        Color(0x00, 0xDE, 0x00),
-        Color(0x00, 0xEE, 0x00),
-        Color(0x00, 0xFB, 0x00),
-        Color(0x94, 0x5A, 0x4A),
-        Color(0xB5, 0x73, 0x63),
-        Color(0xD6, 0x8C, 0x7B),
-        Color(0xD6, 0x7B, 0x6B),
-        Color(0xFF, 0x88, 0x77),
-        Color(0xCE, 0xC6, 0xC6),
-        Color(0x9C, 0x94, 0x94),
-        Color(0xC6, 0x94, 0x9C),
-        Color(0x39, 0x31, 0x31),
-        Color(0x84, 0x18, 0x29),
-        Color(0x84, 0x00, 0x18),
-        Color(0x52, 0x42, 0x4A),
-        Color(0x7B, 0x42, 0x52),
-        Color(0x73, 0x5A, 0x63),
-        Color(0xF7, 0xB5, 0xCE),
-        Color(0x9C, 0x7B, 0x8C),
-        Color(0xCC, 0x22, 0x77),
-        Color(0xFF, 0xAA, 0xDD),
-        Color(0x2A, 0xB4, 0xF0),
-        Color(0x9F, 0x00, 0xDF),
-        Color(0xB3, 0x17, 0xE3),
-        Color(0xF0, 0xFB, 0xFF),
-        Color(0xA4, 0xA0, 0xA0),
-        Color(0x80, 0x80, 0x80),
-        Color(0x00, 0x00, 0xFF),
-        Color(0x00, 0xFF, 0x00),
-        Color(0x00, 0xFF, 0xFF),
-        Color(0xFF, 0x00, 0x00),
-        Color(0xFF, 0x00, 0xFF),
-        Color(0xFF, 0xFF, 0x00),
-        Color(0xFF, 0xFF, 0xFF),
-    ];
-}
\ No newline at end of file
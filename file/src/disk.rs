@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use bytes::{Buf, BufMut, Bytes};
+use tracing::{debug, warn};
+use crate::error::Result;
+use crate::library::ImageData;
+
+const ENTRY_MAGIC: &[u8; 4] = b"ICRC";
+const ENTRY_VERSION: u8 = 1;
+/// magic(4) version(1) reserve(3) w(2) h(2) ox(2) oy(2)
+const ENTRY_HEADER_SIZE: usize = 16;
+const ENTRY_SUFFIX: &str = "rgba";
+
+static TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 解码后图片的磁盘缓存, 每张图片一个文件, 保存未压缩的RGBA, 读取时不需要解码
+/// 每个图片库一个目录, 目录名包含源文件的指纹(路径, 大小, 修改时间)
+/// 源文件变化后使用新目录, 同一图片库的旧目录在第一次使用新目录时删除
+pub struct DiskCache {
+    dir: PathBuf,
+    /// 图片库当前的指纹和缓存目录
+    libraries: Mutex<HashMap<u32, (u64, PathBuf)>>,
+}
+
+impl DiskCache {
+
+    /// dir不存在时创建
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, libraries: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 删除所有缓存文件
+    pub fn clear(&self) -> Result<()> {
+        let mut libraries = self.libraries.lock().unwrap_or_else(|e| e.into_inner());
+        libraries.clear();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 读取缓存, 不存在, 源文件已变化或缓存文件损坏时返回None
+    pub(crate) fn get(&self, library: u32, sources: &[PathBuf], index: u32) -> Option<ImageData> {
+        let path = self.library_dir(library, sources)?.join(entry_name(index));
+        let data = fs::read(&path).ok()?;
+        let image = parse_entry(Bytes::from(data));
+        if image.is_none() {
+            warn!("bad disk cache entry: {:?}", path);
+            let _ = fs::remove_file(&path);
+        }
+        image
+    }
+
+    /// 写入缓存, 先写临时文件再重命名, 失败时只记录日志
+    pub(crate) fn put(&self, library: u32, sources: &[PathBuf], index: u32, image: &ImageData) {
+        let Some(dir) = self.library_dir(library, sources) else { return };
+        let mut data = Vec::with_capacity(ENTRY_HEADER_SIZE + image.bytes.len());
+        data.put_slice(ENTRY_MAGIC);
+        data.put_u8(ENTRY_VERSION);
+        data.put_bytes(0, 3);
+        data.put_u16_le(image.width);
+        data.put_u16_le(image.height);
+        data.put_i16_le(image.offset_x);
+        data.put_i16_le(image.offset_y);
+        data.put_slice(&image.bytes);
+
+        let path = dir.join(entry_name(index));
+        let temp = dir.join(format!("{:08x}.{}-{}.tmp", index, std::process::id(), TEMP_ID.fetch_add(1, Ordering::Relaxed)));
+        if let Err(e) = fs::write(&temp, &data).and_then(|_| fs::rename(&temp, &path)) {
+            warn!("write disk cache {:?}: {}", path, e);
+            let _ = fs::remove_file(&temp);
+        }
+    }
+
+    /// 返回图片库当前指纹对应的目录, 没有源文件或无法读取文件信息时返回None
+    fn library_dir(&self, library: u32, sources: &[PathBuf]) -> Option<PathBuf> {
+        let fingerprint = fingerprint(sources)?;
+        let mut libraries = self.libraries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((value, dir)) = libraries.get(&library) {
+            if *value == fingerprint {
+                return Some(dir.clone());
+            }
+        }
+
+        let prefix = format!("{:08x}-", library);
+        let name = format!("{}{:016x}", prefix, fingerprint);
+        let dir = self.dir.join(&name);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!("create disk cache {:?}: {}", dir, e);
+            return None;
+        }
+        for entry in fs::read_dir(&self.dir).ok()?.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with(&prefix) && file_name != name {
+                debug!("remove stale disk cache: {}", file_name);
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+        libraries.insert(library, (fingerprint, dir.clone()));
+        Some(dir)
+    }
+}
+
+fn entry_name(index: u32) -> String {
+    format!("{:08x}.{}", index, ENTRY_SUFFIX)
+}
+
+fn parse_entry(data: Bytes) -> Option<ImageData> {
+    if data.len() < ENTRY_HEADER_SIZE || &data[..4] != ENTRY_MAGIC || data[4] != ENTRY_VERSION {
+        return None;
+    }
+    let mut head = &data[8..ENTRY_HEADER_SIZE];
+    let (width, height) = (head.get_u16_le(), head.get_u16_le());
+    let (offset_x, offset_y) = (head.get_i16_le(), head.get_i16_le());
+    if data.len() - ENTRY_HEADER_SIZE != width as usize * height as usize * 4 {
+        return None;
+    }
+    Some(ImageData { width, height, offset_x, offset_y, bytes: data.slice(ENTRY_HEADER_SIZE..) })
+}
+
+/// 按路径, 大小和修改时间计算, 使用FNV-1a, 不同版本的程序结果一致
+fn fingerprint(sources: &[PathBuf]) -> Option<u64> {
+    if sources.is_empty() {
+        return None;
+    }
+    let mut hasher = Fnv(0xcbf29ce484222325);
+    for path in sources {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).map(|x| x.as_nanos() as u64).unwrap_or(0);
+        hasher.write(path.to_string_lossy().as_bytes());
+        hasher.write_u64(meta.len());
+        hasher.write_u64(modified);
+    }
+    Some(hasher.finish())
+}
+
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use super::*;
+
+    fn image(seed: u8) -> ImageData {
+        ImageData { width: 2, height: 3, offset_x: -1, offset_y: 4, bytes: Bytes::from(vec![seed; 24]) }
+    }
+
+    /// 缓存目录下的子目录名
+    fn dirs(cache: &DiskCache) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(cache.dir()).unwrap().flatten()
+            .filter(|x| x.path().is_dir()).map(|x| x.file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn put_then_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("tiles.wzl");
+        fs::write(&source, [1; 100]).unwrap();
+        let sources = vec![source];
+        let cache = DiskCache::new(dir.path().join("cache")).unwrap();
+
+        assert!(cache.get(1, &sources, 0).is_none());
+        cache.put(1, &sources, 0, &image(7));
+        let cached = cache.get(1, &sources, 0).unwrap();
+        assert_eq!((cached.width, cached.height, cached.offset_x, cached.offset_y), (2, 3, -1, 4));
+        assert_eq!(cached.bytes, image(7).bytes);
+        assert!(cache.get(1, &sources, 1).is_none());
+        assert!(cache.get(2, &sources, 0).is_none());
+
+        // 重新创建后仍然命中
+        let cache = DiskCache::new(cache.dir()).unwrap();
+        assert_eq!(cache.get(1, &sources, 0).unwrap().bytes, image(7).bytes);
+
+        // 没有源文件时不缓存
+        cache.put(3, &[], 0, &image(1));
+        assert!(cache.get(3, &[], 0).is_none());
+        assert!(dirs(&cache).iter().all(|x| !x.starts_with("00000003-")));
+
+        // 损坏的缓存文件会删除
+        let path = cache.dir().join(&dirs(&cache)[0]).join(entry_name(0));
+        assert!(dirs(&cache)[0].starts_with("00000001-"));
+        fs::write(&path, b"ICRC").unwrap();
+        assert!(cache.get(1, &sources, 0).is_none());
+        assert!(!path.exists());
+
+        cache.put(1, &sources, 0, &image(7));
+        cache.clear().unwrap();
+        assert!(dirs(&cache).is_empty());
+        assert!(cache.get(1, &sources, 0).is_none());
+    }
+
+    #[test]
+    fn source_change_invalidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wzl, wzx) = (dir.path().join("tiles.wzl"), dir.path().join("tiles.wzx"));
+        fs::write(&wzl, [1; 100]).unwrap();
+        fs::write(&wzx, [2; 52]).unwrap();
+        let sources = vec![wzl.clone(), wzx.clone()];
+        let cache = DiskCache::new(dir.path().join("cache")).unwrap();
+        cache.put(1, &sources, 0, &image(7));
+        let first = dirs(&cache);
+
+        // 大小变化
+        fs::write(&wzx, [2; 56]).unwrap();
+        assert!(cache.get(1, &sources, 0).is_none());
+        cache.put(1, &sources, 0, &image(8));
+        assert_eq!(cache.get(1, &sources, 0).unwrap().bytes, image(8).bytes);
+        let second = dirs(&cache);
+        assert_eq!(second.len(), 1);
+        assert_ne!(first, second);
+
+        // 只有修改时间变化
+        let modified = fs::metadata(&wzl).unwrap().modified().unwrap();
+        File::options().write(true).open(&wzl).unwrap().set_modified(modified - Duration::from_secs(60)).unwrap();
+        assert!(cache.get(1, &sources, 0).is_none());
+        assert_ne!(dirs(&cache), second);
+
+        // 恢复原来的源文件时旧缓存已经删除
+        fs::write(&wzx, [2; 52]).unwrap();
+        File::options().write(true).open(&wzl).unwrap().set_modified(SystemTime::now()).unwrap();
+        assert!(cache.get(1, &sources, 0).is_none());
+    }
+
+    #[test]
+    fn stale_dirs_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("tiles.wzl");
+        fs::write(&source, [1; 100]).unwrap();
+        let root = dir.path().join("cache");
+        // 上次运行留下的目录
+        for name in ["00000001-0000000000000001", "00000001-0000000000000002", "00000002-0000000000000001"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join(entry_name(0)), [0]).unwrap();
+        }
+        let cache = DiskCache::new(&root).unwrap();
+        cache.put(1, &[source], 0, &image(7));
+        let names = dirs(&cache);
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"00000002-0000000000000001".to_string()));
+        assert!(names.iter().all(|x| !x.starts_with("00000001-000000000000000")));
+    }
+}
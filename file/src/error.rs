@@ -0,0 +1,117 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::Arc;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// 资源文件读取错误
+/// 实现Clone以便与解码结果一起放入缓存
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// 文件读写错误
+    Io(Arc<io::Error>),
+    /// 文件头长度不足
+    TruncatedHeader { expected: usize, actual: usize },
+    /// 数据长度不足
+    TruncatedData { expected: usize, actual: usize },
+    /// 索引越界或索引值非法
+    BadIndex { index: u32, len: usize },
+    /// 地图宽高与数据长度不匹配
+    BadMapSize { width: u32, height: u32, length: usize },
+    /// 无法识别的地图格式
+    UnknownMapFormat,
+    /// 不支持写入的地图格式
+    UnsupportedMapFormat,
+    /// zlib解压失败
+    Decompress(String),
+    /// 不支持的像素格式
+    UnsupportedPixelFormat(u8),
+    /// 不支持的颜色数, wil仅支持256和65536色
+    UnsupportedColorCount(u32),
+    /// 不支持的文件版本
+    UnsupportedVersion(u32),
+    /// 不是RIFF/WAVE格式或缺少fmt/data块
+    BadSoundHeader,
+    /// 不支持的声音编码格式, 仅支持PCM(1)
+    UnsupportedSoundFormat(u16),
+    /// 不支持的样本位数, 仅支持8/16位
+    UnsupportedSampleBits(u16),
+    /// 图片大于图集页面
+    ImageTooLarge { width: u16, height: u16 },
+    /// 图集页数已达上限
+    AtlasFull,
+    /// 图片或元数据编码失败
+    Encode(String),
+    /// 图片或元数据解析失败
+    Decode(String),
+    /// 未注册的文件编号
+    UnknownFile(u32),
+    /// 空图片(索引为0或宽高为0)
+    EmptyImage,
+    /// 图片头中的宽高或长度非法
+    BadImage(String),
+    /// 资源包文件头或目录非法
+    BadPack(String),
+    /// 校验失败的文件
+    ChecksumMismatch(String),
+    /// 图片库清单格式错误或编号重复
+    BadManifest(String),
+    /// 数据KEY超出范围或无法解析
+    BadKey(String),
+    /// 参数不合法
+    InvalidOptions(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::TruncatedHeader { expected, actual } => write!(f, "truncated header: expected {} bytes, got {}", expected, actual),
+            Error::TruncatedData { expected, actual } => write!(f, "truncated data: expected {} bytes, got {}", expected, actual),
+            Error::BadIndex { index, len } => write!(f, "bad index: {} (len: {})", index, len),
+            Error::BadMapSize { width, height, length } => write!(f, "bad map size: {}x{}, body: {} bytes", width, height, length),
+            Error::UnknownMapFormat => write!(f, "unknown map format"),
+            Error::UnsupportedMapFormat => write!(f, "unsupported map format"),
+            Error::Decompress(e) => write!(f, "decompress failed: {}", e),
+            Error::UnsupportedPixelFormat(p) => write!(f, "unsupported pixel format: {}", p),
+            Error::UnsupportedColorCount(c) => write!(f, "unsupported color count: {}", c),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported version: {}", v),
+            Error::BadSoundHeader => write!(f, "bad sound header"),
+            Error::UnsupportedSoundFormat(t) => write!(f, "unsupported sound format: {}", t),
+            Error::UnsupportedSampleBits(b) => write!(f, "unsupported sample bits: {}", b),
+            Error::ImageTooLarge { width, height } => write!(f, "image too large: {}x{}", width, height),
+            Error::AtlasFull => write!(f, "atlas full"),
+            Error::Encode(e) => write!(f, "encode failed: {}", e),
+            Error::Decode(e) => write!(f, "decode failed: {}", e),
+            Error::UnknownFile(k) => write!(f, "unknown file key: {}", k),
+            Error::EmptyImage => write!(f, "empty image"),
+            Error::BadImage(e) => write!(f, "bad image: {}", e),
+            Error::BadPack(e) => write!(f, "bad pack: {}", e),
+            Error::ChecksumMismatch(name) => write!(f, "checksum mismatch: {}", name),
+            Error::BadManifest(e) => write!(f, "bad manifest: {}", e),
+            Error::BadKey(e) => write!(f, "bad key: {}", e),
+            Error::InvalidOptions(e) => write!(f, "invalid options: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(Arc::new(e))
+    }
+}
+
+impl From<flate2::DecompressError> for Error {
+    fn from(e: flate2::DecompressError) -> Self {
+        Error::Decompress(e.to_string())
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use memmap2::Mmap;
+use tracing::debug;
+use crate::error::Result;
+
+/// 默认同时映射的文件数量
+pub const DEFAULT_HANDLE_CAPACITY: usize = 64;
+
+/// 已映射的图片库文件, 按最近使用淘汰, 超出数量时解除最久未使用的映射
+/// 映射期间文件被其他程序修改时读取结果未定义, 资源目录应视为只读
+pub struct LibraryHandles {
+    capacity: usize,
+    inner: Mutex<Handles>,
+}
+
+struct Handles {
+    tick: u64,
+    maps: HashMap<PathBuf, (Arc<Mmap>, u64)>,
+}
+
+impl LibraryHandles {
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(Handles { tick: 0, maps: HashMap::with_capacity(capacity) }),
+        }
+    }
+
+    /// 返回文件的映射, 未映射时打开文件, 返回的映射在被淘汰后仍然有效
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Result<Arc<Mmap>> {
+        let path = path.as_ref();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.tick += 1;
+        let tick = inner.tick;
+        if let Some((map, used)) = inner.maps.get_mut(path) {
+            *used = tick;
+            return Ok(map.clone());
+        }
+
+        let file = File::open(path)?;
+        // Safety: 资源文件在运行期间不会被修改
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        if inner.maps.len() >= self.capacity {
+            let oldest = inner.maps.iter().min_by_key(|(_, (_, used))| *used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                debug!("unmap: {:?}", oldest);
+                inner.maps.remove(&oldest);
+            }
+        }
+        inner.maps.insert(path.to_path_buf(), (map.clone(), tick));
+        Ok(map)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 解除所有映射, 用于替换资源文件之前
+    pub fn clear(&self) {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).maps.clear();
+    }
+}
+
+impl Default for LibraryHandles {
+    fn default() -> Self {
+        Self::new(DEFAULT_HANDLE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let [a, b, c] = ["a.wzl", "b.wzl", "c.wzl"].map(|x| dir.path().join(x));
+        for (i, path) in [&a, &b, &c].into_iter().enumerate() {
+            std::fs::write(path, [i as u8; 4]).unwrap();
+        }
+        let handles = LibraryHandles::new(2);
+        let map_a = handles.get(&a).unwrap();
+        let map_b = handles.get(&b).unwrap();
+        // 再次使用a, 之后淘汰的是b
+        assert!(Arc::ptr_eq(&handles.get(&a).unwrap(), &map_a));
+        let map_c = handles.get(&c).unwrap();
+        assert_eq!(handles.len(), 2);
+        assert_eq!(Arc::strong_count(&map_b), 1);
+        assert_eq!(Arc::strong_count(&map_a), 2);
+        // 淘汰后的映射仍然有效
+        assert_eq!(&map_b[..], &[1; 4]);
+
+        // 被淘汰的文件重新映射, 淘汰c
+        assert!(Arc::ptr_eq(&handles.get(&a).unwrap(), &map_a));
+        let map_b2 = handles.get(&b).unwrap();
+        assert!(!Arc::ptr_eq(&map_b, &map_b2));
+        assert_eq!(&map_b2[..], &[1; 4]);
+        assert_eq!(Arc::strong_count(&map_c), 1);
+        assert!(Arc::ptr_eq(&handles.get(&a).unwrap(), &map_a));
+
+        assert!(handles.get(dir.path().join("d.wzl")).is_err());
+        assert_eq!(handles.len(), 2);
+        handles.clear();
+        assert!(handles.is_empty());
+        assert_eq!(Arc::strong_count(&map_a), 1);
+    }
+}
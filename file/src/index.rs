@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Seek};
+use std::path::Path;
+use bytes::{Buf, BufMut, BytesMut};
+use crate::library::read_buffer;
+use crate::error::{Error, Result};
+use crate::pixel::PixelFormat;
+use crate::writer::raw_body_length;
+
+const WZL_SUFFIX: &str = "wzl";
+const WZX_SUFFIX: &str = "wzx";
+const IDX_SUFFIX: &str = "idx";
+
+const FILE_HEADER_SIZE: u64 = 48;
+const IMAGE_HEADER_SIZE: u64 = 16;
+/// 未压缩图片与下一个wzx位置的间隔不超过此值时, 直接以间隔作为数据长度
+const MAX_INDEX_GAP: u64 = 432;
+
+/// 扫描wzl和核对wzx时发现的问题, 位置均为wzl中的字节偏移
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexIssue {
+    /// wzl文件头, wzx文件头, wzx条目数不一致, 或扫描到的非空图片数与wzx中不同的非0位置数不一致
+    CountMismatch { wzl: u32, wzx: u32, wzx_entries: u32, scanned: u32 },
+    /// 扫描到的图片没有被wzx引用
+    Orphaned { offset: u32 },
+    /// wzx条目不在任何图片的起始位置
+    Misaligned { index: u32, offset: u32 },
+    /// wzx条目或图片数据超出文件末尾, 扫描时发现的index为扫描序号
+    PastEof { index: u32, offset: u32, end: u64 },
+    /// 两个wzx条目的数据范围重叠
+    Overlap { index: u32, offset: u32, end: u64, next: u32 },
+    /// 无法识别的图片头, resume为根据wzx跳过后继续扫描的位置
+    Unreadable { offset: u32, resume: Option<u32> },
+}
+
+/// 重建结果, index为按wzl中的顺序排列的图片位置, 最后一项为文件长度, 可直接写入idx
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    pub file_size: u64,
+    pub header_count: u32,
+    pub index: Vec<u32>,
+    pub issues: Vec<IndexIssue>,
+}
+
+impl IndexReport {
+
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// 扫描到的图片头数量, 包括空图片占位
+    pub fn image_count(&self) -> usize {
+        self.index.len().saturating_sub(1)
+    }
+
+    /// 写入path对应的idx文件
+    pub fn write_idx(&self, path: &str) -> Result<()> {
+        let mut idx = BytesMut::with_capacity(self.index.len() * 4);
+        for i in &self.index {
+            idx.put_u32_le(*i);
+        }
+        fs::write(Path::new(path).with_extension(IDX_SUFFIX), &idx)?;
+        Ok(())
+    }
+}
+
+/// 顺序扫描path对应的wzl重建索引, 存在wzx时同时核对wzx
+pub fn rebuild_index(path: &str) -> Result<IndexReport> {
+    let path = Path::new(path);
+    let wzx = match fs::read(path.with_extension(WZX_SUFFIX)) {
+        Ok(x) => Some(parse_wzx(&x)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let file = File::open(path.with_extension(WZL_SUFFIX))?;
+    let file_size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut header = [0u8; FILE_HEADER_SIZE as usize];
+    read_buffer(&mut reader, &mut header[..])?;
+    let header_count = (&header[44..]).get_u32_le();
+
+    let mut report = IndexReport { file_size, header_count, ..Default::default() };
+    // wzx中的非0位置, 用于无法识别时跳到下一张图片
+    let mut offsets: Vec<u32> = wzx.as_ref().map(|(_, x)| x.iter().copied().filter(|x| *x > 0).collect()).unwrap_or_default();
+    offsets.sort_unstable();
+    offsets.dedup();
+
+    let mut images = Vec::new();
+    let mut ends = HashMap::new();
+    let mut pos = FILE_HEADER_SIZE;
+    while pos + IMAGE_HEADER_SIZE <= file_size {
+        let head = read_head(&mut reader, pos)?;
+        let lengths = if is_valid_head(&head) { body_lengths(&head, pos, &offsets) } else { Vec::new() };
+        match choose_length(&mut reader, pos, file_size, &lengths)? {
+            Some(length) => {
+                let end = pos + IMAGE_HEADER_SIZE + length;
+                if end > file_size {
+                    report.issues.push(IndexIssue::PastEof { index: report.index.len() as u32, offset: pos as u32, end });
+                    break;
+                }
+                report.index.push(pos as u32);
+                if length > 0 || head[..].iter().any(|x| *x != 0) {
+                    images.push(pos as u32);
+                }
+                ends.insert(pos as u32, end);
+                pos = end;
+            }
+            None => {
+                let resume = offsets.iter().copied().find(|x| *x as u64 > pos);
+                report.issues.push(IndexIssue::Unreadable { offset: pos as u32, resume });
+                match resume {
+                    Some(x) => pos = x as u64,
+                    None => break,
+                }
+            }
+        }
+    }
+    report.index.push(file_size as u32);
+
+    if let Some((wzx_count, entries)) = wzx {
+        check_wzx(&mut report, &mut reader, wzx_count, &entries, &images, &ends)?;
+    } else if images.len() > header_count as usize {
+        // 没有wzx时无法知道空图片的数量, 只检查图片是否多于文件头中的数量
+        report.issues.insert(0, IndexIssue::CountMismatch {
+            wzl: header_count, wzx: 0, wzx_entries: 0, scanned: images.len() as u32,
+        });
+    }
+    Ok(report)
+}
+
+/// ends为扫描时确定的图片结束位置, 其他位置按图片头计算
+fn check_wzx(report: &mut IndexReport, reader: &mut BufReader<File>, wzx_count: u32, entries: &[u32], images: &[u32], ends: &HashMap<u32, u64>) -> Result<()> {
+    let scanned = images.len() as u32;
+    let distinct = entries.iter().filter(|x| **x > 0).collect::<HashSet<_>>().len() as u32;
+    if report.header_count != wzx_count || wzx_count as usize != entries.len() || distinct != scanned {
+        report.issues.insert(0, IndexIssue::CountMismatch {
+            wzl: report.header_count, wzx: wzx_count, wzx_entries: entries.len() as u32, scanned,
+        });
+    }
+
+    let starts: HashSet<u32> = report.index[..report.index.len() - 1].iter().copied().collect();
+    let mut ranges = Vec::with_capacity(entries.len());
+    for (index, offset) in entries.iter().enumerate() {
+        let (index, offset) = (index as u32, *offset);
+        if offset == 0 { continue }
+        if offset as u64 + IMAGE_HEADER_SIZE > report.file_size {
+            report.issues.push(IndexIssue::PastEof { index, offset, end: offset as u64 + IMAGE_HEADER_SIZE });
+            continue;
+        }
+        if !starts.contains(&offset) {
+            report.issues.push(IndexIssue::Misaligned { index, offset });
+        }
+        let end = match ends.get(&offset) {
+            Some(end) => *end,
+            None => {
+                let head = read_head(reader, offset as u64)?;
+                let Ok(length) = raw_body_length(&head) else { continue };
+                offset as u64 + IMAGE_HEADER_SIZE + length as u64
+            }
+        };
+        if end > report.file_size {
+            report.issues.push(IndexIssue::PastEof { index, offset, end });
+        }
+        ranges.push((offset, end, index));
+    }
+
+    // 相同位置的条目共用一张图片, 不视为重叠
+    ranges.sort_unstable();
+    ranges.dedup_by_key(|x| x.0);
+    for pair in ranges.windows(2) {
+        let ((offset, end, index), (next, _, _)) = (pair[0], pair[1]);
+        if end > next as u64 {
+            report.issues.push(IndexIssue::Overlap { index, offset, end, next });
+        }
+    }
+
+    let referenced: HashSet<u32> = entries.iter().copied().collect();
+    for offset in images {
+        if !referenced.contains(offset) {
+            report.issues.push(IndexIssue::Orphaned { offset: *offset });
+        }
+    }
+    Ok(())
+}
+
+/// wzx文件头中的数量和全部条目
+fn parse_wzx(bytes: &[u8]) -> Result<(u32, Vec<u32>)> {
+    if bytes.len() < FILE_HEADER_SIZE as usize {
+        return Err(Error::TruncatedHeader { expected: FILE_HEADER_SIZE as usize, actual: bytes.len() });
+    }
+    let count = (&bytes[44..48]).get_u32_le();
+    let entries = bytes[48..].chunks_exact(4).map(|mut x| x.get_u32_le()).collect();
+    Ok((count, entries))
+}
+
+/// 使用相对位置跳转, 顺序扫描时可以保留BufReader中的缓存
+fn read_head(reader: &mut BufReader<File>, pos: u64) -> Result<[u8; IMAGE_HEADER_SIZE as usize]> {
+    let current = reader.stream_position()?;
+    reader.seek_relative(pos as i64 - current as i64)?;
+    let mut head = [0u8; IMAGE_HEADER_SIZE as usize];
+    read_buffer(reader, &mut head[..])?;
+    Ok(head)
+}
+
+/// 图片数据可能的长度, 按顺序尝试
+/// 部分客户端文件中未压缩图片的数据长度与图片头不符:
+/// 1x1和4x1的图片RGB565为4字节(或8字节), 其他格式为16字节(或4字节)
+/// 其他未压缩图片与下一个wzx位置的间隔不超过432字节时使用间隔, 否则按16位像素, 宽度2像素对齐计算
+fn body_lengths(head: &[u8], pos: u64, offsets: &[u32]) -> Vec<u64> {
+    let Ok(length) = raw_body_length(head) else { return Vec::new() };
+    let length = length as u64;
+    let mut buf = &head[4..];
+    let (width, height) = (buf.get_u16_le() as u64, buf.get_u16_le() as u64);
+    let compressed = (&head[12..16]).get_u32_le() > 0;
+    if compressed || width == 0 || height == 0 {
+        return vec![length];
+    }
+    let mut lengths = Vec::with_capacity(3);
+    if (width == 1 || width == 4) && height == 1 {
+        if head[0] == PixelFormat::Rgb565.value() {
+            lengths.extend([4, 8]);
+        } else {
+            lengths.extend([16, 4]);
+        }
+    } else {
+        let next = offsets.iter().copied().find(|x| *x as u64 >= pos + IMAGE_HEADER_SIZE);
+        match next.map(|x| x as u64 - pos - IMAGE_HEADER_SIZE) {
+            Some(gap) if gap <= MAX_INDEX_GAP => lengths.push(gap),
+            _ => lengths.push(width.div_ceil(2) * 2 * height * 2),
+        }
+    }
+    if !lengths.contains(&length) {
+        lengths.push(length);
+    }
+    lengths
+}
+
+/// 选择之后为文件末尾或有效图片头的长度, 都不符合时使用第一个, 由下一次扫描报告问题
+fn choose_length(reader: &mut BufReader<File>, pos: u64, file_size: u64, lengths: &[u64]) -> Result<Option<u64>> {
+    if lengths.len() > 1 {
+        for length in lengths {
+            let end = pos + IMAGE_HEADER_SIZE + length;
+            if end == file_size || (end + IMAGE_HEADER_SIZE <= file_size && is_valid_head(&read_head(reader, end)?)) {
+                return Ok(Some(*length));
+            }
+        }
+    }
+    Ok(lengths.first().copied())
+}
+
+/// 已知的像素格式, 压缩标记1/5/9, 或者全部为0的空图片
+fn is_valid_head(head: &[u8]) -> bool {
+    let width = (&head[4..6]).get_u16_le();
+    (PixelFormat::from_value(head[0]).is_ok() && matches!(head[1], 1 | 5 | 9) && width < 20000)
+        || head.iter().all(|x| *x == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head(buf: &mut BytesMut, pixel: u8, width: u16, height: u16, length: u32) {
+        buf.put_slice(&[pixel, 1, 0, 9]);
+        buf.put_u16_le(width);
+        buf.put_u16_le(height);
+        buf.put_u32_le(0);
+        buf.put_u32_le(length);
+    }
+
+    fn file_header(buf: &mut BytesMut, count: u32) {
+        buf.put_bytes(0, FILE_HEADER_SIZE as usize - 4);
+        buf.put_u32_le(count);
+    }
+
+    /// 数据长度与图片头不符的未压缩图片仍然按原来的规则识别
+    #[test]
+    fn irregular_uncompressed_images() {
+        let (palette, rgb565) = (PixelFormat::Palette8.value(), PixelFormat::Rgb565.value());
+        let mut wzl = BytesMut::new();
+        file_header(&mut wzl, 7);
+        let mut starts = Vec::new();
+        // (像素格式, 宽, 高, 图片头中的长度, 实际数据长度)
+        for (pixel, width, height, length, body) in [
+            (palette, 2, 2, 10, 10),
+            (palette, 1, 1, 0, 16),
+            (rgb565, 4, 1, 0, 4),
+            (palette, 10, 3, 0, 20),
+            (palette, 300, 2, 0, 1200),
+            (0, 0, 0, 0, 0),
+            (rgb565, 1, 1, 5, 5),
+        ] {
+            starts.push(wzl.len() as u32);
+            if pixel == 0 {
+                wzl.put_bytes(0, IMAGE_HEADER_SIZE as usize);
+            } else {
+                head(&mut wzl, pixel, width, height, length);
+                wzl.put_bytes(0xEE, body);
+            }
+        }
+        let mut wzx = BytesMut::new();
+        file_header(&mut wzx, 7);
+        for (i, start) in starts.iter().enumerate() {
+            wzx.put_u32_le(if i == 5 { 0 } else { *start });
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test");
+        fs::write(path.with_extension(WZL_SUFFIX), &wzl).unwrap();
+        fs::write(path.with_extension(WZX_SUFFIX), &wzx).unwrap();
+        let report = rebuild_index(path.to_str().unwrap()).unwrap();
+        assert_eq!(report.issues, vec![]);
+        starts.push(wzl.len() as u32);
+        assert_eq!(report.index, starts);
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use crate::asset::FileDescType;
+use crate::error::{Error, Result};
+
+const INDEX_BITS: u32 = 32;
+const FILE_BITS: u32 = 12;
+const NUMBER_BITS: u32 = 12;
+const NUMBER_SHIFT: u32 = INDEX_BITS + FILE_BITS;
+pub(crate) const TYPE_SHIFT: u32 = NUMBER_SHIFT + NUMBER_BITS;
+const LEVEL_SHIFT: u32 = 60;
+/// 旧版本格式: 44-51位序号, 52-59位文件类型
+const LEGACY_NUMBER_BITS: u32 = 8;
+const LEGACY_TYPE_SHIFT: u32 = NUMBER_SHIFT + LEGACY_NUMBER_BITS;
+
+pub const MAX_FILE: u16 = (1 << FILE_BITS) - 1;
+pub const MAX_NUMBER: u16 = (1 << NUMBER_BITS) - 1;
+pub const MAX_LEVEL: u8 = 0xF;
+/// 文件名称编号和序号, 即`FileDesc::get_file_key`
+pub(crate) const FILE_KEY_MASK: u32 = (1 << (FILE_BITS + NUMBER_BITS)) - 1;
+/// 去掉文件类型和缓存级别
+pub(crate) const CACHE_KEY_MASK: u64 = (1 << TYPE_SHIFT) - 1;
+
+/// 数据KEY, 按位组成:
+/// 60-63位: 4位缓存级别
+/// 56-59位: 4位文件类型, 见`FileDescType`
+/// 44-55位: 12位序号, 0为不带序号的文件
+/// 32-43位: 12位文件名称编号
+/// 00-31位: 32位数据文件索引
+/// 文本格式为`文件.序号#索引`, 序号为0时省略, 如`3.12#4031`, 带名称的格式见`LibraryManifest::describe`
+///
+/// 旧版本的KEY序号只有8位(44-51位), 文件类型在52-59位, 序号为0且类型为wzl时两种格式的值相同
+/// 其他旧KEY(如保存在配置或缓存中的u64)需要用`FileKey::from_legacy`转换, 不能直接用`from_raw`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct FileKey(u64);
+
+impl FileKey {
+
+    /// file或number超出范围时返回错误
+    pub fn new(file: u16, number: u16, index: u32) -> Result<Self> {
+        if file > MAX_FILE || number > MAX_NUMBER {
+            return Err(Error::BadKey(format!("file {} number {} out of range", file, number)));
+        }
+        Ok(Self((number as u64) << NUMBER_SHIFT | (file as u64) << INDEX_BITS | index as u64))
+    }
+
+    /// 检查文件类型, 未使用的类型值返回错误
+    pub fn from_raw(raw: u64) -> Result<Self> {
+        let key = Self(raw);
+        if FileDescType::from_value(key.type_value()).is_none() {
+            return Err(Error::BadKey(format!("unknown file type {} in {:#x}", key.type_value(), raw)));
+        }
+        Ok(key)
+    }
+
+    /// 转换旧版本格式的KEY, 缓存级别, 文件编号和索引的位置不变
+    pub fn from_legacy(raw: u64) -> Result<Self> {
+        let number = (raw >> NUMBER_SHIFT) as u16 & ((1 << LEGACY_NUMBER_BITS) - 1);
+        let typ = (raw >> LEGACY_TYPE_SHIFT) as u8;
+        let typ = FileDescType::from_value(typ)
+            .ok_or_else(|| Error::BadKey(format!("unknown file type {} in legacy key {:#x}", typ, raw)))?;
+        let key = Self::new((raw >> INDEX_BITS) as u16 & MAX_FILE, number, raw as u32)?.with_type(typ);
+        key.with_level((raw >> LEVEL_SHIFT) as u8)
+    }
+
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    pub fn file(self) -> u16 {
+        (self.0 >> INDEX_BITS) as u16 & MAX_FILE
+    }
+
+    pub fn number(self) -> u16 {
+        (self.0 >> NUMBER_SHIFT) as u16 & MAX_NUMBER
+    }
+
+    pub fn index(self) -> u32 {
+        self.0 as u32
+    }
+
+    pub fn file_type(self) -> FileDescType {
+        FileDescType::from_value(self.type_value()).unwrap_or(FileDescType::WZL)
+    }
+
+    pub fn level(self) -> u8 {
+        (self.0 >> LEVEL_SHIFT) as u8
+    }
+
+    /// 文件名称编号和序号, 用于查找图片库
+    pub fn file_key(self) -> u32 {
+        (self.0 >> INDEX_BITS) as u32 & FILE_KEY_MASK
+    }
+
+    /// 不含文件类型和缓存级别的KEY
+    pub fn cache_key(self) -> u64 {
+        self.0 & CACHE_KEY_MASK
+    }
+
+    pub fn with_index(self, index: u32) -> Self {
+        Self(self.0 & !(u32::MAX as u64) | index as u64)
+    }
+
+    pub fn with_type(self, typ: FileDescType) -> Self {
+        Self(self.0 & !(0xF << TYPE_SHIFT) | typ.get_value())
+    }
+
+    pub fn with_level(self, level: u8) -> Result<Self> {
+        if level > MAX_LEVEL {
+            return Err(Error::BadKey(format!("level {} out of range", level)));
+        }
+        Ok(Self(self.0 & !(0xF << LEVEL_SHIFT) | (level as u64) << LEVEL_SHIFT))
+    }
+
+    fn type_value(self) -> u8 {
+        (self.0 >> TYPE_SHIFT) as u8 & 0xF
+    }
+}
+
+impl Display for FileKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.number() {
+            0 => write!(f, "{}#{}", self.file(), self.index()),
+            n => write!(f, "{}.{}#{}", self.file(), n, self.index()),
+        }
+    }
+}
+
+impl Debug for FileKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FileKey({}, {:?}, level {})", self, self.file_type(), self.level())
+    }
+}
+
+/// 解析`文件.序号#索引`或`文件#索引`, 不包含文件类型和缓存级别
+impl FromStr for FileKey {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let bad = || Error::BadKey(text.to_string());
+        let (file, index) = text.trim().split_once('#').ok_or_else(bad)?;
+        let (file, number) = file.split_once('.').unwrap_or((file, "0"));
+        let parse = |x: &str| x.parse::<u32>().map_err(|_| bad());
+        let (file, number) = (parse(file)?, parse(number)?);
+        if file > MAX_FILE as u32 || number > MAX_NUMBER as u32 {
+            return Err(bad());
+        }
+        Self::new(file as u16, number as u16, parse(index)?)
+    }
+}
+
+impl From<FileKey> for u64 {
+    fn from(key: FileKey) -> Self {
+        key.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::LibraryManifest;
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let key = FileKey::new(3, 12, 4031).unwrap();
+        assert_eq!(key.to_string(), "3.12#4031");
+        assert_eq!("3.12#4031".parse::<FileKey>().unwrap(), key);
+        assert_eq!(" 3#7 ".parse::<FileKey>().unwrap(), FileKey::new(3, 0, 7).unwrap());
+        assert_eq!(FileKey::new(3, 0, 7).unwrap().to_string(), "3#7");
+        let max = FileKey::new(MAX_FILE, MAX_NUMBER, u32::MAX).unwrap();
+        assert_eq!(max.to_string().parse::<FileKey>().unwrap(), max);
+        for text in ["3.12", "a#1", "3.x#1", "3#-1", "4096#1", "3#4294967296"] {
+            assert!(matches!(text.parse::<FileKey>(), Err(Error::BadKey(_))), "{}", text);
+        }
+    }
+
+    #[test]
+    fn number_range() {
+        let key = FileKey::new(1, 4095, 9).unwrap();
+        assert_eq!((key.file(), key.number(), key.index()), (1, 4095, 9));
+        assert_eq!(key.file_type(), FileDescType::WZL);
+        assert!(matches!(FileKey::new(1, 4096, 9), Err(Error::BadKey(_))));
+        assert!(matches!(FileKey::new(4096, 0, 9), Err(Error::BadKey(_))));
+        assert!("1.4095#9".parse::<FileKey>().is_ok());
+        assert!("1.4096#9".parse::<FileKey>().is_err());
+
+        // 最大的序号不会影响文件类型和缓存级别
+        let key = key.with_type(FileDescType::WAV).with_level(MAX_LEVEL).unwrap();
+        assert_eq!((key.number(), key.file_type(), key.level()), (4095, FileDescType::WAV, MAX_LEVEL));
+        assert_eq!(FileKey::from_raw(key.raw()).unwrap(), key);
+        assert_eq!(key.cache_key(), FileKey::new(1, 4095, 9).unwrap().raw());
+        assert!(FileKey::from_raw(5 << TYPE_SHIFT).is_err());
+    }
+
+    #[test]
+    fn manifest_names() {
+        let manifest = LibraryManifest::default();
+        let key = FileKey::new(3, 12, 4031).unwrap();
+        assert_eq!(manifest.describe(key), "objects12#4031");
+        assert_eq!(manifest.parse_key("objects12#4031").unwrap(), key);
+        assert_eq!(manifest.parse_key("Objects#5").unwrap(), FileKey::new(3, 0, 5).unwrap());
+        assert_eq!(manifest.describe(FileKey::new(2, 0, 1).unwrap()), "smTiles#1");
+        assert_eq!(manifest.parse_key("3.12#4031").unwrap(), key);
+        assert_eq!(manifest.describe(FileKey::new(9, 1, 1).unwrap()), "9.1#1");
+        assert_eq!(manifest.parse_key("objects4095#1").unwrap().number(), 4095);
+        for text in ["objects4096#1", "hum#1", "objects#x", "objects"] {
+            assert!(matches!(manifest.parse_key(text), Err(Error::BadKey(_))), "{}", text);
+        }
+    }
+
+    #[test]
+    fn legacy_keys() {
+        // 旧格式: 52位文件类型, 44位8位序号
+        let raw = 0x3u64 << 60 | 1 << 52 | 12 << 44 | 3 << 32 | 4031;
+        let key = FileKey::from_legacy(raw).unwrap();
+        assert_eq!(key.to_string(), "3.12#4031");
+        assert_eq!((key.file_type(), key.level()), (FileDescType::WZX, 3));
+        assert_ne!(key.raw(), raw);
+        assert_eq!(FileKey::from_legacy(3 << 32 | 7).unwrap().raw(), 3 << 32 | 7);
+        assert!(FileKey::from_legacy(9 << 52).is_err());
+    }
+}
@@ -1,4 +1,24 @@
 pub mod map;
 pub mod data;
 pub mod asset;
+pub mod error;
+pub mod writer;
+pub mod pixel;
+pub mod wil;
+pub mod mlib;
+pub mod collision;
+pub mod sound;
+pub mod index;
+pub mod handle;
+pub mod worker;
+pub mod atlas;
+pub mod library;
+pub mod vfs;
+pub mod pack;
+pub mod cache;
+pub mod disk;
+pub mod manifest;
+pub mod key;
+pub mod render;
 
+pub use error::{Error, Result};
@@ -0,0 +1,457 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use bytes::{Buf, Bytes};
+use flate2::{FlushDecompress, Status};
+use tracing::{debug, warn};
+use crate::error::{Error, Result};
+use crate::handle::LibraryHandles;
+use crate::pixel::{PixelFormat, PALETTE_RGBA};
+
+const WZL_SUFFIX: &str = "wzl";
+const WZX_SUFFIX: &str = "wzx";
+const IDX_SUFFIX: &str = "idx";
+
+/// wzl图片头固定16字节
+const IMAGE_HEADER_SIZE: usize = 16;
+/// wzx文件头48字节
+const WZX_HEADER_SIZE: usize = 48;
+
+/// 图片库, 按序号读取图片头和解码后的图片
+/// wzl, wil, Lib和内存中的图片都实现此接口, ImageAsset只通过它读取图片
+pub trait ImageLibrary: Send + Sync {
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 只读取图片头, 不解码像素
+    fn header(&self, index: u32) -> Result<ImageHeader>;
+
+    /// 空图片返回`Error::EmptyImage`
+    fn load_image(&self, index: u32) -> Result<ImageData>;
+
+    /// 批量读取, 结果与indices顺序一致
+    fn load_images(&self, indices: &[u32]) -> Vec<Result<ImageData>> {
+        indices.iter().map(|x| self.load_image(*x)).collect()
+    }
+
+    /// 图片库读取的文件, 用于判断文件是否变化, 内存中的图片库返回空
+    fn sources(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// 图片头信息, format为文件中的像素格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageHeader {
+    pub format: PixelFormat,
+    pub width: u16,
+    pub height: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+}
+
+/// 解码后的图片, bytes为自上而下的RGBA
+#[derive(Clone, Default)]
+pub struct ImageData {
+    pub width: u16,
+    pub height: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub bytes: Bytes,
+}
+
+impl ImageData {
+    pub fn from(src: &[u8]) -> Result<Self> {
+        if src.len() < IMAGE_HEADER_SIZE {
+            return Err(Error::TruncatedHeader { expected: IMAGE_HEADER_SIZE, actual: src.len() });
+        }
+        Self::from_head_data(&src[..IMAGE_HEADER_SIZE], &src[IMAGE_HEADER_SIZE..])
+    }
+
+    pub fn from_head_data(head: &[u8], data: &[u8]) -> Result<Self> {
+        if head.len() < IMAGE_HEADER_SIZE {
+            return Err(Error::TruncatedHeader { expected: IMAGE_HEADER_SIZE, actual: head.len() });
+        }
+        let mut body = head;
+        let pixel = body.get_u8();
+        let _compress = body.get_u8();
+        let _reserve = body.get_u8();
+        let _compress_level = body.get_u8();
+
+        let width = body.get_u16_le();
+        let height = body.get_u16_le();
+        let offset_x = body.get_i16_le();
+        let offset_y = body.get_i16_le();
+        let length = body.get_u32_le();
+
+        if length == 0 {
+            let bytes = if !data.is_empty() {
+                Bytes::from(byte_to_rgba(pixel, width as usize, height as usize, data)?)
+            } else { Bytes::new() };
+            Ok(Self { width, height, offset_x, offset_y, bytes })
+        } else {
+            let x = deflate_image(data, width as u32 * height as u32)?;
+            let data = byte_to_rgba(pixel, width as usize, height as usize, &x[..])?;
+            Ok(Self { width, height, offset_x, offset_y, bytes: Bytes::from(data) })
+        }
+    }
+}
+
+/// wzl图片库使用的索引文件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexKind {
+    /// wzx: 48字节文件头之后为每张图片的位置, 0为空图片
+    Wzx,
+    /// idx: 连续记录每张图片头的位置, 最后一项为wzl文件长度
+    Idx,
+}
+
+/// wzl图片库, 文件通过LibraryHandles映射到内存, 不会每次读取都重新打开
+pub struct WzlLibrary {
+    source: WzlSource,
+    kind: IndexKind,
+    offsets: Vec<u32>,
+}
+
+enum WzlSource {
+    Mapped { path: PathBuf, index: PathBuf, handles: Arc<LibraryHandles> },
+    /// 资源包或内存中的wzl
+    Bytes(Bytes),
+}
+
+impl WzlLibrary {
+
+    /// path不含扩展名, 单独使用时只保持自身文件的映射
+    pub fn open<P: AsRef<Path>>(path: P, kind: IndexKind) -> Result<Self> {
+        Self::open_with_handles(path, kind, Arc::new(LibraryHandles::new(1)))
+    }
+
+    /// 多个图片库共用handles, 同时映射的文件数量由handles限制
+    pub fn open_with_handles<P: AsRef<Path>>(path: P, kind: IndexKind, handles: Arc<LibraryHandles>) -> Result<Self> {
+        let path = path.as_ref();
+        let index = match kind {
+            IndexKind::Wzx => path.with_extension(WZX_SUFFIX),
+            IndexKind::Idx => path.with_extension(IDX_SUFFIX),
+        };
+        Self::open_files(path.with_extension(WZL_SUFFIX), index, kind, handles)
+    }
+
+    /// 分别指定wzl和索引文件, 用于文件名大小写不一致的情况
+    pub fn open_files<P: AsRef<Path>, I: AsRef<Path>>(wzl: P, index: I, kind: IndexKind, handles: Arc<LibraryHandles>) -> Result<Self> {
+        let offsets = parse_index(&fs::read(index.as_ref())?, kind)?;
+        debug!("open: {:?}, {:?}, count: {}", wzl.as_ref(), kind, offsets.len());
+        Ok(Self { source: WzlSource::Mapped { path: wzl.as_ref().to_path_buf(), index: index.as_ref().to_path_buf(), handles }, kind, offsets })
+    }
+
+    /// 使用已读取的wzl和索引内容, 用于资源包中的图片库
+    pub fn from_bytes(wzl: Bytes, index: &[u8], kind: IndexKind) -> Result<Self> {
+        Ok(Self { source: WzlSource::Bytes(wzl), kind, offsets: parse_index(index, kind)? })
+    }
+
+    fn with_data<T>(&self, f: impl FnOnce(&[u8]) -> Result<T>) -> Result<T> {
+        match &self.source {
+            WzlSource::Mapped { path, handles, .. } => f(&handles.get(path)?[..]),
+            WzlSource::Bytes(bytes) => f(bytes),
+        }
+    }
+
+    /// 图片头的位置和长度, idx按相邻两项计算长度, wzx只知道图片头长度
+    fn locate(&self, index: u32) -> Result<(u32, u32)> {
+        let len = self.len();
+        if index as usize >= len {
+            return Err(Error::BadIndex { index, len });
+        }
+        let seek = self.offsets[index as usize];
+        let length = match self.kind {
+            IndexKind::Wzx => IMAGE_HEADER_SIZE as u32,
+            IndexKind::Idx => self.offsets[index as usize + 1].checked_sub(seek).ok_or(Error::BadIndex { index, len })?,
+        };
+        if seek == 0 { return Err(Error::EmptyImage) }
+        Ok((seek, length))
+    }
+}
+
+impl ImageLibrary for WzlLibrary {
+
+    fn len(&self) -> usize {
+        match self.kind {
+            IndexKind::Wzx => self.offsets.len(),
+            IndexKind::Idx => self.offsets.len().saturating_sub(1),
+        }
+    }
+
+    fn header(&self, index: u32) -> Result<ImageHeader> {
+        let (seek, _) = self.locate(index)?;
+        self.with_data(|data| {
+            let mut head = slice(data, seek as usize, IMAGE_HEADER_SIZE)?;
+            let format = PixelFormat::from_value(head.get_u8())?;
+            head.advance(3);
+            Ok(ImageHeader {
+                format,
+                width: head.get_u16_le(),
+                height: head.get_u16_le(),
+                offset_x: head.get_i16_le(),
+                offset_y: head.get_i16_le(),
+            })
+        })
+    }
+
+    fn load_image(&self, index: u32) -> Result<ImageData> {
+        let (seek, length) = self.locate(index)?;
+        self.with_data(|data| decode_image(data, seek, length))
+    }
+
+    /// 只取一次文件映射, 按图片在wzl中的位置顺序读取
+    fn load_images(&self, indices: &[u32]) -> Vec<Result<ImageData>> {
+        let mut order: Vec<(usize, Result<(u32, u32)>)> = indices.iter().map(|x| self.locate(*x)).enumerate().collect();
+        order.sort_by_key(|(_, x)| x.as_ref().map(|x| x.0).unwrap_or(0));
+        let values = self.with_data(|data| {
+            Ok(order.into_iter().map(|(i, x)| (i, x.and_then(|(seek, length)| decode_image(data, seek, length)))).collect::<Vec<_>>())
+        });
+        match values {
+            Ok(values) => {
+                let mut result: Vec<Option<Result<ImageData>>> = indices.iter().map(|_| None).collect();
+                for (i, value) in values {
+                    result[i] = Some(value);
+                }
+                result.into_iter().map(|x| x.unwrap_or(Err(Error::EmptyImage))).collect()
+            }
+            Err(e) => indices.iter().map(|_| Err(e.clone())).collect(),
+        }
+    }
+
+    fn sources(&self) -> Vec<PathBuf> {
+        match &self.source {
+            WzlSource::Mapped { path, index, .. } => vec![path.clone(), index.clone()],
+            WzlSource::Bytes(_) => Vec::new(),
+        }
+    }
+}
+
+fn decode_image(data: &[u8], seek: u32, length: u32) -> Result<ImageData> {
+    let image = read_image_data(data, seek, length)?;
+    if image.bytes.is_empty() {
+        return Err(Error::EmptyImage);
+    }
+    Ok(image)
+}
+
+/// 内存中的图片库, 用于生成的图片或测试, None为空图片
+#[derive(Default)]
+pub struct MemoryLibrary {
+    images: Vec<Option<ImageData>>,
+}
+
+impl MemoryLibrary {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加图片, 返回图片序号
+    pub fn push(&mut self, image: Option<ImageData>) -> u32 {
+        self.images.push(image);
+        self.images.len() as u32 - 1
+    }
+
+    fn get(&self, index: u32) -> Result<&ImageData> {
+        let image = self.images.get(index as usize).ok_or(Error::BadIndex { index, len: self.images.len() })?;
+        image.as_ref().filter(|x| !x.bytes.is_empty()).ok_or(Error::EmptyImage)
+    }
+}
+
+impl From<Vec<Option<ImageData>>> for MemoryLibrary {
+    fn from(images: Vec<Option<ImageData>>) -> Self {
+        Self { images }
+    }
+}
+
+impl ImageLibrary for MemoryLibrary {
+
+    fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// 内存中的图片已经是RGBA, 格式固定为Argb32
+    fn header(&self, index: u32) -> Result<ImageHeader> {
+        let image = self.get(index)?;
+        Ok(ImageHeader { format: PixelFormat::Argb32, width: image.width, height: image.height, offset_x: image.offset_x, offset_y: image.offset_y })
+    }
+
+    fn load_image(&self, index: u32) -> Result<ImageData> {
+        self.get(index).cloned()
+    }
+}
+
+fn parse_index(bytes: &[u8], kind: IndexKind) -> Result<Vec<u32>> {
+    match kind {
+        IndexKind::Wzx => {
+            if bytes.len() < WZX_HEADER_SIZE {
+                return Err(Error::TruncatedHeader { expected: WZX_HEADER_SIZE, actual: bytes.len() });
+            }
+            Ok(read_offsets(&bytes[WZX_HEADER_SIZE..]))
+        }
+        IndexKind::Idx => Ok(read_offsets(bytes)),
+    }
+}
+
+fn read_offsets(bytes: &[u8]) -> Vec<u32> {
+    bytes.chunks_exact(4).map(|mut x| x.get_u32_le()).collect()
+}
+
+/// 直接在文件内容上截取图片头和数据, 不复制
+/// length不超过16时只知道图片头的位置, 按图片头中的数据长度读取
+pub(crate) fn read_image_data(file: &[u8], seek: u32, length: u32) -> Result<ImageData> {
+    let length = length.max(IMAGE_HEADER_SIZE as u32) as usize;
+    let data = slice(file, seek as usize, length)?;
+    if length <= IMAGE_HEADER_SIZE {
+        let mut x = &data[12..];
+        let x = x.get_u32_le();
+        if x > 0 {
+            let body = slice(file, (seek as usize).saturating_add(IMAGE_HEADER_SIZE), x as usize)?;
+            return ImageData::from_head_data(data, body)
+        }
+    }
+    ImageData::from(data)
+}
+
+fn slice(file: &[u8], start: usize, length: usize) -> Result<&[u8]> {
+    let actual = file.len().saturating_sub(start).min(length);
+    if actual < length {
+        return Err(Error::TruncatedData { expected: length, actual });
+    }
+    Ok(&file[start..start + length])
+}
+
+/// wil和Lib图片库的数据, 文件在每次读取时打开, 资源包中的文件已读入内存
+pub(crate) enum LibrarySource {
+    File(PathBuf),
+    Bytes(Bytes),
+}
+
+impl LibrarySource {
+    pub(crate) fn open(&self) -> Result<SourceReader> {
+        match self {
+            LibrarySource::File(path) => Ok(SourceReader::File(BufReader::new(File::open(path)?))),
+            LibrarySource::Bytes(bytes) => Ok(SourceReader::Bytes(Cursor::new(bytes.clone()))),
+        }
+    }
+
+    pub(crate) fn path(&self) -> Option<&Path> {
+        match self {
+            LibrarySource::File(path) => Some(path),
+            LibrarySource::Bytes(_) => None,
+        }
+    }
+}
+
+pub(crate) enum SourceReader {
+    File(BufReader<File>),
+    Bytes(Cursor<Bytes>),
+}
+
+impl SourceReader {
+    fn len(&self) -> Result<u64> {
+        match self {
+            SourceReader::File(x) => Ok(x.get_ref().metadata()?.len()),
+            SourceReader::Bytes(x) => Ok(x.get_ref().len() as u64),
+        }
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SourceReader::File(x) => x.read(buf),
+            SourceReader::Bytes(x) => x.read(buf),
+        }
+    }
+}
+
+impl Seek for SourceReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            SourceReader::File(x) => x.seek(pos),
+            SourceReader::Bytes(x) => x.seek(pos),
+        }
+    }
+}
+
+/// 按文件剩余长度检查图片头中的数据长度, 避免按损坏的图片头分配内存
+pub(crate) fn check_remaining(reader: &mut SourceReader, length: usize) -> Result<()> {
+    let total = reader.len()?;
+    let actual = total.saturating_sub(reader.stream_position()?);
+    if actual < length as u64 {
+        return Err(Error::TruncatedData { expected: length, actual: actual as usize });
+    }
+    Ok(())
+}
+
+pub(crate) fn read_buffer<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<()> {
+    let length = buffer.len();
+    let mut len = 0;
+    while len < length {
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => return Err(Error::TruncatedData { expected: length, actual: len }),
+            Ok(n) => len += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// zlib最大压缩比约为1032:1, 解压结果不会超过此长度
+const MAX_INFLATE_RATIO: usize = 1032;
+
+/// 按图片头的宽高分配解压缓冲区, 不超过输入长度可能解压出的最大长度
+fn deflate_image(input: &[u8], size: u32) -> Result<Vec<u8>> {
+    let capacity = size.checked_mul(4).ok_or_else(|| Error::BadImage(format!("{} pixels", size)))?;
+    let capacity = (capacity as usize).min(input.len().saturating_mul(MAX_INFLATE_RATIO).saturating_add(1024));
+    let mut rs: Vec<u8> = Vec::with_capacity(capacity);
+    let status = flate2::Decompress::new(true).decompress_vec(input, &mut rs, FlushDecompress::Finish)?;
+    if status != Status::StreamEnd {
+        warn!("input: {}, output: {}, status: {:?}, size: {}", input.len(), rs.len(), status, size);
+    }
+    Ok(rs)
+}
+
+fn byte_to_rgba(pixel: u8, width: usize, height: usize, bytes: &[u8]) -> Result<Vec<u8>> {
+    PixelFormat::from_value(pixel)?.decode(width, height, bytes, &PALETTE_RGBA)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BufMut;
+    use super::*;
+
+    fn head(width: u16, height: u16, length: u32) -> Vec<u8> {
+        let mut head = Vec::new();
+        head.put_slice(&[5, 1, 0, 9]);
+        head.put_u16_le(width);
+        head.put_u16_le(height);
+        head.put_i16_le(0);
+        head.put_i16_le(0);
+        head.put_u32_le(length);
+        head
+    }
+
+    #[test]
+    fn corrupt_size_is_error() {
+        let result = ImageData::from_head_data(&head(u16::MAX, u16::MAX, 4), &[0x78, 0x9c, 0, 0]);
+        assert!(matches!(result, Err(Error::BadImage(_))));
+    }
+
+    #[test]
+    fn truncated_body_is_error() {
+        let mut file = vec![0u8; 8];
+        file.extend(head(4, 4, 1000));
+        assert!(matches!(read_image_data(&file, 8, 16), Err(Error::TruncatedData { .. })));
+        assert!(matches!(read_image_data(&file, u32::MAX, 16), Err(Error::TruncatedData { .. })));
+    }
+}
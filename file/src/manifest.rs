@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::cache::ImageClass;
+use crate::error::{Error, Result};
+use crate::key::{FileKey, MAX_FILE, MAX_NUMBER};
+use crate::vfs::Vfs;
+
+const IMAGE_DIR: &str = "data";
+/// 图片库存在时至少有其中一种数据文件
+const LIBRARY_SUFFIXES: [&str; 3] = ["wzl", "wil", "Lib"];
+
+/// 图片库清单, 指定文件编号, 名称, 分类, 带序号的文件和是否预加载
+/// 支持JSON, TOML和RON格式, 示例见examples/libraries.json, libraries.toml和libraries.ron
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryManifest {
+    pub libraries: Vec<LibraryEntry>,
+}
+
+/// 清单中的一个图片库, name不含扩展名
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub id: u16,
+    pub name: String,
+    pub category: LibraryCategory,
+    /// 带序号的文件, 如objects2到objects51, 序号0为不带序号的name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numbered: Option<NumberRange>,
+    /// 注册时立即打开索引
+    #[serde(default)]
+    pub preload: bool,
+    /// 文件不存在时不报告
+    #[serde(default)]
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LibraryCategory {
+    Tile,
+    Actor,
+    Hair,
+    Weapon,
+    Effect,
+    Ui,
+    Other,
+}
+
+impl LibraryCategory {
+    /// 对应的缓存分类, 人物, 头发和武器共用角色预算
+    pub fn cache_class(self) -> ImageClass {
+        match self {
+            LibraryCategory::Tile => ImageClass::Tile,
+            LibraryCategory::Actor | LibraryCategory::Hair | LibraryCategory::Weapon => ImageClass::Actor,
+            LibraryCategory::Effect => ImageClass::Effect,
+            LibraryCategory::Ui | LibraryCategory::Other => ImageClass::Other,
+        }
+    }
+}
+
+/// 序号范围, 包含start和end
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NumberRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl LibraryEntry {
+
+    /// 不带序号的文件和所有带序号的文件, 返回(序号, 文件名)
+    pub fn files(&self) -> Vec<(u16, String)> {
+        let mut files = vec![(0, self.name.clone())];
+        if let Some(range) = self.numbered {
+            files.extend((range.start.max(1)..=range.end).map(|n| (n, format!("{}{}", self.name, n))));
+        }
+        files
+    }
+
+    /// 带序号文件的文件KEY, 与`FileDesc::get_file_key`一致
+    pub fn file_key(&self, number: u16) -> u32 {
+        FileKey::new(self.id, number, 0).map(|x| x.file_key()).unwrap_or(0)
+    }
+}
+
+impl LibraryManifest {
+
+    /// 读取JSON格式的清单并检查编号和名称
+    pub fn from_json(text: &str) -> Result<Self> {
+        let manifest: Self = serde_json::from_str(text).map_err(|e| Error::BadManifest(e.to_string()))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// 读取TOML格式的清单, 每个图片库为一个[[libraries]]表
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(text).map_err(|e| Error::BadManifest(e.to_string()))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// 读取RON格式的清单, numbered需要写为`Some((start: 2, end: 51))`
+    pub fn from_ron(text: &str) -> Result<Self> {
+        let manifest: Self = ron::from_str(text).map_err(|e| Error::BadManifest(e.to_string()))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// 按扩展名(json, toml, ron, 不区分大小写)选择格式, 其他扩展名返回`Error::BadManifest`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().map(|x| x.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        let parse = match extension.as_str() {
+            "json" => Self::from_json,
+            "toml" => Self::from_toml,
+            "ron" => Self::from_ron,
+            _ => return Err(Error::BadManifest(format!("unknown manifest format: {}", path.display()))),
+        };
+        parse(&fs::read_to_string(path)?)
+    }
+
+    /// 检查编号和序号范围, 编号和名称不能重复, 名称不区分大小写
+    pub fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        let mut names = HashSet::new();
+        for entry in &self.libraries {
+            if entry.id > MAX_FILE {
+                return Err(Error::BadManifest(format!("{}: id {} out of range", entry.name, entry.id)));
+            }
+            if entry.name.is_empty() {
+                return Err(Error::BadManifest(format!("library {}: empty name", entry.id)));
+            }
+            if let Some(range) = entry.numbered {
+                if range.start > range.end || range.end > MAX_NUMBER {
+                    return Err(Error::BadManifest(format!("{}: bad number range {}..={}", entry.name, range.start, range.end)));
+                }
+            }
+            if !ids.insert(entry.id) {
+                return Err(Error::BadManifest(format!("{}: duplicate id {}", entry.name, entry.id)));
+            }
+            if !names.insert(entry.name.to_ascii_lowercase()) {
+                return Err(Error::BadManifest(format!("duplicate name {}", entry.name)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: u16) -> Option<&LibraryEntry> {
+        self.libraries.iter().find(|x| x.id == id)
+    }
+
+    /// 名称不区分大小写
+    pub fn find(&self, name: &str) -> Option<&LibraryEntry> {
+        self.libraries.iter().find(|x| x.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<u16> {
+        self.find(name).map(|x| x.id)
+    }
+
+    pub fn by_category(&self, category: LibraryCategory) -> impl Iterator<Item = &LibraryEntry> {
+        self.libraries.iter().filter(move |x| x.category == category)
+    }
+
+    /// 使用图片库名称显示KEY, 如`objects12#4031`, 未登记的文件使用数字格式
+    pub fn describe(&self, key: FileKey) -> String {
+        match (self.get(key.file()), key.number()) {
+            (Some(entry), 0) => format!("{}#{}", entry.name, key.index()),
+            (Some(entry), n) => format!("{}{}#{}", entry.name, n, key.index()),
+            (None, _) => key.to_string(),
+        }
+    }
+
+    /// 解析`describe`的结果, 也接受`FileKey`的数字格式, 名称不区分大小写
+    /// 名称以数字结尾时优先匹配完整名称, 否则末尾的数字作为序号
+    pub fn parse_key(&self, text: &str) -> Result<FileKey> {
+        let bad = || Error::BadKey(text.to_string());
+        let (name, index) = text.trim().split_once('#').ok_or_else(bad)?;
+        if name.starts_with(|x: char| x.is_ascii_digit()) {
+            return text.parse();
+        }
+        let index = index.parse::<u32>().map_err(|_| bad())?;
+        if let Some(entry) = self.find(name) {
+            return FileKey::new(entry.id, 0, index);
+        }
+        let base = name.trim_end_matches(|x: char| x.is_ascii_digit());
+        let entry = self.find(base).ok_or_else(bad)?;
+        let number = name[base.len()..].parse::<u16>().map_err(|_| bad())?;
+        FileKey::new(entry.id, number, index)
+    }
+
+    /// 检查图片库文件是否存在, 返回缺少的文件, optional的图片库不检查
+    pub fn check(&self, vfs: &Vfs) -> Vec<String> {
+        let mut missing = Vec::new();
+        for entry in self.libraries.iter().filter(|x| !x.optional) {
+            for (_, name) in entry.files() {
+                let path = Path::new(IMAGE_DIR).join(&name);
+                let exists = if path.extension().is_some_and(|x| x.eq_ignore_ascii_case("Lib")) {
+                    vfs.exists(&path)
+                } else {
+                    LIBRARY_SUFFIXES.iter().any(|x| vfs.exists(path.with_extension(x)))
+                };
+                if !exists {
+                    missing.push(name);
+                }
+            }
+        }
+        missing
+    }
+}
+
+/// 原来固定注册的地图图块
+impl Default for LibraryManifest {
+    fn default() -> Self {
+        let tile = |id: u16, name: &str| LibraryEntry {
+            id,
+            name: name.to_string(),
+            category: LibraryCategory::Tile,
+            numbered: None,
+            preload: true,
+            optional: false,
+        };
+        Self { libraries: vec![tile(1, "tiles"), tile(2, "smTiles"), tile(3, "objects")] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples");
+
+    #[test]
+    fn load_by_extension() {
+        let json = LibraryManifest::load(Path::new(EXAMPLES).join("libraries.json")).unwrap();
+        assert_eq!(json.libraries.len(), 8);
+        assert_eq!(json.get(3).unwrap().numbered, Some(NumberRange { start: 2, end: 51 }));
+        assert!(json.get(8).unwrap().optional);
+        for name in ["libraries.toml", "libraries.ron"] {
+            assert_eq!(LibraryManifest::load(Path::new(EXAMPLES).join(name)).unwrap(), json, "{}", name);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("libraries.TOML");
+        fs::write(&path, "[[libraries]]\nid = 1\nname = \"tiles\"\ncategory = \"tile\"\n").unwrap();
+        assert_eq!(LibraryManifest::load(&path).unwrap().libraries[0].name, "tiles");
+        for name in ["libraries.yaml", "libraries"] {
+            fs::write(dir.path().join(name), "").unwrap();
+            assert!(matches!(LibraryManifest::load(dir.path().join(name)), Err(Error::BadManifest(_))));
+        }
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        assert!(matches!(LibraryManifest::from_toml("[[libraries]]\nid = 1\n"), Err(Error::BadManifest(_))));
+        assert!(matches!(LibraryManifest::from_ron("(libraries: [(id: 1, name: \"a\", category: unknown)])"), Err(Error::BadManifest(_))));
+        let duplicate = "(libraries: [(id: 1, name: \"a\", category: tile), (id: 1, name: \"b\", category: tile)])";
+        assert!(matches!(LibraryManifest::from_ron(duplicate), Err(Error::BadManifest(e)) if e.contains("duplicate id")));
+        let range = "[[libraries]]\nid = 1\nname = \"a\"\ncategory = \"tile\"\nnumbered = { start = 3, end = 2 }\n";
+        assert!(matches!(LibraryManifest::from_toml(range), Err(Error::BadManifest(e)) if e.contains("range")));
+    }
+}
@@ -1,32 +1,215 @@
-
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::fs;
 use std::path::Path;
-use bytes::Buf;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crate::error::{Error, Result};
+
+/// 自动识别地图格式
+pub fn read_map_file(path: &str) -> Result<MapInfo> {
+    read_map(path, None)
+}
+
+/// 按指定格式读取地图
+pub fn read_map_file_as(path: &str, format: MapFormat) -> Result<MapInfo> {
+    read_map(path, Some(format))
+}
 
-pub fn read_map_file(path: &str) -> MapInfo {
+fn read_map(path: &str, format: Option<MapFormat>) -> Result<MapInfo> {
     // println!("read_map_file: {}", path);
     let path = Path::new(path);
-    let name = path.file_name().unwrap().to_str().unwrap().to_string();
-    let file_size = path.metadata().unwrap().len();
-    let mut file = File::open(path).unwrap();
-    let mut header = [0u8; 52];
-    file.read(&mut header).unwrap();
-    let mut header = &header[..];
-    let width = header.get_u16_le() as u32;
-    let height = header.get_u16_le() as u32;
-    let length = ((file_size as u32 - 52) / (width * height)) as usize;
-    let mut body = Vec::with_capacity(file_size as usize -52);
-    file.read_to_end(&mut body).unwrap();
-    // let mut reader = BufReader::new(file);
-    let mut tiles = Vec::with_capacity((width * height) as usize);
-    for i in 0..width * height {
-        let start = i as usize * length;
-        let end = start + length;
-        let tile = Tile::from(&body[start..end]);
-        tiles.push(tile);
-    }
-    MapInfo {width, height, step: length as u32, size: file_size, name, tiles}
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default().to_string();
+    let body = fs::read(path)?;
+    parse_map(name, &body, format)
+}
+
+/// 解析地图数据, format为None时根据文件头和数据长度识别
+pub fn parse_map(name: String, bytes: &[u8], format: Option<MapFormat>) -> Result<MapInfo> {
+    let format = match format {
+        Some(format) => format,
+        None => MapFormat::detect(bytes)?,
+    };
+    let (width, height) = format.read_size(bytes)?;
+    let count = (width * height) as usize;
+    let header_size = format.header_size(width, height);
+    let length = bytes.len().saturating_sub(header_size);
+    if count == 0 || length < count * format.tile_size() {
+        return Err(Error::BadMapSize { width, height, length });
+    }
+    let body = &bytes[header_size..];
+    let tiles = match format {
+        MapFormat::Classic | MapFormat::Shanda => {
+            body.chunks_exact(format.tile_size()).take(count).map(Tile::from).collect()
+        }
+        MapFormat::Shanda2012 => split_tiles(body, count, format.tile_size(), |x| Tile::from(&x).with_extra(x.slice(14..))),
+        MapFormat::Mir3 => decode_mir3(width as usize, height as usize, &bytes[MIR3_HEADER_SIZE..]),
+        MapFormat::Crystal => split_tiles(body, count, format.tile_size(), decode_crystal),
+    };
+    let header = match format {
+        MapFormat::Mir3 => Bytes::copy_from_slice(&bytes[..MIR3_HEADER_SIZE]),
+        _ => Bytes::copy_from_slice(&bytes[..header_size.min(MAP_HEADER_SIZE)]),
+    };
+    Ok(MapInfo {width, height, step: format.tile_size() as u32, size: bytes.len() as u64, name, format, header, tiles})
+}
+
+/// 复制一次数据, 每格的原始数据共用同一块内存
+fn split_tiles(body: &[u8], count: usize, size: usize, f: impl Fn(Bytes) -> Tile) -> Vec<Tile> {
+    let body = Bytes::copy_from_slice(&body[..count * size]);
+    (0..count).map(|i| f(body.slice(i * size..(i + 1) * size))).collect()
+}
+
+const MAP_HEADER_SIZE: usize = 52;
+const MIR3_HEADER_SIZE: usize = 28;
+const CRYSTAL_HEADER_SIZE: usize = 8;
+const CRYSTAL_TILE_SIZE: usize = 26;
+/// Crystal每格中未解析的动画数据
+const CRYSTAL_EXTRA: std::ops::Range<usize> = 18..25;
+
+/// 地图文件格式
+/// Classic:    52字节文件头, 每格12字节
+/// Shanda:     52字节文件头, 每格14字节, 多出地表和中间层的文件序号
+/// Shanda2012: 52字节文件头, 每格36字节, 前14字节同Shanda
+/// Mir3:       28字节文件头, 地表按2x2格每块3字节, 之后每格14字节
+/// Crystal:    8字节文件头(2-3字节为"C#"), 每格26字节
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapFormat {
+    Classic,
+    Shanda,
+    Shanda2012,
+    Mir3,
+    Crystal,
+}
+
+impl MapFormat {
+
+    /// 先匹配文件头标记, 再根据每格长度判断
+    pub fn detect(bytes: &[u8]) -> Result<MapFormat> {
+        if bytes.len() >= CRYSTAL_HEADER_SIZE && bytes[2] == 0x43 && bytes[3] == 0x23 {
+            return Ok(MapFormat::Crystal);
+        }
+        if bytes.len() >= MAP_HEADER_SIZE {
+            let mut header = bytes;
+            let count = header.get_u16_le() as usize * header.get_u16_le() as usize;
+            let step = (bytes.len() - MAP_HEADER_SIZE).checked_div(count).unwrap_or(0);
+            let shanda = (bytes[4] == 0x0F || bytes[4] == 0x03) && bytes[18] == 0x0D && bytes[19] == 0x0A;
+            match step {
+                36.. if shanda => return Ok(MapFormat::Shanda2012),
+                14.. if shanda => return Ok(MapFormat::Shanda),
+                12 => return Ok(MapFormat::Classic),
+                14 => return Ok(MapFormat::Shanda),
+                36 => return Ok(MapFormat::Shanda2012),
+                _ => {}
+            }
+        }
+        if bytes.len() >= MIR3_HEADER_SIZE && bytes[0] == 0 {
+            let (width, height) = MapFormat::Mir3.read_size(bytes)?;
+            let size = MapFormat::Mir3.header_size(width, height) + (width * height) as usize * MapFormat::Mir3.tile_size();
+            if width > 0 && height > 0 && bytes.len() >= size {
+                return Ok(MapFormat::Mir3);
+            }
+        }
+        Err(Error::UnknownMapFormat)
+    }
+
+    /// 每格字节数
+    pub fn tile_size(&self) -> usize {
+        match self {
+            MapFormat::Classic => 12,
+            MapFormat::Shanda => 14,
+            MapFormat::Shanda2012 => 36,
+            MapFormat::Mir3 => 14,
+            MapFormat::Crystal => CRYSTAL_TILE_SIZE,
+        }
+    }
+
+    /// 每格数据开始前的字节数, Mir3包含地表块
+    pub fn header_size(&self, width: u32, height: u32) -> usize {
+        match self {
+            MapFormat::Classic | MapFormat::Shanda | MapFormat::Shanda2012 => MAP_HEADER_SIZE,
+            MapFormat::Mir3 => MIR3_HEADER_SIZE + 3 * (width as usize).div_ceil(2) * (height as usize / 2),
+            MapFormat::Crystal => CRYSTAL_HEADER_SIZE,
+        }
+    }
+
+    fn read_size(&self, bytes: &[u8]) -> Result<(u32, u32)> {
+        let (start, expected) = match self {
+            MapFormat::Classic | MapFormat::Shanda | MapFormat::Shanda2012 => (0, MAP_HEADER_SIZE),
+            MapFormat::Mir3 => (22, MIR3_HEADER_SIZE),
+            MapFormat::Crystal => (4, CRYSTAL_HEADER_SIZE),
+        };
+        if bytes.len() < expected {
+            return Err(Error::TruncatedHeader { expected, actual: bytes.len() });
+        }
+        let mut header = &bytes[start..];
+        Ok((header.get_u16_le() as u32, header.get_u16_le() as u32))
+    }
+}
+
+/// Mir3地表每2x2格共用一块, 文件序号255表示无图片
+/// 标记位0为0时不可行走, 位1为0时不可飞越, 转换为back和objects的最高位
+fn decode_mir3(width: usize, height: usize, bytes: &[u8]) -> Vec<Tile> {
+    let mut tiles = vec![Tile::default(); width * height];
+    let mut buf = bytes;
+    for x in 0..width / 2 {
+        for y in 0..height / 2 {
+            let idx = buf.get_u8();
+            let image = buf.get_u16_le().wrapping_add(1) & 0x7FFF;
+            for i in 0..4 {
+                let tile = &mut tiles[(x * 2 + i % 2) * height + y * 2 + i / 2];
+                if idx != 255 {
+                    tile.back = image;
+                    tile.tile_idx = idx;
+                }
+            }
+        }
+    }
+    let mut buf = &bytes[3 * width.div_ceil(2) * (height / 2)..];
+    for tile in tiles.iter_mut() {
+        let flag = buf.get_u8();
+        let _middle_frame = buf.get_u8();
+        let frame = buf.get_u8();
+        let front_idx = buf.get_u8();
+        let middle_idx = buf.get_u8();
+        let middle = buf.get_u16_le().wrapping_add(1);
+        let front = buf.get_u16_le();
+        buf.advance(3);
+        let light = buf.get_u8();
+        buf.advance(1);
+
+        if middle_idx != 255 {
+            tile.middle = middle & 0x7FFF;
+            tile.middle_idx = middle_idx;
+        }
+        if front_idx != 255 && !(front == 0 && front_idx == 0) {
+            tile.objects = front.wrapping_add(1) & 0x7FFF;
+            tile.file_idx = front_idx;
+            tile.frame = if frame == 255 { 0 } else { frame & 0x8F };
+        }
+        tile.light = light & 0x0F;
+        if flag & 0x01 != 1 { tile.back |= 0x8000 }
+        if flag & 0x02 != 2 { tile.objects |= 0x8000 }
+    }
+    tiles
+}
+
+/// Crystal地表图片为32位, 0x20000000表示不可行走; 文件序号沿用Crystal的图片库编号
+/// 图片库编号为i16, 截断为u8, 原始值保留在extra中
+fn decode_crystal(bytes: Bytes) -> Tile {
+    let mut buf = &bytes[..];
+    let tile_idx = buf.get_i16_le() as u8;
+    let back_image = buf.get_u32_le();
+    let middle_idx = buf.get_i16_le() as u8;
+    let middle = buf.get_u16_le();
+    let file_idx = buf.get_i16_le() as u8;
+    let objects = buf.get_u16_le();
+    let door_idx = buf.get_u8();
+    let door_offset = buf.get_u8();
+    let frame = buf.get_u8();
+    let tick = buf.get_u8();
+    // 中间层动画帧和地表动画
+    buf.advance(CRYSTAL_EXTRA.len());
+    let light = buf.get_u8();
+    let back = (back_image & 0x7FFF) as u16 | if back_image & 0x20000000 != 0 { 0x8000 } else { 0 };
+    let extra = bytes;
+    Tile { back, middle, objects, door_idx, door_offset, frame, tick, file_idx, light, tile_idx, middle_idx, extra }
 }
 
 pub struct MapInfo {
@@ -35,10 +218,198 @@ pub struct MapInfo {
     pub step: u32,
     pub size: u64,
     pub name: String,
+    pub format: MapFormat,
+    /// 读取时的文件头, 包含宽高之外的标记和描述, 写入同类格式时原样保留
+    pub header: Bytes,
     pub tiles: Vec<Tile>
 }
 
+impl MapInfo {
+
+    /// 写入地图文件, Mir3格式的地表按2x2块存储且不含门的信息, 不支持写入
+    /// 宽高超过65535时返回错误; 读取时的文件头和每格未解析的数据在格式相同时原样写回
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: MapFormat) -> Result<()> {
+        let count = self.width as usize * self.height as usize;
+        let bad_size = || Error::BadMapSize { width: self.width, height: self.height, length: self.tiles.len() };
+        if self.tiles.len() != count {
+            return Err(bad_size());
+        }
+        let width = u16::try_from(self.width).map_err(|_| bad_size())?;
+        let height = u16::try_from(self.height).map_err(|_| bad_size())?;
+        let header_size = format.header_size(self.width, self.height);
+        let mut buf = BytesMut::with_capacity(header_size + count * format.tile_size());
+        match format {
+            MapFormat::Classic | MapFormat::Shanda | MapFormat::Shanda2012 => {
+                buf.put_u16_le(width);
+                buf.put_u16_le(height);
+                let classic = matches!(self.format, MapFormat::Classic | MapFormat::Shanda | MapFormat::Shanda2012);
+                match self.header.get(4..MAP_HEADER_SIZE) {
+                    Some(header) if classic => buf.put_slice(header),
+                    _ => buf.put_bytes(0, MAP_HEADER_SIZE - 4),
+                }
+                for tile in &self.tiles {
+                    tile.put(&mut buf, format.tile_size());
+                }
+            }
+            MapFormat::Crystal => {
+                match self.header.get(..2) {
+                    Some(version) if self.format == MapFormat::Crystal => buf.put_slice(version),
+                    _ => buf.put_bytes(0, 2),
+                }
+                buf.put_slice(&[0x43, 0x23]);
+                buf.put_u16_le(width);
+                buf.put_u16_le(height);
+                for tile in &self.tiles {
+                    tile.put_crystal(&mut buf);
+                }
+            }
+            MapFormat::Mir3 => return Err(Error::UnsupportedMapFormat),
+        }
+        fs::write(path, &buf)?;
+        Ok(())
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> Option<&Tile> {
+        if x >= self.width || y >= self.height { return None }
+        self.tiles.get(x as usize * self.height as usize + y as usize)
+    }
+
+    pub fn tile_mut(&mut self, x: u32, y: u32) -> Option<&mut Tile> {
+        if x >= self.width || y >= self.height { return None }
+        self.tiles.get_mut(x as usize * self.height as usize + y as usize)
+    }
+
+    /// 设置某一层的图片和文件序号, 保留back和objects最高位的标记
+    pub fn set_layer(&mut self, x: u32, y: u32, layer: MapLayer, image: u16, file: u8) -> Result<()> {
+        let (index, len) = (x.saturating_mul(self.height).saturating_add(y), self.tiles.len());
+        let tile = self.tile_mut(x, y).ok_or(Error::BadIndex { index, len })?;
+        tile.set_layer(layer, image, file);
+        Ok(())
+    }
+
+    /// 调整地图大小, 原有内容按anchor对齐, 新增的格子为空
+    pub fn resize(&mut self, width: u32, height: u32, anchor: MapAnchor) {
+        let (ax, ay) = anchor.factor();
+        let dx = offset(self.width, width, ax);
+        let dy = offset(self.height, height, ay);
+        let mut tiles = vec![Tile::default(); width as usize * height as usize];
+        for x in 0..width {
+            for y in 0..height {
+                let (ox, oy) = (x as i64 - dx, y as i64 - dy);
+                if ox < 0 || oy < 0 || ox > u32::MAX as i64 || oy > u32::MAX as i64 { continue }
+                if let Some(tile) = self.tile(ox as u32, oy as u32) {
+                    tiles[x as usize * height as usize + y as usize] = tile.clone();
+                }
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.size = (self.format.header_size(width, height) + tiles.len() * self.step as usize) as u64;
+        self.tiles = tiles;
+    }
+
+    /// 复制矩形区域, 区域限制在地图范围内
+    pub fn copy_region(&self, x: u32, y: u32, width: u32, height: u32) -> MapRegion {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        let mut tiles = Vec::with_capacity(width as usize * height as usize);
+        for i in 0..width {
+            for j in 0..height {
+                tiles.push(self.tile(x + i, y + j).cloned().unwrap_or_default());
+            }
+        }
+        MapRegion { width, height, tiles }
+    }
+
+    /// 粘贴区域到(x, y), 超出地图的部分忽略
+    pub fn paste_region(&mut self, region: &MapRegion, x: u32, y: u32) {
+        let width = region.width.min(self.width.saturating_sub(x));
+        let height = region.height.min(self.height.saturating_sub(y));
+        for i in 0..width {
+            for j in 0..height {
+                let Some(source) = region.tiles.get(i as usize * region.height as usize + j as usize) else { continue };
+                if let Some(tile) = self.tile_mut(x + i, y + j) {
+                    *tile = source.clone();
+                }
+            }
+        }
+    }
+}
+
+/// 调整大小时原内容在新地图中的偏移, 居中时向0取偶数以保持地表2x2对齐
+fn offset(old: u32, new: u32, factor: u8) -> i64 {
+    let diff = new as i64 - old as i64;
+    match factor {
+        0 => 0,
+        1 => diff / 2 - diff / 2 % 2,
+        _ => diff,
+    }
+}
+
+/// 从地图中复制的矩形区域, 与地图相同按列存储
 #[derive(Debug, Clone)]
+pub struct MapRegion {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<Tile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapLayer {
+    Back,
+    Middle,
+    Objects,
+}
+
+/// 调整地图大小时原内容的对齐位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl MapAnchor {
+    /// 水平和垂直方向: 0起始, 1居中, 2末尾
+    fn factor(&self) -> (u8, u8) {
+        match self {
+            MapAnchor::TopLeft => (0, 0),
+            MapAnchor::Top => (1, 0),
+            MapAnchor::TopRight => (2, 0),
+            MapAnchor::Left => (0, 1),
+            MapAnchor::Center => (1, 1),
+            MapAnchor::Right => (2, 1),
+            MapAnchor::BottomLeft => (0, 2),
+            MapAnchor::Bottom => (1, 2),
+            MapAnchor::BottomRight => (2, 2),
+        }
+    }
+}
+
+/// 门: door_idx最高位表示该格子属于门, 低7位为门编号
+/// door_offset最高位表示门已打开, 低7位为门图片相对objects的偏移
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Door {
+    pub index: u8,
+    pub offset: u8,
+    pub open: bool,
+}
+
+/// 动画物体: frame最高位表示混合绘制, 低7位为帧数; tick为每帧持续的时间单位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    pub frames: u8,
+    pub tick: u8,
+    pub blend: bool,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Tile {
     pub back: u16,
     pub middle: u16,
@@ -51,9 +422,12 @@ pub struct Tile {
     pub light: u8,
     pub tile_idx: u8,
     pub middle_idx: u8,
+    /// 未解析的原始数据: Shanda2012为第14字节之后的22字节, Crystal为每格26字节的原始数据
+    pub extra: Bytes,
 }
 
 impl Tile {
+    /// 至少需要12字节, 由调用方保证
     pub fn from(bytes: &[u8]) -> Self {
         let len = bytes.len();
         let mut bytes = bytes;
@@ -79,6 +453,348 @@ impl Tile {
         //     }
         // }
 
-        Tile { back, middle, objects, door_idx, door_offset, frame, tick, file_idx, light, tile_idx, middle_idx }
+        Tile { back, middle, objects, door_idx, door_offset, frame, tick, file_idx, light, tile_idx, middle_idx, extra: Bytes::new() }
     }
-}
\ No newline at end of file
+
+    fn with_extra(mut self, extra: Bytes) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    /// back或objects最高位为1时不可行走, 关闭的门也不可行走
+    pub fn can_walk(&self) -> bool {
+        self.can_walk_ignoring_door() && !self.is_door_closed()
+    }
+
+    /// objects最高位为1时不可飞越, 关闭的门也不可飞越
+    pub fn can_fly(&self) -> bool {
+        self.can_fly_ignoring_door() && !self.is_door_closed()
+    }
+
+    /// 只看back和objects的最高位, 门的状态另外判断
+    pub fn can_walk_ignoring_door(&self) -> bool {
+        self.back & 0x8000 == 0 && self.objects & 0x8000 == 0
+    }
+
+    /// 只看objects的最高位, 门的状态另外判断
+    pub fn can_fly_ignoring_door(&self) -> bool {
+        self.objects & 0x8000 == 0
+    }
+
+    /// 属于门且door_offset最高位为0
+    pub fn is_door_closed(&self) -> bool {
+        self.door_idx & 0x80 != 0 && self.door_offset & 0x80 == 0
+    }
+
+    pub fn door(&self) -> Option<Door> {
+        if self.door_idx & 0x80 == 0 { return None }
+        Some(Door { index: self.door_idx & 0x7F, offset: self.door_offset & 0x7F, open: self.door_offset & 0x80 != 0 })
+    }
+
+    /// 设置门的开关状态, 不是门时忽略
+    pub fn set_door_open(&mut self, open: bool) {
+        if self.door_idx & 0x80 == 0 { return }
+        if open { self.door_offset |= 0x80 } else { self.door_offset &= 0x7F }
+    }
+
+    pub fn animation(&self) -> Option<Animation> {
+        let frames = self.frame & 0x7F;
+        if frames == 0 { return None }
+        Some(Animation { frames, tick: self.tick, blend: self.frame & 0x80 != 0 })
+    }
+
+    /// 光源半径, 0为无光源
+    pub fn light_radius(&self) -> u8 {
+        self.light
+    }
+
+    /// 修改图片时保留最高位的标记, image为0表示清除该层
+    pub fn set_layer(&mut self, layer: MapLayer, image: u16, file: u8) {
+        match layer {
+            MapLayer::Back => {
+                self.back = (self.back & 0x8000) | (image & 0x7FFF);
+                self.tile_idx = file;
+            }
+            MapLayer::Middle => {
+                self.middle = image;
+                self.middle_idx = file;
+            }
+            MapLayer::Objects => {
+                self.objects = (self.objects & 0x8000) | (image & 0x7FFF);
+                self.file_idx = file;
+            }
+        }
+    }
+
+    /// 按Classic/Shanda格式写入size字节, 超出14字节的部分使用extra, 长度不符时补0
+    fn put(&self, buf: &mut BytesMut, size: usize) {
+        buf.put_u16_le(self.back);
+        buf.put_u16_le(self.middle);
+        buf.put_u16_le(self.objects);
+        buf.put_u8(self.door_idx);
+        buf.put_u8(self.door_offset);
+        buf.put_u8(self.frame);
+        buf.put_u8(self.tick);
+        buf.put_u8(self.file_idx);
+        buf.put_u8(self.light);
+        if size > 12 {
+            buf.put_u8(self.tile_idx);
+            buf.put_u8(self.middle_idx);
+            if self.extra.len() == size - 14 {
+                buf.put_slice(&self.extra);
+            } else {
+                buf.put_bytes(0, size - 14);
+            }
+        }
+    }
+
+    fn put_crystal(&self, buf: &mut BytesMut) {
+        buf.put_i16_le(self.crystal_index(0, self.tile_idx));
+        buf.put_u32_le((self.back & 0x7FFF) as u32 | if self.back & 0x8000 != 0 { 0x20000000 } else { 0 });
+        buf.put_i16_le(self.crystal_index(6, self.middle_idx));
+        buf.put_u16_le(self.middle);
+        buf.put_i16_le(self.crystal_index(10, self.file_idx));
+        buf.put_u16_le(self.objects);
+        buf.put_u8(self.door_idx);
+        buf.put_u8(self.door_offset);
+        buf.put_u8(self.frame);
+        buf.put_u8(self.tick);
+        if self.extra.len() == CRYSTAL_TILE_SIZE {
+            buf.put_slice(&self.extra[CRYSTAL_EXTRA]);
+        } else {
+            buf.put_bytes(0, CRYSTAL_EXTRA.len());
+        }
+        buf.put_u8(self.light);
+    }
+
+    /// 读取时的i16编号截断后仍等于file时写回原始值, 否则说明序号已修改
+    fn crystal_index(&self, offset: usize, file: u8) -> i16 {
+        if self.extra.len() != CRYSTAL_TILE_SIZE {
+            return file as i16;
+        }
+        let raw = i16::from_le_bytes([self.extra[offset], self.extra[offset + 1]]);
+        if raw as u8 == file { raw } else { file as i16 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shanda2012(width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.put_u16_le(width);
+        bytes.put_u16_le(height);
+        bytes.put_u8(0x0F);
+        bytes.put_slice(b"title 2012\0\0\0\r\n");
+        bytes.resize(MAP_HEADER_SIZE, 0x5A);
+        for i in 0..(width as usize * height as usize * 36) {
+            bytes.put_u8((i * 7 + 3) as u8);
+        }
+        bytes
+    }
+
+    fn crystal(width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = vec![2, 0, 0x43, 0x23];
+        bytes.put_u16_le(width);
+        bytes.put_u16_le(height);
+        for i in 0..width * height {
+            bytes.put_i16_le(i as i16 % 7);
+            bytes.put_u32_le((i as u32 * 3) | if i % 2 == 0 { 0x20000000 } else { 0 });
+            bytes.put_i16_le(i as i16 % 5);
+            bytes.put_u16_le(i * 11);
+            bytes.put_i16_le(i as i16 % 3);
+            bytes.put_u16_le(i * 13);
+            bytes.put_slice(&[0x81, 0x82, 0x03, 0x04]);
+            bytes.put_slice(&[i as u8, 1, 2, 3, 4, 5, 6]);
+            bytes.put_u8(9);
+        }
+        bytes
+    }
+
+    /// 4x2格子, 文件头和地表块填充非0字节, 第一格不可行走
+    fn mir3() -> Vec<u8> {
+        let mut bytes = vec![0x77; 22];
+        bytes[0] = 0;
+        bytes.put_u16_le(4);
+        bytes.put_u16_le(2);
+        bytes.put_slice(&[0x77, 0x77]);
+        for image in [4u16, 6] {
+            bytes.put_u8(0);
+            bytes.put_u16_le(image);
+        }
+        for i in 0..8 {
+            bytes.put_slice(&[if i == 0 { 0 } else { 3 }, 0, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 2, 0]);
+        }
+        bytes
+    }
+
+    fn save_bytes(map: &MapInfo, format: MapFormat) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.map");
+        map.save(&path, format).unwrap();
+        fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn load_save_round_trip() {
+        for (bytes, format) in [(shanda2012(3, 2), MapFormat::Shanda2012), (crystal(4, 3), MapFormat::Crystal)] {
+            let map = parse_map("test".to_string(), &bytes, None).unwrap();
+            assert_eq!(map.format, format);
+            assert_eq!(save_bytes(&map, format), bytes);
+        }
+
+        let map = parse_map("test".to_string(), &shanda2012(3, 2), None).unwrap();
+        let shanda = parse_map("test".to_string(), &save_bytes(&map, MapFormat::Shanda), None).unwrap();
+        assert_eq!(shanda.format, MapFormat::Shanda);
+        assert_eq!(&shanda.header[4..], &map.header[4..]);
+        assert_eq!(shanda.tiles[5].objects, map.tiles[5].objects);
+        assert!(shanda.tiles[5].extra.is_empty());
+    }
+
+    #[test]
+    fn mir3_to_classic() {
+        let map = parse_map("test".to_string(), &mir3(), Some(MapFormat::Mir3)).unwrap();
+        assert_eq!(map.header.len(), MIR3_HEADER_SIZE);
+        let bytes = save_bytes(&map, MapFormat::Classic);
+        assert_eq!(bytes.len(), MAP_HEADER_SIZE + 8 * 12);
+        assert_eq!(&bytes[..4], &[4, 0, 2, 0]);
+        assert!(bytes[4..MAP_HEADER_SIZE].iter().all(|x| *x == 0));
+
+        let classic = parse_map("test".to_string(), &bytes, None).unwrap();
+        assert_eq!(classic.format, MapFormat::Classic);
+        for (a, b) in classic.tiles.iter().zip(&map.tiles) {
+            assert_eq!((a.back, a.middle, a.objects, a.light), (b.back, b.middle, b.objects, b.light));
+        }
+        assert_eq!((classic.tiles[0].back, classic.tiles[0].objects), (0x8005, 0x8000));
+        assert_eq!(classic.tile(3, 1).unwrap().back, 7);
+    }
+
+    #[test]
+    fn crystal_library_indices() {
+        let mut bytes = crystal(2, 1);
+        bytes[8..10].copy_from_slice(&(-1i16).to_le_bytes());
+        bytes[14..16].copy_from_slice(&300i16.to_le_bytes());
+        bytes[34 + 10..34 + 12].copy_from_slice(&(-1i16).to_le_bytes());
+        let mut map = parse_map("test".to_string(), &bytes, None).unwrap();
+        assert_eq!((map.tiles[0].tile_idx, map.tiles[0].middle_idx, map.tiles[1].file_idx), (255, 44, 255));
+        assert_eq!(save_bytes(&map, MapFormat::Crystal), bytes);
+
+        // 修改过的序号按新值写入, 其余保持原值
+        map.set_layer(0, 0, MapLayer::Middle, 7, 3).unwrap();
+        let saved = save_bytes(&map, MapFormat::Crystal);
+        assert_eq!(&saved[14..16], &3i16.to_le_bytes());
+        assert_eq!(&saved[8..10], &(-1i16).to_le_bytes());
+        assert_eq!(&saved[34 + 10..34 + 12], &(-1i16).to_le_bytes());
+    }
+
+    #[test]
+    fn set_layer_keeps_flags() {
+        let mut map = parse_map("test".to_string(), &shanda2012(3, 2), None).unwrap();
+        let tile = map.tile_mut(1, 1).unwrap();
+        tile.back = 0x8005;
+        tile.objects = 0x8006;
+        map.set_layer(1, 1, MapLayer::Back, 0x8009, 2).unwrap();
+        map.set_layer(1, 1, MapLayer::Objects, 0, 4).unwrap();
+        map.set_layer(1, 1, MapLayer::Middle, 0x8003, 5).unwrap();
+        let tile = map.tile(1, 1).unwrap();
+        assert_eq!((tile.back, tile.tile_idx), (0x8009, 2));
+        assert_eq!((tile.objects, tile.file_idx), (0x8000, 4));
+        assert_eq!((tile.middle, tile.middle_idx), (0x8003, 5));
+
+        map.tile_mut(0, 0).unwrap().back = 0x1234;
+        map.set_layer(0, 0, MapLayer::Back, 0x8001, 0).unwrap();
+        assert_eq!(map.tile(0, 0).unwrap().back, 0x0001);
+        assert!(matches!(map.set_layer(3, 0, MapLayer::Back, 1, 0), Err(Error::BadIndex { .. })));
+    }
+
+    #[test]
+    fn resize_anchor() {
+        let resized = |width, height, anchor| {
+            let mut map = parse_map("test".to_string(), &shanda2012(3, 2), None).unwrap();
+            for (i, tile) in map.tiles.iter_mut().enumerate() {
+                tile.back = i as u16 + 1;
+            }
+            map.resize(width, height, anchor);
+            assert_eq!((map.width, map.height, map.tiles.len()), (width, height, (width * height) as usize));
+            map
+        };
+        let back = |map: &MapInfo, x, y| map.tile(x, y).unwrap().back;
+
+        let map = resized(5, 4, MapAnchor::BottomRight);
+        assert_eq!((back(&map, 2, 2), back(&map, 4, 3), back(&map, 1, 1)), (1, 6, 0));
+        // 居中时偏移向0取偶数
+        let map = resized(7, 6, MapAnchor::Center);
+        assert_eq!((back(&map, 2, 2), back(&map, 4, 3), back(&map, 1, 1)), (1, 6, 0));
+        let map = resized(5, 4, MapAnchor::Center);
+        assert_eq!((back(&map, 0, 0), back(&map, 2, 1)), (1, 6));
+        let map = resized(2, 1, MapAnchor::Right);
+        assert_eq!((back(&map, 0, 0), back(&map, 1, 0)), (3, 5));
+        let map = resized(3, 1, MapAnchor::Bottom);
+        assert_eq!((back(&map, 0, 0), back(&map, 2, 0)), (2, 6));
+    }
+
+    #[test]
+    fn save_rejects_large_map() {
+        let mut map = parse_map("test".to_string(), &shanda2012(3, 2), None).unwrap();
+        map.resize(70000, 1, MapAnchor::TopLeft);
+        let dir = tempfile::tempdir().unwrap();
+        let result = map.save(dir.path().join("test.map"), MapFormat::Shanda);
+        assert!(matches!(result, Err(Error::BadMapSize { width: 70000, .. })));
+    }
+
+    #[test]
+    fn region_clamped_to_map() {
+        let mut map = parse_map("test".to_string(), &shanda2012(3, 2), None).unwrap();
+        let region = map.copy_region(2, 1, u32::MAX, u32::MAX);
+        assert_eq!((region.width, region.height, region.tiles.len()), (1, 1, 1));
+        assert_eq!(region.tiles[0].back, map.tile(2, 1).unwrap().back);
+        assert_eq!(map.copy_region(u32::MAX, 0, 2, 2).tiles.len(), 0);
+
+        let region = map.copy_region(0, 0, 2, 2);
+        map.paste_region(&region, 1, u32::MAX);
+        map.paste_region(&region, 2, 1);
+        assert_eq!(map.tile(2, 1).unwrap().back, region.tiles[0].back);
+    }
+
+    #[test]
+    fn tile_flags() {
+        // back, middle, objects, door_idx, door_offset, frame, tick, file_idx, light, tile_idx, middle_idx
+        let tile = Tile::from(&[0x05, 0x80, 0x00, 0x00, 0x10, 0x00, 0x83, 0x85, 0x88, 0x04, 0x02, 0x03, 0x01, 0x02]);
+        assert_eq!(tile.door(), Some(Door { index: 3, offset: 5, open: true }));
+        assert_eq!(tile.animation(), Some(Animation { frames: 8, tick: 4, blend: true }));
+        assert_eq!(tile.light_radius(), 3);
+        assert!(!tile.can_walk());
+        assert!(tile.can_fly());
+
+        let mut tile = Tile::from(&[0x05, 0x00, 0x00, 0x00, 0x10, 0x80, 0x03, 0x05, 0x07, 0x04, 0x02, 0x00]);
+        assert_eq!(tile.door(), None);
+        assert_eq!(tile.animation(), Some(Animation { frames: 7, tick: 4, blend: false }));
+        assert_eq!(tile.light_radius(), 0);
+        assert!(!tile.can_walk());
+        assert!(!tile.can_fly());
+        tile.set_door_open(true);
+        assert_eq!(tile.door_offset, 0x05);
+
+        // 关闭的门不可通过, 打开后按back和objects判断
+        let mut tile = Tile::from(&[0x05, 0x00, 0x00, 0x00, 0x10, 0x00, 0x81, 0x02, 0x00, 0x00, 0x02, 0x07]);
+        assert_eq!(tile.animation(), None);
+        assert_eq!(tile.door(), Some(Door { index: 1, offset: 2, open: false }));
+        assert!(tile.is_door_closed());
+        assert!(!tile.can_walk());
+        assert!(!tile.can_fly());
+        assert!(tile.can_walk_ignoring_door() && tile.can_fly_ignoring_door());
+        tile.set_door_open(true);
+        assert_eq!(tile.door(), Some(Door { index: 1, offset: 2, open: true }));
+        assert_eq!(tile.door_offset, 0x82);
+        assert!(!tile.is_door_closed());
+        assert!(tile.can_walk());
+        assert!(tile.can_fly());
+
+        // 打开的门不影响back的标记
+        let tile = Tile::from(&[0x05, 0x80, 0x00, 0x00, 0x00, 0x00, 0x81, 0x82, 0x00, 0x00, 0x00, 0x00]);
+        assert!(!tile.is_door_closed());
+        assert!(!tile.can_walk());
+        assert!(tile.can_fly());
+    }
+}
@@ -0,0 +1,289 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use bytes::{Buf, Bytes};
+use flate2::read::GzDecoder;
+use crate::library::{check_remaining, read_buffer, ImageData, ImageHeader, ImageLibrary, LibrarySource, SourceReader};
+use crate::pixel::PixelFormat;
+use crate::error::{Error, Result};
+
+/// Crystal格式.Lib图片库
+/// 文件头: 4字节版本号, 4字节图片数量, 版本3及以上多4字节动画帧位置, 之后为每张图片的位置
+/// 图片头17字节: 宽, 高, x, y, 阴影x, 阴影y(各2字节), 阴影标记(1字节), 数据长度(4字节)
+/// 阴影标记最高位为1时, 图片数据之后还有一层遮罩: 宽, 高, x, y(各2字节), 数据长度(4字节)
+/// 图片数据为gzip压缩的32位BGRA像素, 行序自上而下
+pub struct MLibrary {
+    source: LibrarySource,
+    version: u32,
+    offsets: Vec<u32>,
+}
+
+/// .Lib中的一张图片, 包含阴影偏移和可选的遮罩层
+pub struct MImage {
+    pub image: ImageData,
+    pub shadow_x: i16,
+    pub shadow_y: i16,
+    pub shadow: u8,
+    pub mask: Option<ImageData>,
+}
+
+const IMAGE_HEADER_SIZE: usize = 17;
+const MASK_HEADER_SIZE: usize = 12;
+
+impl MLibrary {
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_source(LibrarySource::File(path.as_ref().to_path_buf()))
+    }
+
+    /// 使用已读取的.Lib内容, 用于资源包中的图片库
+    pub fn from_bytes(lib: Bytes) -> Result<Self> {
+        Self::from_source(LibrarySource::Bytes(lib))
+    }
+
+    fn from_source(source: LibrarySource) -> Result<Self> {
+        let mut reader = source.open()?;
+        let mut header = [0u8; 12];
+        read_buffer(&mut reader, &mut header[..8])?;
+        let mut buf = &header[..];
+        let version = buf.get_u32_le();
+        let count = buf.get_u32_le() as usize;
+        if version < 2 {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        if version >= 3 {
+            read_buffer(&mut reader, &mut header[8..12])?;
+        }
+        let length = count.checked_mul(4).ok_or_else(|| Error::BadImage(format!("{} images", count)))?;
+        check_remaining(&mut reader, length)?;
+        let mut index = vec![0u8; length];
+        read_buffer(&mut reader, &mut index[..])?;
+        let offsets = index.chunks_exact(4).map(|mut x| x.get_u32_le()).collect();
+        Ok(Self { source, version, offsets })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn load(&self, index: u32) -> Result<MImage> {
+        let (mut reader, head) = self.read_head(index)?;
+        let image = read_layer(&mut reader, head.width, head.height, head.offset_x, head.offset_y, head.length)?;
+
+        let mask = if head.shadow >> 7 == 1 {
+            let mut head = [0u8; MASK_HEADER_SIZE];
+            read_buffer(&mut reader, &mut head[..])?;
+            let mut head = &head[..];
+            let width = head.get_u16_le();
+            let height = head.get_u16_le();
+            let offset_x = head.get_i16_le();
+            let offset_y = head.get_i16_le();
+            let length = head.get_u32_le();
+            if width > 0 && height > 0 && length > 0 {
+                Some(read_layer(&mut reader, width, height, offset_x, offset_y, length)?)
+            } else { None }
+        } else { None };
+
+        Ok(MImage { image, shadow_x: head.shadow_x, shadow_y: head.shadow_y, shadow: head.shadow, mask })
+    }
+
+    /// 读取17字节图片头, 返回定位到图片数据的reader
+    fn read_head(&self, index: u32) -> Result<(SourceReader, MHeader)> {
+        let seek = *self.offsets.get(index as usize).ok_or(Error::BadIndex { index, len: self.offsets.len() })?;
+        if seek == 0 { return Err(Error::EmptyImage) }
+
+        let mut reader = self.source.open()?;
+        reader.seek(SeekFrom::Start(seek as u64))?;
+        let mut head = [0u8; IMAGE_HEADER_SIZE];
+        read_buffer(&mut reader, &mut head[..])?;
+        let mut head = &head[..];
+        let head = MHeader {
+            width: head.get_u16_le(),
+            height: head.get_u16_le(),
+            offset_x: head.get_i16_le(),
+            offset_y: head.get_i16_le(),
+            shadow_x: head.get_i16_le(),
+            shadow_y: head.get_i16_le(),
+            shadow: head.get_u8(),
+            length: head.get_u32_le(),
+        };
+        if head.width == 0 || head.height == 0 || head.length == 0 {
+            return Err(Error::EmptyImage);
+        }
+        Ok((reader, head))
+    }
+}
+
+struct MHeader {
+    width: u16,
+    height: u16,
+    offset_x: i16,
+    offset_y: i16,
+    shadow_x: i16,
+    shadow_y: i16,
+    shadow: u8,
+    length: u32,
+}
+
+/// 像素解压后为32位BGRA, 格式固定为Argb32
+impl ImageLibrary for MLibrary {
+
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn header(&self, index: u32) -> Result<ImageHeader> {
+        let (_, head) = self.read_head(index)?;
+        Ok(ImageHeader { format: PixelFormat::Argb32, width: head.width, height: head.height, offset_x: head.offset_x, offset_y: head.offset_y })
+    }
+
+    fn load_image(&self, index: u32) -> Result<ImageData> {
+        self.load(index).map(|x| x.image)
+    }
+
+    fn sources(&self) -> Vec<PathBuf> {
+        self.source.path().into_iter().map(Path::to_path_buf).collect()
+    }
+}
+
+fn read_layer(reader: &mut SourceReader, width: u16, height: u16, offset_x: i16, offset_y: i16, length: u32) -> Result<ImageData> {
+    check_remaining(reader, length as usize)?;
+    let mut data = vec![0u8; length as usize];
+    read_buffer(reader, &mut data[..])?;
+    let mut raw = Vec::with_capacity((width as usize * height as usize * 4).min(data.len().saturating_mul(1032)));
+    GzDecoder::new(&data[..]).read_to_end(&mut raw).map_err(|e| Error::Decompress(e.to_string()))?;
+    let bytes = bgra_to_rgba(width as usize, height as usize, &raw)?;
+    Ok(ImageData { width, height, offset_x, offset_y, bytes: Bytes::from(bytes) })
+}
+
+/// 旧版本按4像素对齐宽高后压缩, 按解压后的长度确定行宽
+fn bgra_to_rgba(width: usize, height: usize, raw: &[u8]) -> Result<Vec<u8>> {
+    let stride = if raw.len() >= width.div_ceil(4) * 4 * height.div_ceil(4) * 4 * 4 {
+        width.div_ceil(4) * 4
+    } else { width };
+    let expected = stride * height * 4;
+    if raw.len() < expected {
+        return Err(Error::TruncatedData { expected, actual: raw.len() });
+    }
+    let mut result = Vec::with_capacity(width * height * 4);
+    for row in raw.chunks_exact(stride * 4).take(height) {
+        for c in row[..width * 4].chunks_exact(4) {
+            result.extend_from_slice(&[c[2], c[1], c[0], c[3]]);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use bytes::BufMut;
+    use flate2::write::GzEncoder;
+    use super::*;
+
+    fn gzip(raw: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(raw).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    type Image = (u16, u16, Vec<u8>, Option<(u16, u16, Vec<u8>)>);
+
+    /// 每张图片为(宽, 高, 压缩数据, 遮罩), 遮罩存在时设置阴影标记最高位
+    fn lib_bytes(version: u32, images: &[Image]) -> Vec<u8> {
+        let mut lib = Vec::new();
+        lib.put_u32_le(version);
+        lib.put_u32_le(images.len() as u32);
+        if version >= 3 { lib.put_u32_le(0) }
+        let header_size = lib.len() + images.len() * 4;
+        let mut body = Vec::new();
+        for (width, height, data, mask) in images {
+            lib.put_u32_le((header_size + body.len()) as u32);
+            body.put_u16_le(*width);
+            body.put_u16_le(*height);
+            body.put_i16_le(-1);
+            body.put_i16_le(2);
+            body.put_i16_le(3);
+            body.put_i16_le(-4);
+            body.put_u8(if mask.is_some() { 0x85 } else { 0x05 });
+            body.put_u32_le(data.len() as u32);
+            body.put_slice(data);
+            if let Some((width, height, data)) = mask {
+                body.put_u16_le(*width);
+                body.put_u16_le(*height);
+                body.put_i16_le(5);
+                body.put_i16_le(6);
+                body.put_u32_le(data.len() as u32);
+                body.put_slice(data);
+            }
+        }
+        lib.put_slice(&body);
+        lib
+    }
+
+    fn open_lib(version: u32, images: &[Image]) -> Result<MLibrary> {
+        MLibrary::from_bytes(Bytes::from(lib_bytes(version, images)))
+    }
+
+    #[test]
+    fn versions_and_mask() {
+        // 2x1 BGRA
+        let pixels = gzip(&[1, 2, 3, 255, 4, 5, 6, 128]);
+        for version in [2, 3] {
+            let mask = Some((1, 1, gzip(&[7, 8, 9, 10])));
+            let library = open_lib(version, &[(2, 1, pixels.clone(), None), (2, 1, pixels.clone(), mask)]).unwrap();
+            assert_eq!((library.version(), library.len()), (version, 2));
+            let image = library.load(0).unwrap();
+            assert_eq!((image.image.width, image.image.height, image.image.offset_x, image.image.offset_y), (2, 1, -1, 2));
+            assert_eq!(&image.image.bytes[..], &[3, 2, 1, 255, 6, 5, 4, 128]);
+            assert_eq!((image.shadow_x, image.shadow_y, image.shadow), (3, -4, 0x05));
+            assert!(image.mask.is_none());
+
+            let image = library.load(1).unwrap();
+            assert_eq!(image.shadow >> 7, 1);
+            let mask = image.mask.unwrap();
+            assert_eq!((mask.width, mask.height, mask.offset_x, mask.offset_y), (1, 1, 5, 6));
+            assert_eq!(&mask.bytes[..], &[9, 8, 7, 10]);
+            assert_eq!(library.load_image(1).unwrap().bytes, image.image.bytes);
+            assert_eq!(library.header(1).unwrap().format, PixelFormat::Argb32);
+        }
+        assert!(matches!(open_lib(1, &[]), Err(Error::UnsupportedVersion(1))));
+    }
+
+    #[test]
+    fn aligned_stride() {
+        // 3x2按4像素对齐为4x4, 每行多出1个像素, 多出的行忽略
+        let mut raw = Vec::new();
+        for i in 0..16u8 {
+            raw.extend_from_slice(&[i, 0, 0, 255]);
+        }
+        let library = open_lib(2, &[(3, 2, gzip(&raw), None)]).unwrap();
+        let bytes = library.load_image(0).unwrap().bytes;
+        let reds: Vec<u8> = bytes.chunks_exact(4).map(|x| x[2]).collect();
+        assert_eq!(reds, vec![0, 1, 2, 4, 5, 6]);
+
+        // 未对齐时按宽度取行
+        let raw: Vec<u8> = (0..6u8).flat_map(|i| [i, 0, 0, 255]).collect();
+        let library = open_lib(2, &[(3, 2, gzip(&raw), None)]).unwrap();
+        let reds: Vec<u8> = library.load_image(0).unwrap().bytes.chunks_exact(4).map(|x| x[2]).collect();
+        assert_eq!(reds, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn truncated_data() {
+        // 压缩数据被截断
+        let pixels = gzip(&[1, 2, 3, 255, 4, 5, 6, 128]);
+        let library = open_lib(3, &[(2, 1, pixels[..pixels.len() / 2].to_vec(), None)]).unwrap();
+        assert!(matches!(library.load_image(0), Err(Error::Decompress(_))));
+        // 解压后像素不足
+        let library = open_lib(3, &[(2, 2, gzip(&[1, 2, 3, 255]), None)]).unwrap();
+        assert!(matches!(library.load_image(0), Err(Error::TruncatedData { expected: 16, actual: 4 })));
+        // 数据长度为0
+        let library = open_lib(3, &[(2, 1, Vec::new(), None)]).unwrap();
+        assert!(matches!(library.load_image(0), Err(Error::EmptyImage)));
+        // 文件被截断
+        let bytes = lib_bytes(3, &[(2, 1, pixels.clone(), None)]);
+        let library = MLibrary::from_bytes(Bytes::copy_from_slice(&bytes[..bytes.len() - 4])).unwrap();
+        assert!(matches!(library.load_image(0), Err(Error::TruncatedData { .. })));
+        assert!(matches!(MLibrary::from_bytes(Bytes::copy_from_slice(&bytes[..14])), Err(Error::TruncatedData { .. })));
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::Crc;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use memmap2::Mmap;
+use tracing::debug;
+use crate::error::{Error, Result};
+use crate::vfs::not_found;
+
+pub const PACK_SUFFIX: &str = "icmir";
+
+/// 文件头32字节
+/// 00-07: 标记"ICMIRPAK", 08: 版本, 12: 文件数量, 16: 目录位置(8字节), 24: 目录长度, 28: 目录crc32
+const PACK_MAGIC: &[u8; 8] = b"ICMIRPAK";
+const PACK_VERSION: u32 = 1;
+const PACK_HEADER_SIZE: usize = 32;
+/// 压缩后不小于原长度的90%时直接存储, 已压缩的wzl图片库通常如此
+const COMPRESS_RATIO: usize = 90;
+/// 目录项最小长度, 名称为空时
+const ENTRY_MIN_SIZE: usize = 31;
+/// zlib最大压缩比, 用于限制解压前预分配的内存
+const ZLIB_MAX_RATIO: usize = 1032;
+
+/// 包内文件的压缩方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackCompression {
+    Store,
+    Zlib,
+}
+
+impl PackCompression {
+    fn value(&self) -> u8 {
+        match self {
+            PackCompression::Store => 0,
+            PackCompression::Zlib => 1,
+        }
+    }
+
+    fn from_value(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(PackCompression::Store),
+            1 => Ok(PackCompression::Zlib),
+            _ => Err(Error::BadPack(format!("unknown compression: {}", value))),
+        }
+    }
+}
+
+/// 目录项格式
+/// 2字节名称长度, 名称(utf-8, 以/分隔), 1字节压缩方式, 8字节位置, 8字节存储长度, 8字节原长度, 4字节原数据crc32
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackEntry {
+    pub name: String,
+    pub compression: PackCompression,
+    pub offset: u64,
+    pub stored_size: u64,
+    pub size: u64,
+    pub crc: u32,
+}
+
+/// 资源包, 整个文件映射到内存, 未压缩的文件读取时不复制
+/// 名称不区分大小写, \和/都可以作为分隔符
+pub struct Pack {
+    path: PathBuf,
+    map: Arc<Mmap>,
+    entries: Vec<PackEntry>,
+    names: HashMap<String, usize>,
+}
+
+impl Pack {
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        // Safety: 资源文件在运行期间不会被修改
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        if map.len() < PACK_HEADER_SIZE {
+            return Err(Error::TruncatedHeader { expected: PACK_HEADER_SIZE, actual: map.len() });
+        }
+        if &map[..8] != PACK_MAGIC {
+            return Err(Error::BadPack("bad magic".to_string()));
+        }
+        let mut header = &map[8..PACK_HEADER_SIZE];
+        let version = header.get_u32_le();
+        if version != PACK_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let count = header.get_u32_le() as usize;
+        let dir_offset = header.get_u64_le() as usize;
+        let dir_length = header.get_u32_le() as usize;
+        let dir_crc = header.get_u32_le();
+
+        let Some(dir_end) = dir_offset.checked_add(dir_length).filter(|x| *x <= map.len()) else {
+            let actual = map.len().saturating_sub(dir_offset).min(dir_length);
+            return Err(Error::TruncatedData { expected: dir_length, actual });
+        };
+        let mut dir = &map[dir_offset..dir_end];
+        if crc32(dir) != dir_crc {
+            return Err(Error::ChecksumMismatch(path.display().to_string()));
+        }
+
+        // 文件数量来自文件头, 按目录长度限制预分配
+        let capacity = count.min(dir_length / ENTRY_MIN_SIZE);
+        let mut entries = Vec::with_capacity(capacity);
+        let mut names = HashMap::with_capacity(capacity);
+        for _ in 0..count {
+            let entry = read_entry(&mut dir)?;
+            if entry.offset.saturating_add(entry.stored_size) > dir_offset as u64 {
+                return Err(Error::BadPack(format!("entry out of range: {}", entry.name)));
+            }
+            names.insert(normalize(&entry.name), entries.len());
+            entries.push(entry);
+        }
+        debug!("open pack: {:?}, {} entries", path, count);
+        Ok(Self { path, map, entries, names })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> &[PackEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entry<P: AsRef<Path>>(&self, name: P) -> Option<&PackEntry> {
+        self.names.get(&normalize_path(name.as_ref())).map(|x| &self.entries[*x])
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, name: P) -> bool {
+        self.entry(name).is_some()
+    }
+
+    /// 读取文件内容, 压缩的文件解压后校验crc32
+    /// 未压缩的文件直接引用映射的内存, 不校验, 需要时使用`verify`
+    pub fn read<P: AsRef<Path>>(&self, name: P) -> Result<Bytes> {
+        let name = name.as_ref();
+        let entry = self.entry(name).ok_or_else(|| not_found(name))?;
+        self.read_entry(entry, false)
+    }
+
+    /// 校验所有文件的crc32, 返回校验失败的文件名
+    pub fn verify(&self) -> Vec<String> {
+        self.entries.iter().filter(|x| self.read_entry(x, true).is_err()).map(|x| x.name.clone()).collect()
+    }
+
+    fn read_entry(&self, entry: &PackEntry, verify: bool) -> Result<Bytes> {
+        let start = entry.offset as usize;
+        let stored = entry.stored_size as usize;
+        let bytes = match entry.compression {
+            PackCompression::Store => {
+                let bytes = Bytes::from_owner(MapSlice { map: self.map.clone(), start, end: start + stored });
+                if !verify { return Ok(bytes) }
+                bytes
+            }
+            // 目录中的原长度不可信, 预分配不超过最大压缩比, 最多多读1字节用于判断长度不符
+            PackCompression::Zlib => {
+                let mut data = Vec::with_capacity((entry.size as usize).min(stored.saturating_mul(ZLIB_MAX_RATIO)));
+                ZlibDecoder::new(&self.map[start..start + stored]).take(entry.size.saturating_add(1)).read_to_end(&mut data)
+                    .map_err(|e| Error::Decompress(e.to_string()))?;
+                Bytes::from(data)
+            }
+        };
+        if bytes.len() as u64 != entry.size || crc32(&bytes) != entry.crc {
+            return Err(Error::ChecksumMismatch(entry.name.clone()));
+        }
+        Ok(bytes)
+    }
+}
+
+/// 映射内存中的一段, 保持映射直到所有引用释放
+struct MapSlice {
+    map: Arc<Mmap>,
+    start: usize,
+    end: usize,
+}
+
+impl AsRef<[u8]> for MapSlice {
+    fn as_ref(&self) -> &[u8] {
+        &self.map[self.start..self.end]
+    }
+}
+
+/// 资源包生成, 文件在写入时才读取
+pub struct PackBuilder {
+    entries: Vec<(String, PackSource)>,
+    level: u32,
+}
+
+enum PackSource {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl Default for PackBuilder {
+    fn default() -> Self {
+        Self { entries: Vec::new(), level: 6 }
+    }
+}
+
+impl PackBuilder {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// zlib压缩级别0-9, 0表示全部直接存储
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 添加内存中的文件, 同名文件(不区分大小写)会被替换
+    pub fn add_bytes(&mut self, name: &str, bytes: Vec<u8>) {
+        self.put(name, PackSource::Bytes(bytes));
+    }
+
+    pub fn add_file<P: AsRef<Path>>(&mut self, name: &str, path: P) {
+        self.put(name, PackSource::File(path.as_ref().to_path_buf()));
+    }
+
+    /// 递归添加目录下的所有文件, 名称为相对dir的路径, 如data/objects.wzl, 返回添加的文件数
+    /// 符号链接会跳过, 避免链接到上级目录时无限递归
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<usize> {
+        let dir = dir.as_ref();
+        let mut pending = vec![dir.to_path_buf()];
+        let mut files = Vec::new();
+        while let Some(path) = pending.pop() {
+            for entry in fs::read_dir(&path)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_symlink() {
+                    debug!("skip symlink: {:?}", entry.path());
+                } else if file_type.is_dir() {
+                    pending.push(entry.path());
+                } else {
+                    files.push(entry.path());
+                }
+            }
+        }
+        files.sort();
+        for path in &files {
+            let name = path.strip_prefix(dir).unwrap_or(path);
+            let name: Vec<_> = name.components().filter_map(|x| match x {
+                Component::Normal(x) => Some(x.to_string_lossy()),
+                _ => None,
+            }).collect();
+            self.add_file(&name.join("/"), path);
+        }
+        Ok(files.len())
+    }
+
+    fn put(&mut self, name: &str, source: PackSource) {
+        let name = name.replace('\\', "/");
+        let key = normalize(&name);
+        match self.entries.iter_mut().find(|(x, _)| normalize(x) == key) {
+            Some(entry) => *entry = (name, source),
+            None => self.entries.push((name, source)),
+        }
+    }
+
+    /// 写入资源包, 返回所有目录项, 名称超过65535字节时返回`Error::BadPack`
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<Vec<PackEntry>> {
+        if let Some((name, _)) = self.entries.iter().find(|(x, _)| x.len() > u16::MAX as usize) {
+            return Err(Error::BadPack(format!("name too long: {} bytes", name.len())));
+        }
+        let mut writer = BufWriter::new(File::create(path.as_ref())?);
+        writer.write_all(&[0u8; PACK_HEADER_SIZE])?;
+        let mut offset = PACK_HEADER_SIZE as u64;
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for (name, source) in &self.entries {
+            let data = match source {
+                PackSource::File(path) => fs::read(path)?,
+                PackSource::Bytes(bytes) => bytes.clone(),
+            };
+            let compressed = self.compress(&data)?;
+            let (compression, stored) = match compressed.as_ref() {
+                Some(x) => (PackCompression::Zlib, &x[..]),
+                None => (PackCompression::Store, &data[..]),
+            };
+            writer.write_all(stored)?;
+            entries.push(PackEntry {
+                name: name.clone(),
+                compression,
+                offset,
+                stored_size: stored.len() as u64,
+                size: data.len() as u64,
+                crc: crc32(&data),
+            });
+            offset += stored.len() as u64;
+        }
+
+        let mut dir = BytesMut::new();
+        for entry in &entries {
+            write_entry(&mut dir, entry);
+        }
+        writer.write_all(&dir)?;
+
+        let mut header = BytesMut::with_capacity(PACK_HEADER_SIZE);
+        header.put_slice(PACK_MAGIC);
+        header.put_u32_le(PACK_VERSION);
+        header.put_u32_le(entries.len() as u32);
+        header.put_u64_le(offset);
+        header.put_u32_le(dir.len() as u32);
+        header.put_u32_le(crc32(&dir));
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&header)?;
+        writer.flush()?;
+        debug!("write pack: {:?}, {} entries, {} bytes", path.as_ref(), entries.len(), offset + dir.len() as u64);
+        Ok(entries)
+    }
+
+    /// 压缩效果不明显时返回None
+    fn compress(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        if self.level == 0 || data.is_empty() {
+            return Ok(None);
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::new(self.level));
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        Ok((compressed.len() * 100 < data.len() * COMPRESS_RATIO).then_some(compressed))
+    }
+}
+
+fn read_entry(buf: &mut &[u8]) -> Result<PackEntry> {
+    if buf.remaining() < 2 {
+        return Err(Error::TruncatedData { expected: 2, actual: buf.remaining() });
+    }
+    let name_length = buf.get_u16_le() as usize;
+    let expected = name_length + 29;
+    if buf.remaining() < expected {
+        return Err(Error::TruncatedData { expected, actual: buf.remaining() });
+    }
+    let name = String::from_utf8_lossy(&buf[..name_length]).to_string();
+    buf.advance(name_length);
+    Ok(PackEntry {
+        name,
+        compression: PackCompression::from_value(buf.get_u8())?,
+        offset: buf.get_u64_le(),
+        stored_size: buf.get_u64_le(),
+        size: buf.get_u64_le(),
+        crc: buf.get_u32_le(),
+    })
+}
+
+fn write_entry(buf: &mut BytesMut, entry: &PackEntry) {
+    buf.put_u16_le(entry.name.len() as u16);
+    buf.put_slice(entry.name.as_bytes());
+    buf.put_u8(entry.compression.value());
+    buf.put_u64_le(entry.offset);
+    buf.put_u64_le(entry.stored_size);
+    buf.put_u64_le(entry.size);
+    buf.put_u32_le(entry.crc);
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+fn normalize(name: &str) -> String {
+    name.split(['\\', '/']).filter(|x| !x.is_empty()).collect::<Vec<_>>().join("/").to_ascii_lowercase()
+}
+
+fn normalize_path(name: &Path) -> String {
+    let parts: Vec<_> = name.components().filter_map(|x| match x {
+        Component::Normal(x) => Some(x.to_string_lossy()),
+        _ => None,
+    }).collect();
+    normalize(&parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.icmir");
+        let mut builder = PackBuilder::new();
+        builder.add_bytes("data\\Tiles.wzl", vec![1; 4096]);
+        builder.add_bytes("map/0.map", vec![1, 2, 3]);
+        builder.add_bytes("DATA/tiles.wzl", vec![7; 10]);
+        assert_eq!(builder.len(), 2);
+        builder.write(&path).unwrap();
+
+        let pack = Pack::open(&path).unwrap();
+        assert_eq!(pack.len(), 2);
+        assert_eq!(&pack.read("data/tiles.WZL").unwrap()[..], &[7; 10]);
+        assert_eq!(pack.entry("map\\0.map").unwrap().compression, PackCompression::Store);
+        assert!(pack.verify().is_empty());
+        assert!(matches!(pack.read("map/1.map"), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn long_name_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.icmir");
+        let mut builder = PackBuilder::new();
+        builder.add_bytes(&"a".repeat(u16::MAX as usize), vec![1]);
+        builder.write(&path).unwrap();
+        assert_eq!(Pack::open(&path).unwrap().entries()[0].name.len(), u16::MAX as usize);
+
+        builder.add_bytes(&"b".repeat(u16::MAX as usize + 1), vec![1]);
+        assert!(matches!(builder.write(&path), Err(Error::BadPack(_))));
+    }
+
+    /// 修改文件头或目录后重新计算目录的crc32
+    fn patch(path: &Path, f: impl Fn(&mut Vec<u8>, usize)) {
+        let mut bytes = fs::read(path).unwrap();
+        let dir_offset = u64::from_le_bytes(bytes[16..24].try_into().unwrap()) as usize;
+        f(&mut bytes, dir_offset);
+        if let Some(dir) = bytes.get(dir_offset..) {
+            let crc = crc32(dir);
+            bytes[28..32].copy_from_slice(&crc.to_le_bytes());
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn bad_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.icmir");
+        let mut builder = PackBuilder::new();
+        builder.add_bytes("a", vec![1; 4096]);
+        builder.write(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(Pack::open(&path).unwrap().entry("a").unwrap().compression, PackCompression::Zlib);
+
+        // 目录位置超出文件, 长度为0或位置加长度溢出
+        for (offset, length) in [(bytes.len() as u64 + 100, 0u32), (u64::MAX, 10)] {
+            fs::write(&path, &bytes).unwrap();
+            patch(&path, |x, _| {
+                x[16..24].copy_from_slice(&offset.to_le_bytes());
+                x[24..28].copy_from_slice(&length.to_le_bytes());
+            });
+            assert!(matches!(Pack::open(&path), Err(Error::TruncatedData { .. })));
+        }
+
+        // 文件数量大于目录中的条目
+        fs::write(&path, &bytes).unwrap();
+        patch(&path, |x, _| x[12..16].copy_from_slice(&u32::MAX.to_le_bytes()));
+        assert!(matches!(Pack::open(&path), Err(Error::TruncatedData { .. })));
+
+        // 目录被截断
+        fs::write(&path, &bytes[..bytes.len() - 5]).unwrap();
+        assert!(matches!(Pack::open(&path), Err(Error::TruncatedData { .. })));
+
+        // crc正确但原长度错误
+        for size in [u64::MAX, 4095, 4097] {
+            fs::write(&path, &bytes).unwrap();
+            patch(&path, |x, dir| x[dir + 20..dir + 28].copy_from_slice(&size.to_le_bytes()));
+            let pack = Pack::open(&path).unwrap();
+            assert!(matches!(pack.read("a"), Err(Error::ChecksumMismatch(_))));
+            assert_eq!(pack.verify(), vec!["a"]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_dir_skips_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path().join("data");
+        fs::create_dir_all(data.join("sub")).unwrap();
+        fs::write(data.join("a.wzl"), [1]).unwrap();
+        fs::write(data.join("sub").join("b.wzx"), [2]).unwrap();
+        // 指向上级目录的链接会造成无限递归
+        std::os::unix::fs::symlink(dir.path(), data.join("sub").join("loop")).unwrap();
+        std::os::unix::fs::symlink(data.join("a.wzl"), data.join("c.wzl")).unwrap();
+
+        let mut builder = PackBuilder::new();
+        assert_eq!(builder.add_dir(dir.path()).unwrap(), 2);
+        let path = dir.path().join("test.icmir");
+        builder.write(&path).unwrap();
+        let names: Vec<String> = Pack::open(&path).unwrap().entries().iter().map(|x| x.name.clone()).collect();
+        assert_eq!(names, vec!["data/a.wzl", "data/sub/b.wzx"]);
+    }
+}
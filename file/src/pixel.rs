@@ -0,0 +1,283 @@
+use crate::error::{Error, Result};
+
+/// 图片像素格式, 对应wzl图片头第1个字节
+/// 3: 8位调色板, 4: RGB555, 5: RGB565, 6: 24位BGR, 7: 32位BGRA(小端ARGB)
+/// 每行按4字节对齐, 行序自下而上
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// 8位调色板, 索引0为透明
+    Palette8,
+    /// 16位RGB555, 0为透明
+    Rgb555,
+    /// 16位RGB565, 0为透明
+    Rgb565,
+    /// 24位BGR, 纯黑为透明
+    Rgb24,
+    /// 32位BGRA, 使用自带的alpha
+    Argb32,
+}
+
+impl PixelFormat {
+
+    pub fn from_value(value: u8) -> Result<Self> {
+        match value {
+            3 => Ok(PixelFormat::Palette8),
+            4 => Ok(PixelFormat::Rgb555),
+            5 => Ok(PixelFormat::Rgb565),
+            6 => Ok(PixelFormat::Rgb24),
+            7 => Ok(PixelFormat::Argb32),
+            _ => Err(Error::UnsupportedPixelFormat(value)),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            PixelFormat::Palette8 => 3,
+            PixelFormat::Rgb555 => 4,
+            PixelFormat::Rgb565 => 5,
+            PixelFormat::Rgb24 => 6,
+            PixelFormat::Argb32 => 7,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Palette8 => 1,
+            PixelFormat::Rgb555 | PixelFormat::Rgb565 => 2,
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Argb32 => 4,
+        }
+    }
+
+    /// 每行按4字节对齐后的字节数
+    pub fn stride(&self, width: usize) -> usize {
+        (width * self.bytes_per_pixel()).div_ceil(4) * 4
+    }
+
+    /// 未压缩的像素数据长度
+    pub fn data_length(&self, width: usize, height: usize) -> usize {
+        self.stride(width) * height
+    }
+
+    /// 解码为自上而下的RGBA, palette为256色RGBA调色板, 仅Palette8使用
+    /// 最后一行可以不补齐
+    pub fn decode(&self, width: usize, height: usize, data: &[u8], palette: &[u8]) -> Result<Vec<u8>> {
+        if width == 0 || height == 0 {
+            return Ok(Vec::new());
+        }
+        let bpp = self.bytes_per_pixel();
+        let stride = self.stride(width);
+        let expected = (height - 1) * stride + width * bpp;
+        if data.len() < expected {
+            return Err(Error::TruncatedData { expected, actual: data.len() });
+        }
+        if *self == PixelFormat::Palette8 && palette.len() < 1024 {
+            return Err(Error::TruncatedData { expected: 1024, actual: palette.len() });
+        }
+
+        let mut result = Vec::with_capacity(width * height * 4);
+        for i in 0..height {
+            let row = &data[(height - i - 1) * stride..];
+            for c in row[..width * bpp].chunks_exact(bpp) {
+                match self {
+                    PixelFormat::Palette8 => {
+                        let x = c[0] as usize * 4;
+                        result.extend_from_slice(&palette[x..x + 4]);
+                    }
+                    PixelFormat::Rgb555 => {
+                        let v = u16::from_le_bytes([c[0], c[1]]);
+                        let r = ((v >> 10) & 0x1F) as u8;
+                        let g = ((v >> 5) & 0x1F) as u8;
+                        let b = (v & 0x1F) as u8;
+                        result.extend_from_slice(&[r << 3, g << 3, b << 3, if v & 0x7FFF == 0 { 0 } else { 255 }]);
+                    }
+                    PixelFormat::Rgb565 => {
+                        let (lo, hi) = (c[0], c[1]);
+                        result.push(hi & 0xF8);
+                        result.push((((hi & 0x7) << 3) | (lo >> 5)) * 4);
+                        result.push((lo & 0x1F) * 8);
+                        result.push(if lo == 0 && hi == 0 { 0 } else { 255 });
+                    }
+                    PixelFormat::Rgb24 => {
+                        let alpha = if c[0] == 0 && c[1] == 0 && c[2] == 0 { 0 } else { 255 };
+                        result.extend_from_slice(&[c[2], c[1], c[0], alpha]);
+                    }
+                    PixelFormat::Argb32 => {
+                        result.extend_from_slice(&[c[2], c[1], c[0], c[3]]);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// 编码一个RGBA像素, out长度为bytes_per_pixel, Palette8需要查找调色板, 由调用方处理
+    /// 除Argb32外alpha小于128写为透明色, 不透明的纯黑写为最暗的颜色以免被当作透明
+    pub(crate) fn encode_pixel(&self, c: &[u8], out: &mut [u8]) {
+        let opaque = c[3] >= 128;
+        match self {
+            PixelFormat::Palette8 => {}
+            PixelFormat::Rgb555 => {
+                let v = ((c[0] as u16 >> 3) << 10) | ((c[1] as u16 >> 3) << 5) | (c[2] as u16 >> 3);
+                let v = if !opaque { 0 } else if v == 0 { 0x20 } else { v };
+                out.copy_from_slice(&v.to_le_bytes());
+            }
+            PixelFormat::Rgb565 => {
+                let v = ((c[0] as u16 & 0xF8) << 8) | ((c[1] as u16 & 0xFC) << 3) | (c[2] as u16 >> 3);
+                let v = if !opaque { 0 } else if v == 0 { 0x20 } else { v };
+                out.copy_from_slice(&v.to_le_bytes());
+            }
+            PixelFormat::Rgb24 => {
+                if !opaque {
+                    out.fill(0);
+                } else if c[0] == 0 && c[1] == 0 && c[2] == 0 {
+                    out.copy_from_slice(&[0, 1, 0]);
+                } else {
+                    out.copy_from_slice(&[c[2], c[1], c[0]]);
+                }
+            }
+            PixelFormat::Argb32 => out.copy_from_slice(&[c[2], c[1], c[0], c[3]]),
+        }
+    }
+}
+
+/// 8位图片使用的默认调色板, RGBA, 索引0为透明
+pub const PALETTE_RGBA: [u8; 1024] = [
+    0x00,0x00,0x00,0x00,0x80,0x00,0x00,0xFF,0x00,0x80,0x00,0xFF,0x80,0x80,0x00,0xFF,0x00,0x00,0x80,0xFF,0x80,0x00,0x80,0xFF,0x00,0x80,0x80,0xFF,0xC0,0xC0,0xC0,0xFF,
+    0x55,0x80,0x97,0xFF,0x9D,0xB9,0xC8,0xFF,0x7B,0x73,0x73,0xFF,0x2D,0x29,0x29,0xFF,0x5A,0x52,0x52,0xFF,0x63,0x5A,0x5A,0xFF,0x42,0x39,0x39,0xFF,0x1D,0x18,0x18,0xFF,
+    0x18,0x10,0x10,0xFF,0x29,0x18,0x18,0xFF,0x10,0x08,0x08,0xFF,0xF2,0x79,0x71,0xFF,0xE1,0x67,0x5F,0xFF,0xFF,0x5A,0x5A,0xFF,0xFF,0x31,0x31,0xFF,0xD6,0x5A,0x52,0xFF,
+    0x94,0x10,0x00,0xFF,0x94,0x29,0x18,0xFF,0x39,0x08,0x00,0xFF,0x73,0x10,0x00,0xFF,0xB5,0x18,0x00,0xFF,0xBD,0x63,0x52,0xFF,0x42,0x18,0x10,0xFF,0xFF,0xAA,0x99,0xFF,
+    0x5A,0x10,0x00,0xFF,0x73,0x39,0x29,0xFF,0xA5,0x4A,0x31,0xFF,0x94,0x7B,0x73,0xFF,0xBD,0x52,0x31,0xFF,0x52,0x21,0x10,0xFF,0x7B,0x31,0x18,0xFF,0x2D,0x18,0x10,0xFF,
+    0x8C,0x4A,0x31,0xFF,0x94,0x29,0x00,0xFF,0xBD,0x31,0x00,0xFF,0xC6,0x73,0x52,0xFF,0x6B,0x31,0x18,0xFF,0xC6,0x6B,0x42,0xFF,0xCE,0x4A,0x00,0xFF,0xA5,0x63,0x39,0xFF,
+    0x5A,0x31,0x18,0xFF,0x2A,0x10,0x00,0xFF,0x15,0x08,0x00,0xFF,0x3A,0x18,0x00,0xFF,0x08,0x00,0x00,0xFF,0x29,0x00,0x00,0xFF,0x4A,0x00,0x00,0xFF,0x9D,0x00,0x00,0xFF,
+    0xDC,0x00,0x00,0xFF,0xDE,0x00,0x00,0xFF,0xFB,0x00,0x00,0xFF,0x9C,0x73,0x52,0xFF,0x94,0x6B,0x4A,0xFF,0x73,0x4A,0x29,0xFF,0x52,0x31,0x18,0xFF,0x8C,0x4A,0x18,0xFF,
+    0x88,0x44,0x11,0xFF,0x4A,0x21,0x00,0xFF,0x21,0x18,0x10,0xFF,0xD6,0x94,0x5A,0xFF,0xC6,0x6B,0x21,0xFF,0xEF,0x6B,0x00,0xFF,0xFF,0x77,0x00,0xFF,0xA5,0x94,0x84,0xFF,
+    0x42,0x31,0x21,0xFF,0x18,0x10,0x08,0xFF,0x29,0x18,0x08,0xFF,0x21,0x10,0x00,0xFF,0x39,0x29,0x18,0xFF,0x8C,0x63,0x39,0xFF,0x42,0x29,0x10,0xFF,0x6B,0x42,0x18,0xFF,
+    0x7B,0x4A,0x18,0xFF,0x94,0x4A,0x00,0xFF,0x8C,0x84,0x7B,0xFF,0x6B,0x63,0x5A,0xFF,0x4A,0x42,0x39,0xFF,0x29,0x21,0x18,0xFF,0x46,0x39,0x29,0xFF,0xB5,0xA5,0x94,0xFF,
+    0x7B,0x6B,0x5A,0xFF,0xCE,0xB1,0x94,0xFF,0xA5,0x8C,0x73,0xFF,0x8C,0x73,0x5A,0xFF,0xB5,0x94,0x73,0xFF,0xD6,0xA5,0x73,0xFF,0xEF,0xA5,0x4A,0xFF,0xEF,0xC6,0x8C,0xFF,
+    0x7B,0x63,0x42,0xFF,0x6B,0x56,0x39,0xFF,0xBD,0x94,0x5A,0xFF,0x63,0x39,0x00,0xFF,0xD6,0xC6,0xAD,0xFF,0x52,0x42,0x29,0xFF,0x94,0x63,0x18,0xFF,0xEF,0xD6,0xAD,0xFF,
+    0xA5,0x8C,0x63,0xFF,0x63,0x5A,0x4A,0xFF,0xBD,0xA5,0x7B,0xFF,0x5A,0x42,0x18,0xFF,0xBD,0x8C,0x31,0xFF,0x35,0x31,0x29,0xFF,0x94,0x84,0x63,0xFF,0x7B,0x6B,0x4A,0xFF,
+    0xA5,0x8C,0x5A,0xFF,0x5A,0x4A,0x29,0xFF,0x9C,0x7B,0x39,0xFF,0x42,0x31,0x10,0xFF,0xEF,0xAD,0x21,0xFF,0x18,0x10,0x00,0xFF,0x29,0x21,0x00,0xFF,0x9C,0x6B,0x00,0xFF,
+    0x94,0x84,0x5A,0xFF,0x52,0x42,0x18,0xFF,0x6B,0x5A,0x29,0xFF,0x7B,0x63,0x21,0xFF,0x9C,0x7B,0x21,0xFF,0xDE,0xA5,0x00,0xFF,0x5A,0x52,0x39,0xFF,0x31,0x29,0x10,0xFF,
+    0xCE,0xBD,0x7B,0xFF,0x63,0x5A,0x39,0xFF,0x94,0x84,0x4A,0xFF,0xC6,0xA5,0x29,0xFF,0x10,0x9C,0x18,0xFF,0x42,0x8C,0x4A,0xFF,0x31,0x8C,0x42,0xFF,0x10,0x94,0x29,0xFF,
+    0x08,0x18,0x10,0xFF,0x08,0x18,0x18,0xFF,0x08,0x29,0x10,0xFF,0x18,0x42,0x29,0xFF,0xA5,0xB5,0xAD,0xFF,0x6B,0x73,0x73,0xFF,0x18,0x29,0x29,0xFF,0x18,0x42,0x4A,0xFF,
+    0x31,0x42,0x4A,0xFF,0x63,0xC6,0xDE,0xFF,0x44,0xDD,0xFF,0xFF,0x8C,0xD6,0xEF,0xFF,0x73,0x6B,0x39,0xFF,0xF7,0xDE,0x39,0xFF,0xF7,0xEF,0x8C,0xFF,0xF7,0xE7,0x00,0xFF,
+    0x6B,0x6B,0x5A,0xFF,0x5A,0x8C,0xA5,0xFF,0x39,0xB5,0xEF,0xFF,0x4A,0x9C,0xCE,0xFF,0x31,0x84,0xB5,0xFF,0x31,0x52,0x6B,0xFF,0xDE,0xDE,0xD6,0xFF,0xBD,0xBD,0xB5,0xFF,
+    0x8C,0x8C,0x84,0xFF,0xF7,0xF7,0xDE,0xFF,0x00,0x08,0x18,0xFF,0x08,0x18,0x39,0xFF,0x08,0x10,0x29,0xFF,0x08,0x18,0x00,0xFF,0x08,0x29,0x00,0xFF,0x00,0x52,0xA5,0xFF,
+    0x00,0x7B,0xDE,0xFF,0x10,0x29,0x4A,0xFF,0x10,0x39,0x6B,0xFF,0x10,0x52,0x8C,0xFF,0x21,0x5A,0xA5,0xFF,0x10,0x31,0x5A,0xFF,0x10,0x42,0x84,0xFF,0x31,0x52,0x84,0xFF,
+    0x18,0x21,0x31,0xFF,0x4A,0x5A,0x7B,0xFF,0x52,0x6B,0xA5,0xFF,0x29,0x39,0x63,0xFF,0x10,0x4A,0xDE,0xFF,0x29,0x29,0x21,0xFF,0x4A,0x4A,0x39,0xFF,0x29,0x29,0x18,0xFF,
+    0x4A,0x4A,0x29,0xFF,0x7B,0x7B,0x42,0xFF,0x9C,0x9C,0x4A,0xFF,0x5A,0x5A,0x29,0xFF,0x42,0x42,0x14,0xFF,0x39,0x39,0x00,0xFF,0x59,0x59,0x00,0xFF,0xCA,0x35,0x2C,0xFF,
+    0x6B,0x73,0x21,0xFF,0x29,0x31,0x00,0xFF,0x31,0x39,0x10,0xFF,0x31,0x39,0x18,0xFF,0x42,0x4A,0x00,0xFF,0x52,0x63,0x18,0xFF,0x5A,0x73,0x29,0xFF,0x31,0x4A,0x18,0xFF,
+    0x18,0x21,0x00,0xFF,0x18,0x31,0x00,0xFF,0x18,0x39,0x10,0xFF,0x63,0x84,0x4A,0xFF,0x6B,0xBD,0x4A,0xFF,0x63,0xB5,0x4A,0xFF,0x63,0xBD,0x4A,0xFF,0x5A,0x9C,0x4A,0xFF,
+    0x4A,0x8C,0x39,0xFF,0x63,0xC6,0x4A,0xFF,0x63,0xD6,0x4A,0xFF,0x52,0x84,0x4A,0xFF,0x31,0x73,0x29,0xFF,0x63,0xC6,0x5A,0xFF,0x52,0xBD,0x4A,0xFF,0x10,0xFF,0x00,0xFF,
+    0x18,0x29,0x18,0xFF,0x4A,0x88,0x4A,0xFF,0x4A,0xE7,0x4A,0xFF,0x00,0x5A,0x00,0xFF,0x00,0x88,0x00,0xFF,0x00,0x94,0x00,0xFF,0x00,0xDE,0x00,0xFF,0x00,0xEE,0x00,0xFF,
+    0x00,0xFB,0x00,0xFF,0x4A,0x5A,0x94,0xFF,0x63,0x73,0xB5,0xFF,0x7B,0x8C,0xD6,0xFF,0x6B,0x7B,0xD6,0xFF,0x77,0x88,0xFF,0xFF,0xC6,0xC6,0xCE,0xFF,0x94,0x94,0x9C,0xFF,
+    0x9C,0x94,0xC6,0xFF,0x31,0x31,0x39,0xFF,0x29,0x18,0x84,0xFF,0x18,0x00,0x84,0xFF,0x4A,0x42,0x52,0xFF,0x52,0x42,0x7B,0xFF,0x63,0x5A,0x73,0xFF,0xCE,0xB5,0xF7,0xFF,
+    0x8C,0x7B,0x9C,0xFF,0x77,0x22,0xCC,0xFF,0xDD,0xAA,0xFF,0xFF,0xF0,0xB4,0x2A,0xFF,0xDF,0x00,0x9F,0xFF,0xE3,0x17,0xB3,0xFF,0xFF,0xFB,0xF0,0xFF,0xA0,0xA0,0xA4,0xFF,
+    0x80,0x80,0x80,0xFF,0xFF,0x00,0x00,0xFF,0x00,0xFF,0x00,0xFF,0xFF,0xFF,0x00,0xFF,0x00,0x00,0xFF,0xFF,0xFF,0x00,0xFF,0xFF,0x00,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [PixelFormat; 5] = [PixelFormat::Palette8, PixelFormat::Rgb555, PixelFormat::Rgb565, PixelFormat::Rgb24, PixelFormat::Argb32];
+
+    /// 按行对齐和自下而上的行序编码RGBA
+    fn encode(format: PixelFormat, width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+        let (stride, bpp) = (format.stride(width), format.bytes_per_pixel());
+        let mut data = vec![0xEE; format.data_length(width, height)];
+        for y in 0..height {
+            let row = &mut data[(height - y - 1) * stride..];
+            for x in 0..width {
+                let i = (y * width + x) * 4;
+                format.encode_pixel(&rgba[i..i + 4], &mut row[x * bpp..(x + 1) * bpp]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn value_round_trip() {
+        for format in FORMATS {
+            assert_eq!(PixelFormat::from_value(format.value()).unwrap(), format);
+        }
+        assert!(matches!(PixelFormat::from_value(2), Err(Error::UnsupportedPixelFormat(2))));
+        assert!(matches!(PixelFormat::from_value(8), Err(Error::UnsupportedPixelFormat(8))));
+    }
+
+    #[test]
+    fn stride_odd_width() {
+        let expected = [
+            (PixelFormat::Palette8, [4, 4, 8, 8]),
+            (PixelFormat::Rgb555, [4, 8, 12, 16]),
+            (PixelFormat::Rgb565, [4, 8, 12, 16]),
+            (PixelFormat::Rgb24, [4, 12, 16, 24]),
+            (PixelFormat::Argb32, [4, 12, 20, 28]),
+        ];
+        for (format, strides) in expected {
+            for (width, stride) in [1, 3, 5, 7].into_iter().zip(strides) {
+                assert_eq!(format.stride(width), stride, "{:?} width {}", format, width);
+                assert_eq!(stride % 4, 0);
+                assert!(stride >= width * format.bytes_per_pixel());
+                assert_eq!(format.data_length(width, 3), stride * 3);
+            }
+            assert_eq!(format.stride(0), 0);
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        // 每种格式都能无损表示的颜色: 分量为8的倍数
+        let colors: [[u8; 4]; 6] = [[248, 0, 0, 255], [0, 248, 0, 255], [0, 0, 248, 255], [8, 128, 200, 255], [64, 32, 16, 255], [248, 248, 248, 255]];
+        let (width, height) = (3, 2);
+        let rgba: Vec<u8> = colors.iter().flatten().copied().collect();
+        for format in FORMATS.into_iter().filter(|x| *x != PixelFormat::Palette8) {
+            let data = encode(format, width, height, &rgba);
+            assert_eq!(format.decode(width, height, &data, &[]).unwrap(), rgba, "{:?}", format);
+            // 最后一行可以不补齐
+            let short = &data[..format.stride(width) + width * format.bytes_per_pixel()];
+            assert_eq!(format.decode(width, height, short, &[]).unwrap(), rgba);
+            let short = &short[..short.len() - 1];
+            assert!(matches!(format.decode(width, height, short, &[]), Err(Error::TruncatedData { .. })));
+        }
+
+        let argb = [[1, 2, 3, 4], [200, 100, 50, 127]];
+        let rgba: Vec<u8> = argb.iter().flatten().copied().collect();
+        let data = encode(PixelFormat::Argb32, 1, 2, &rgba);
+        assert_eq!(PixelFormat::Argb32.decode(1, 2, &data, &[]).unwrap(), rgba);
+    }
+
+    #[test]
+    fn transparent_and_black() {
+        let rgba = [255, 255, 255, 0, 0, 0, 0, 255];
+        for format in [PixelFormat::Rgb555, PixelFormat::Rgb565, PixelFormat::Rgb24] {
+            let data = encode(format, 2, 1, &rgba);
+            let bpp = format.bytes_per_pixel();
+            assert!(data[..bpp].iter().all(|x| *x == 0), "{:?}", format);
+            let decoded = format.decode(2, 1, &data, &[]).unwrap();
+            assert_eq!(decoded[3], 0);
+            // 不透明的纯黑不能变成透明
+            assert_eq!(decoded[7], 255, "{:?}", format);
+            assert!(decoded[4..7].iter().all(|x| *x <= 8));
+        }
+    }
+
+    #[test]
+    fn palette_decode() {
+        let (width, height) = (5, 3);
+        let stride = PixelFormat::Palette8.stride(width);
+        let indices: Vec<u8> = (0..width * height).map(|x| (x * 17) as u8).collect();
+        let mut data = vec![0; stride * height];
+        for y in 0..height {
+            data[(height - y - 1) * stride..][..width].copy_from_slice(&indices[y * width..(y + 1) * width]);
+        }
+        let decoded = PixelFormat::Palette8.decode(width, height, &data, &PALETTE_RGBA).unwrap();
+        for (i, index) in indices.iter().enumerate() {
+            let c = *index as usize * 4;
+            assert_eq!(&decoded[i * 4..i * 4 + 4], &PALETTE_RGBA[c..c + 4]);
+        }
+        assert_eq!(&decoded[..4], &[0, 0, 0, 0]);
+        assert!(matches!(PixelFormat::Palette8.decode(width, height, &data, &PALETTE_RGBA[..1020]), Err(Error::TruncatedData { expected: 1024, .. })));
+    }
+}
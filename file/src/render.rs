@@ -0,0 +1,549 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use image_webp::{ColorType, WebPEncoder};
+use tracing::debug;
+use crate::asset::{FileDesc, FileDescType, ImageAsset};
+use crate::atlas::write_png;
+use crate::error::{Error, Result};
+use crate::map::{MapInfo, MapLayer, Tile};
+
+/// 地图格子的像素大小
+pub const CELL_WIDTH: u32 = 48;
+pub const CELL_HEIGHT: u32 = 32;
+/// 缩小时每次绘制的原图区域边长, 限制内存占用
+const CHUNK_SIZE: u32 = 1024;
+/// 图块最大边长, 与webp的最大边长一致
+pub const MAX_TILE_SIZE: u32 = 16383;
+
+/// 地图渲染参数, 输出按tile_size切分, 每块单独绘制, 不需要整张地图的内存
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// 输出图块边长, 地图边缘的图块可能更小, 不超过`MAX_TILE_SIZE`
+    pub tile_size: u32,
+    /// 缩放比例, 小于1时按区域平均缩小, 大于1时按最近像素放大
+    pub zoom: f64,
+    /// 绘制的层, 按Back, Middle, Objects的顺序绘制
+    pub layers: Vec<MapLayer>,
+    /// 高大物体或宽图片超出所在格子的最大像素, 超出部分在相邻图块中被截断
+    pub overhang: u32,
+    /// 地表, 小地表和物体图片库的文件编号, 与`ImageAsset`中注册的一致
+    pub back_file: u16,
+    pub middle_file: u16,
+    pub objects_file: u16,
+    pub file_type: FileDescType,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            tile_size: 2048,
+            zoom: 1.0,
+            layers: vec![MapLayer::Back, MapLayer::Middle, MapLayer::Objects],
+            overhang: 1024,
+            back_file: 1,
+            middle_file: 2,
+            objects_file: 3,
+            file_type: FileDescType::WZX,
+        }
+    }
+}
+
+/// 渲染结果中的一块, x和y为在整张输出图中的像素位置
+pub struct MapTile {
+    pub column: u32,
+    pub row: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl MapTile {
+    /// 完全透明
+    pub fn is_empty(&self) -> bool {
+        self.pixels.chunks_exact(4).all(|x| x[3] == 0)
+    }
+
+    /// `{column}_{row}.{扩展名}`
+    pub fn file_name(&self, format: TileFormat) -> String {
+        format!("{}_{}.{}", self.column, self.row, format.extension())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: TileFormat) -> Result<()> {
+        match format {
+            TileFormat::Png => write_png(path.as_ref(), self.width, self.height, &self.pixels),
+            TileFormat::Webp => {
+                let writer = BufWriter::new(File::create(path)?);
+                WebPEncoder::new(writer).encode(&self.pixels, self.width, self.height, ColorType::Rgba8)
+                    .map_err(|e| Error::Encode(e.to_string()))
+            }
+        }
+    }
+}
+
+/// 图块的保存格式, webp为无损压缩
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileFormat {
+    Png,
+    Webp,
+}
+
+impl TileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TileFormat::Png => "png",
+            TileFormat::Webp => "webp",
+        }
+    }
+}
+
+/// 按图块渲染整张地图
+pub struct MapRenderer<'a> {
+    asset: &'a ImageAsset,
+    width: u32,
+    height: u32,
+    tiles: &'a [Tile],
+    options: RenderOptions,
+}
+
+/// 原图坐标中的RGBA区域
+struct Canvas {
+    x: i64,
+    y: i64,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl<'a> MapRenderer<'a> {
+
+    pub fn new(asset: &'a ImageAsset, map: &'a MapInfo, options: RenderOptions) -> Result<Self> {
+        Self::from_tiles(asset, map.width, map.height, &map.tiles, options)
+    }
+
+    /// tiles按列存储, 与`MapInfo::tiles`一致
+    /// 图块边长为0或超过`MAX_TILE_SIZE`, 缩放后的大小超出u32时返回`Error::InvalidOptions`
+    pub fn from_tiles(asset: &'a ImageAsset, width: u32, height: u32, tiles: &'a [Tile], options: RenderOptions) -> Result<Self> {
+        let invalid = || Error::InvalidOptions(format!("tile size {}, zoom {}", options.tile_size, options.zoom));
+        if options.tile_size == 0 || options.tile_size > MAX_TILE_SIZE || !(options.zoom > 0.0 && options.zoom.is_finite()) {
+            return Err(invalid());
+        }
+        if tiles.len() < width as usize * height as usize {
+            return Err(Error::BadMapSize { width, height, length: tiles.len() });
+        }
+        let scaled = |x: u32, cell: u32| (x as f64 * cell as f64 * options.zoom).ceil();
+        if scaled(width, CELL_WIDTH) > u32::MAX as f64 || scaled(height, CELL_HEIGHT) > u32::MAX as f64 {
+            return Err(invalid());
+        }
+        Ok(Self { asset, width, height, tiles, options })
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// 缩放后的输出大小
+    pub fn size(&self) -> (u32, u32) {
+        let scale = |x: u32, cell: u32| (x as f64 * cell as f64 * self.options.zoom).ceil() as u32;
+        (scale(self.width, CELL_WIDTH), scale(self.height, CELL_HEIGHT))
+    }
+
+    /// 图块的列数和行数
+    pub fn grid(&self) -> (u32, u32) {
+        let (width, height) = self.size();
+        (width.div_ceil(self.options.tile_size), height.div_ceil(self.options.tile_size))
+    }
+
+    /// 按行依次渲染所有图块, 每块完成后交给f, f返回错误时停止
+    pub fn render_each<F: FnMut(MapTile) -> Result<()>>(&self, mut f: F) -> Result<()> {
+        let (columns, rows) = self.grid();
+        for row in 0..rows {
+            for column in 0..columns {
+                f(self.render_tile(column, row)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 保存为`{dir}/{column}_{row}.png`, 见`save_tiles`
+    pub fn save_png<P: AsRef<Path>>(&self, dir: P, skip_empty: bool) -> Result<usize> {
+        self.save_tiles(dir, TileFormat::Png, skip_empty)
+    }
+
+    /// 保存为`{dir}/{column}_{row}.{扩展名}`, skip_empty为true时不保存完全透明的图块, 返回保存的数量
+    pub fn save_tiles<P: AsRef<Path>>(&self, dir: P, format: TileFormat, skip_empty: bool) -> Result<usize> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut count = 0;
+        self.render_each(|tile| {
+            if skip_empty && tile.is_empty() {
+                return Ok(());
+            }
+            tile.save(dir.join(tile.file_name(format)), format)?;
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    }
+
+    pub fn render_tile(&self, column: u32, row: u32) -> Result<MapTile> {
+        let (width, height) = self.size();
+        let size = self.options.tile_size;
+        let (x, y) = (column * size, row * size);
+        if x >= width || y >= height {
+            let (columns, rows) = self.grid();
+            return Err(Error::BadIndex { index: row * columns + column, len: (columns * rows) as usize });
+        }
+        let (tile_width, tile_height) = (size.min(width - x), size.min(height - y));
+        let pixels = if self.options.zoom >= 1.0 {
+            self.render_nearest(x, y, tile_width, tile_height)
+        } else {
+            self.render_average(x, y, tile_width, tile_height)
+        };
+        debug!("render tile: {}x{}, {}x{}", column, row, tile_width, tile_height);
+        Ok(MapTile { column, row, x, y, width: tile_width, height: tile_height, pixels })
+    }
+
+    /// 放大或原尺寸, 每个输出像素取对应的原图像素
+    fn render_nearest(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+        let zoom = self.options.zoom;
+        let source = |x: u32| (x as f64 / zoom).floor() as i64;
+        let (left, top) = (source(x), source(y));
+        let canvas = self.render_region(left, top, (source(x + width - 1) - left + 1) as u32, (source(y + height - 1) - top + 1) as u32);
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        for j in 0..height {
+            let sy = (source(y + j) - top) as usize;
+            for i in 0..width {
+                let sx = (source(x + i) - left) as usize;
+                let src = (sy * canvas.width as usize + sx) * 4;
+                let dst = (j as usize * width as usize + i as usize) * 4;
+                pixels[dst..dst + 4].copy_from_slice(&canvas.pixels[src..src + 4]);
+            }
+        }
+        pixels
+    }
+
+    /// 缩小, 每个输出像素为对应原图区域的平均值
+    /// 按输出像素对齐分块绘制原图, 每块只对应自己的输出像素, 内存占用与图块大小无关
+    fn render_average(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+        let zoom = self.options.zoom;
+        let (map_width, map_height) = (self.width as i64 * CELL_WIDTH as i64, self.height as i64 * CELL_HEIGHT as i64);
+        let step = ((CHUNK_SIZE as f64 * zoom).floor() as u32).max(1);
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        for band_y in (0..height).step_by(step as usize) {
+            let band_height = step.min(height - band_y);
+            let (top, bottom) = (first_source(y + band_y, zoom), first_source(y + band_y + band_height, zoom).min(map_height));
+            for band_x in (0..width).step_by(step as usize) {
+                let band_width = step.min(width - band_x);
+                let (left, right) = (first_source(x + band_x, zoom), first_source(x + band_x + band_width, zoom).min(map_width));
+                if left >= right || top >= bottom { continue }
+                let canvas = self.render_region(left, top, (right - left) as u32, (bottom - top) as u32);
+
+                // 预乘alpha的颜色和, alpha和, 像素数量
+                let mut sums = vec![[0u64; 5]; band_width as usize * band_height as usize];
+                for j in 0..canvas.height as i64 {
+                    let oy = ((top + j) as f64 * zoom).floor() as i64 - (y + band_y) as i64;
+                    if oy < 0 || oy >= band_height as i64 { continue }
+                    for i in 0..canvas.width as i64 {
+                        let ox = ((left + i) as f64 * zoom).floor() as i64 - (x + band_x) as i64;
+                        if ox < 0 || ox >= band_width as i64 { continue }
+                        let src = ((j * canvas.width as i64 + i) * 4) as usize;
+                        let p = &canvas.pixels[src..src + 4];
+                        let a = p[3] as u64;
+                        let sum = &mut sums[(oy * band_width as i64 + ox) as usize];
+                        sum[0] += p[0] as u64 * a;
+                        sum[1] += p[1] as u64 * a;
+                        sum[2] += p[2] as u64 * a;
+                        sum[3] += a;
+                        sum[4] += 1;
+                    }
+                }
+                for (k, sum) in sums.iter().enumerate().filter(|(_, x)| x[3] > 0) {
+                    let (i, j) = (k as u32 % band_width + band_x, k as u32 / band_width + band_y);
+                    let p = &mut pixels[(j as usize * width as usize + i as usize) * 4..][..4];
+                    p[0] = (sum[0] / sum[3]) as u8;
+                    p[1] = (sum[1] / sum[3]) as u8;
+                    p[2] = (sum[2] / sum[3]) as u8;
+                    p[3] = (sum[3] / sum[4]) as u8;
+                }
+            }
+        }
+        pixels
+    }
+
+    /// 绘制原图坐标中的区域, 先绘制所有地表, 再绘制小地表, 最后按行绘制物体
+    fn render_region(&self, x: i64, y: i64, width: u32, height: u32) -> Canvas {
+        let mut canvas = Canvas { x, y, width, height, pixels: vec![0u8; width as usize * height as usize * 4] };
+        let overhang = self.options.overhang as i64;
+        let (cell_width, cell_height) = (CELL_WIDTH as i64, CELL_HEIGHT as i64);
+        // 地表从所在格子向下绘制, 物体从下一行向上绘制
+        let first_column = ((x - overhang) / cell_width).max(0);
+        let last_column = ((x + width as i64) / cell_width).min(self.width as i64 - 1);
+        let first_row = ((y - 2 * cell_height) / cell_height).max(0);
+        let last_row = ((y + height as i64 + overhang) / cell_height).min(self.height as i64 - 1);
+
+        for layer in [MapLayer::Back, MapLayer::Middle, MapLayer::Objects] {
+            if !self.options.layers.contains(&layer) { continue }
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    let tile = &self.tiles[(column * self.height as i64 + row) as usize];
+                    self.draw_tile(&mut canvas, layer, column, row, tile);
+                }
+            }
+        }
+        canvas
+    }
+
+    fn draw_tile(&self, canvas: &mut Canvas, layer: MapLayer, column: i64, row: i64, tile: &Tile) {
+        let options = &self.options;
+        // 图片底部所在的行, 序号为0时使用不带序号的文件, 否则使用序号+1的文件
+        let (file, number, index, bottom) = match layer {
+            MapLayer::Back => {
+                // 地表为2x2格子, 只在偶数格子绘制
+                if tile.back & 0x7FFF == 0 || column & 1 == 1 || row & 1 == 1 { return }
+                let number = if tile.tile_idx != 0 && tile.tile_idx < 22 { tile.tile_idx + 1 } else { 0 };
+                (options.back_file, number, (tile.back & 0x7FFF) - 1, row + 2)
+            }
+            MapLayer::Middle => {
+                if tile.middle & 0x7FFF == 0 { return }
+                let number = if tile.middle_idx != 0 && tile.middle_idx < 36 { tile.middle_idx + 1 } else { 0 };
+                (options.middle_file, number, (tile.middle & 0x7FFF) - 1, row + 1)
+            }
+            MapLayer::Objects => {
+                // 动画物体不绘制
+                if tile.objects & 0x7FFF == 0 || tile.frame != 0 { return }
+                let number = if tile.file_idx > 0 && tile.file_idx < 51 { tile.file_idx + 1 } else { 0 };
+                (options.objects_file, number, (tile.objects & 0x7FFF) - 1, row + 1)
+            }
+        };
+        let desc = FileDesc::ZONE { file, number: number as u16, index: index as u32 };
+        let Ok(image) = self.asset.load_image(desc, options.file_type) else { return };
+        let top = bottom * CELL_HEIGHT as i64 - image.height as i64;
+        canvas.blend(column * CELL_WIDTH as i64, top, image.width as u32, image.height as u32, &image.bytes);
+    }
+}
+
+/// 缩小到output像素及之后的第一个原图像素
+fn first_source(output: u32, zoom: f64) -> i64 {
+    let mut source = (output as f64 / zoom).ceil() as i64;
+    while source > 0 && ((source - 1) as f64 * zoom).floor() as i64 >= output as i64 {
+        source -= 1;
+    }
+    while ((source as f64) * zoom).floor() < output as f64 {
+        source += 1;
+    }
+    source
+}
+
+impl Canvas {
+    /// 按alpha混合到(x, y), 超出区域的部分忽略
+    fn blend(&mut self, x: i64, y: i64, width: u32, height: u32, src: &[u8]) {
+        let (left, top) = ((self.x - x).max(0), (self.y - y).max(0));
+        let right = (self.x + self.width as i64 - x).min(width as i64);
+        let bottom = (self.y + self.height as i64 - y).min(height as i64);
+        if left >= right || top >= bottom || src.len() < width as usize * height as usize * 4 {
+            return;
+        }
+        for j in top..bottom {
+            let dst_row = ((y + j - self.y) * self.width as i64 + x - self.x) * 4;
+            let src_row = j * width as i64 * 4;
+            for i in left..right {
+                let s = &src[(src_row + i * 4) as usize..(src_row + i * 4 + 4) as usize];
+                let a = s[3] as u32;
+                if a == 0 { continue }
+                let d = &mut self.pixels[(dst_row + i * 4) as usize..(dst_row + i * 4 + 4) as usize];
+                if a == 255 {
+                    d.copy_from_slice(s);
+                    continue;
+                }
+                let da = d[3] as u32 * (255 - a) / 255;
+                let out = a + da;
+                for c in 0..3 {
+                    d[c] = ((s[c] as u32 * a + d[c] as u32 * da) / out) as u8;
+                }
+                d[3] = out as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use bytes::Bytes;
+    use crate::library::{ImageData, MemoryLibrary};
+    use super::*;
+
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn image(width: u16, height: u16, pixel: impl Fn(usize, usize) -> [u8; 4]) -> Option<ImageData> {
+        let mut bytes = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                bytes.extend_from_slice(&pixel(x, y));
+            }
+        }
+        Some(ImageData { width, height, offset_x: 0, offset_y: 0, bytes: Bytes::from(bytes) })
+    }
+
+    /// 4x4格子的地图: 两块蓝色地表, 一块偶数列为红色的小地表, 一个半透明的绿色高物体和一个不绘制的动画物体
+    fn setup() -> (ImageAsset, Vec<Tile>) {
+        let asset = ImageAsset::new(String::new());
+        let libraries = [
+            image(96, 64, |_, _| BLUE),
+            image(48, 32, |x, _| if x % 2 == 0 { RED } else { [0; 4] }),
+            image(48, 96, |_, _| [0, 255, 0, 128]),
+        ];
+        for (file, image) in libraries.into_iter().enumerate() {
+            asset.put_library(file as u32 + 1, FileDescType::WZX, Arc::new(MemoryLibrary::from(vec![image])));
+        }
+        let mut tiles = vec![Tile::default(); 16];
+        tiles[0].back = 1;
+        tiles[2 * 4 + 2].back = 0x8001;
+        tiles[4 + 3].middle = 1;
+        tiles[1].objects = 1;
+        tiles[3 * 4 + 3].objects = 1;
+        tiles[3 * 4 + 3].frame = 1;
+        (asset, tiles)
+    }
+
+    fn options(tile_size: u32, zoom: f64) -> RenderOptions {
+        RenderOptions { tile_size, zoom, ..Default::default() }
+    }
+
+    fn pixel(tile: &MapTile, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y - tile.y) * tile.width + x - tile.x) as usize * 4;
+        tile.pixels[i..i + 4].try_into().unwrap()
+    }
+
+    /// 按图块渲染后拼成整张图
+    fn render_all(renderer: &MapRenderer) -> Vec<u8> {
+        let (width, height) = renderer.size();
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        renderer.render_each(|tile| {
+            for j in 0..tile.height {
+                let dst = (((tile.y + j) * width + tile.x) * 4) as usize;
+                let src = (j * tile.width * 4) as usize;
+                pixels[dst..dst + tile.width as usize * 4].copy_from_slice(&tile.pixels[src..src + tile.width as usize * 4]);
+            }
+            Ok(())
+        }).unwrap();
+        pixels
+    }
+
+    #[test]
+    fn first_source_bounds() {
+        assert_eq!(first_source(0, 0.5), 0);
+        assert_eq!(first_source(1, 0.5), 2);
+        assert_eq!(first_source(7, 1.0), 7);
+        for zoom in [0.1, 0.3, 0.5, 0.75, 0.999] {
+            for output in 0..200 {
+                let source = first_source(output, zoom);
+                assert!((source as f64 * zoom).floor() as u32 >= output, "{} {}", zoom, output);
+                assert!(source == 0 || (((source - 1) as f64 * zoom).floor() as u32) < output, "{} {}", zoom, output);
+            }
+        }
+    }
+
+    #[test]
+    fn size_and_grid() {
+        let (asset, tiles) = setup();
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(100, 1.0)).unwrap();
+        assert_eq!((renderer.size(), renderer.grid()), ((192, 128), (2, 2)));
+        let tile = renderer.render_tile(1, 1).unwrap();
+        assert_eq!((tile.x, tile.y, tile.width, tile.height), (100, 100, 92, 28));
+        assert_eq!(tile.pixels.len(), 92 * 28 * 4);
+        assert!(matches!(renderer.render_tile(2, 0), Err(Error::BadIndex { index: 2, len: 4 })));
+
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(25, 0.3)).unwrap();
+        assert_eq!((renderer.size(), renderer.grid()), ((58, 39), (3, 2)));
+        assert_eq!(renderer.render_tile(2, 1).unwrap().width, 8);
+
+        assert!(matches!(MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(0, 1.0)), Err(Error::InvalidOptions(_))));
+        assert!(matches!(MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(MAX_TILE_SIZE + 1, 1.0)), Err(Error::InvalidOptions(_))));
+        assert!(matches!(MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(64, 1e8)), Err(Error::InvalidOptions(_))));
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(MAX_TILE_SIZE, 100.0)).unwrap();
+        assert_eq!((renderer.size(), renderer.grid()), ((19200, 12800), (2, 1)));
+        assert!(matches!(MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(10, f64::NAN)), Err(Error::InvalidOptions(_))));
+        assert!(matches!(MapRenderer::from_tiles(&asset, 4, 5, &tiles, options(10, 1.0)), Err(Error::BadMapSize { .. })));
+    }
+
+    #[test]
+    fn layers_and_blending() {
+        let (asset, tiles) = setup();
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(1024, 1.0)).unwrap();
+        let tile = renderer.render_tile(0, 0).unwrap();
+        // 半透明物体混合到地表上
+        assert_eq!(pixel(&tile, 10, 10), [0, 128, 127, 255]);
+        assert_eq!(pixel(&tile, 60, 10), BLUE);
+        assert_eq!(pixel(&tile, 150, 10), [0; 4]);
+        assert_eq!(pixel(&tile, 60, 100), RED);
+        assert_eq!(pixel(&tile, 61, 100), [0; 4]);
+        // 动画物体不绘制, 地表序号的最高位忽略
+        assert_eq!(pixel(&tile, 150, 110), BLUE);
+        assert_eq!(renderer.render_tile(0, 0).unwrap().pixels, tile.pixels);
+
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, RenderOptions { layers: vec![MapLayer::Middle], ..options(1024, 1.0) }).unwrap();
+        let tile = renderer.render_tile(0, 0).unwrap();
+        assert_eq!((pixel(&tile, 10, 10), pixel(&tile, 60, 100)), ([0; 4], RED));
+    }
+
+    #[test]
+    fn zoom_modes() {
+        let (asset, tiles) = setup();
+        // 放大时取最近的像素
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(1024, 2.0)).unwrap();
+        let tile = renderer.render_tile(0, 0).unwrap();
+        assert_eq!((pixel(&tile, 120, 200), pixel(&tile, 121, 201), pixel(&tile, 122, 200)), (RED, RED, [0; 4]));
+
+        // 缩小时按预乘alpha取平均
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(1024, 0.5)).unwrap();
+        let tile = renderer.render_tile(0, 0).unwrap();
+        assert_eq!(pixel(&tile, 30, 50), [255, 0, 0, 127]);
+        assert_eq!(pixel(&tile, 5, 5), [0, 128, 127, 255]);
+        assert_eq!(pixel(&tile, 90, 5), [0; 4]);
+    }
+
+    #[test]
+    fn tiles_match_single_render() {
+        let (asset, tiles) = setup();
+        for zoom in [1.0, 2.0, 0.5, 0.3] {
+            let whole = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(4096, zoom)).unwrap();
+            let split = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(37, zoom)).unwrap();
+            assert_eq!(whole.grid(), (1, 1));
+            assert_eq!(render_all(&split), whole.render_tile(0, 0).unwrap().pixels, "zoom {}", zoom);
+        }
+    }
+
+    #[test]
+    fn save_tiles() {
+        let (asset, tiles) = setup();
+        let renderer = MapRenderer::from_tiles(&asset, 4, 4, &tiles, options(64, 1.0)).unwrap();
+        assert_eq!(renderer.grid(), (3, 2));
+        let mut count = 0;
+        renderer.render_each(|_| { count += 1; Ok(()) }).unwrap();
+        assert_eq!(count, 6);
+        assert!(renderer.render_tile(2, 0).unwrap().is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(renderer.save_png(dir.path(), true).unwrap(), 5);
+        assert!(!dir.path().join("2_0.png").exists());
+        assert!(dir.path().join("1_1.png").exists());
+        assert_eq!(renderer.save_png(dir.path(), false).unwrap(), 6);
+        assert!(dir.path().join("2_0.png").exists());
+
+        // webp为无损压缩
+        assert_eq!(renderer.save_tiles(dir.path(), TileFormat::Webp, true).unwrap(), 5);
+        let tile = renderer.render_tile(1, 1).unwrap();
+        assert_eq!(tile.file_name(TileFormat::Webp), "1_1.webp");
+        let file = std::io::BufReader::new(File::open(dir.path().join("1_1.webp")).unwrap());
+        let mut decoder = image_webp::WebPDecoder::new(file).unwrap();
+        assert_eq!(decoder.dimensions(), (tile.width, tile.height));
+        let mut pixels = vec![0u8; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut pixels).unwrap();
+        assert_eq!(pixels, tile.pixels);
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use bytes::Buf;
+use moka::sync::Cache;
+use tracing::debug;
+use crate::asset::FileDescType;
+use crate::error::{Error, Result};
+use crate::vfs::Vfs;
+
+const SOUND_DIR: &str = "wav";
+const SOUND_LIST_FILE: &str = "sound.lst";
+const SOUND_FILE_SUFFIX: &str = "wav";
+
+/// 解码后的声音, 样本统一为16位, 多声道交错存储
+#[derive(Clone)]
+pub struct SoundData {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Arc<[i16]>,
+}
+
+impl SoundData {
+    /// 解析PCM格式的wav, 支持8位和16位样本
+    pub fn from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 12 {
+            return Err(Error::TruncatedHeader { expected: 12, actual: bytes.len() });
+        }
+        if &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(Error::BadSoundHeader);
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut chunks = &bytes[12..];
+        while chunks.len() >= 8 {
+            let id = &chunks[..4];
+            let size = (&chunks[4..8]).get_u32_le() as usize;
+            chunks = &chunks[8..];
+            // 部分文件的data长度大于实际长度, 按剩余长度截断
+            let body = &chunks[..size.min(chunks.len())];
+            match id {
+                b"fmt " => format = Some(body),
+                b"data" => data = Some(body),
+                _ => {}
+            }
+            // 块按2字节对齐
+            chunks = &chunks[(size + (size & 1)).min(chunks.len())..];
+        }
+
+        let mut format = format.ok_or(Error::BadSoundHeader)?;
+        if format.len() < 16 {
+            return Err(Error::TruncatedHeader { expected: 16, actual: format.len() });
+        }
+        let tag = format.get_u16_le();
+        let channels = format.get_u16_le();
+        let sample_rate = format.get_u32_le();
+        format.advance(6);
+        let bits = format.get_u16_le();
+        if tag != 1 {
+            return Err(Error::UnsupportedSoundFormat(tag));
+        }
+        if channels == 0 {
+            return Err(Error::BadSoundHeader);
+        }
+
+        let data = data.ok_or(Error::BadSoundHeader)?;
+        let samples: Vec<i16> = match bits {
+            8 => data.iter().map(|x| ((*x as i16) - 128) << 8).collect(),
+            16 => data.chunks_exact(2).map(|x| i16::from_le_bytes([x[0], x[1]])).collect(),
+            _ => return Err(Error::UnsupportedSampleBits(bits)),
+        };
+        Ok(Self { channels, sample_rate, samples: Arc::from(samples) })
+    }
+
+    /// 时长(毫秒)
+    pub fn duration_ms(&self) -> u64 {
+        if self.sample_rate == 0 { return 0 }
+        self.samples.len() as u64 / self.channels as u64 * 1000 / self.sample_rate as u64
+    }
+}
+
+/// 声音资源, 按客户端wav/sound.lst中的编号查找文件
+/// 列表中没有的编号使用wav/{编号}.wav
+pub struct SoundAsset {
+    vfs: Vfs,
+    sound_map: HashMap<u32, String>,
+    /// 与ImageAsset相同, IO错误以外的结果都会缓存
+    sound_cache: Cache<u64, Result<SoundData>>
+}
+
+impl SoundAsset {
+
+    pub fn new(dir: String) -> Self {
+        Self::with_vfs(Vfs::new(dir))
+    }
+
+    /// 从分层目录中查找声音文件, 补丁目录中的文件优先
+    pub fn with_vfs(vfs: Vfs) -> Self {
+        SoundAsset {
+            vfs,
+            sound_map: HashMap::with_capacity(1024),
+            sound_cache: Cache::new(1_000)
+        }
+    }
+
+    /// 读取声音列表, 每行格式为"编号 : 文件路径", 返回读取的条目数
+    pub fn load_sound_list(&mut self) -> Result<usize> {
+        let bytes = self.vfs.read(Path::new(SOUND_DIR).join(SOUND_LIST_FILE))?;
+        let text = String::from_utf8_lossy(&bytes);
+        let mut count = 0;
+        for line in text.lines() {
+            let Some((id, name)) = line.split_once(':') else { continue };
+            let (Ok(id), name) = (id.trim().parse::<u32>(), name.trim()) else { continue };
+            if name.is_empty() { continue }
+            self.sound_map.insert(id, name.to_string());
+            count += 1;
+        }
+        debug!("sound list: {} entries", count);
+        Ok(count)
+    }
+
+    /// name为相对客户端目录的路径, 如wav\Logon.wav
+    pub fn put_sound_map(&mut self, id: u32, name: &str) {
+        self.sound_map.insert(id, name.to_string());
+    }
+
+    /// 相对资源目录的路径
+    fn get_sound_path(&self, id: u32) -> PathBuf {
+        match self.sound_map.get(&id) {
+            Some(name) => name.split(['\\', '/']).filter(|x| !x.is_empty()).collect(),
+            None => Path::new(SOUND_DIR).join(id.to_string()).with_extension(SOUND_FILE_SUFFIX),
+        }
+    }
+
+    pub fn load_sound(&self, id: u32) -> Result<SoundData> {
+        let key = id as u64 | FileDescType::WAV.get_value();
+        if let Some(value) = self.sound_cache.get(&key) {
+            return value;
+        }
+        let value = self.vfs.read(self.get_sound_path(id)).and_then(|x| SoundData::from(&x[..]));
+        if !matches!(value, Err(Error::Io(_))) {
+            self.sound_cache.insert(key, value.clone());
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BufMut;
+    use super::*;
+
+    fn wave(tag: u16, channels: u16, bits: u16, data: &[u8]) -> Vec<u8> {
+        wave_at(tag, channels, 8000, bits, data)
+    }
+
+    fn wave_at(tag: u16, channels: u16, rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.put_slice(b"RIFF");
+        bytes.put_u32_le(36 + data.len() as u32);
+        bytes.put_slice(b"WAVEfmt ");
+        bytes.put_u32_le(16);
+        bytes.put_u16_le(tag);
+        bytes.put_u16_le(channels);
+        bytes.put_u32_le(rate);
+        bytes.put_u32_le(rate * channels as u32 * bits as u32 / 8);
+        bytes.put_u16_le(channels * bits / 8);
+        bytes.put_u16_le(bits);
+        bytes.put_slice(b"data");
+        bytes.put_u32_le(data.len() as u32);
+        bytes.put_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        let sound = SoundData::from(&wave(1, 1, 8, &[0, 128, 255])).unwrap();
+        assert_eq!(&sound.samples[..], &[-32768, 0, 127 << 8]);
+        assert!(matches!(SoundData::from(&wave(1, 2, 24, &[0; 6])), Err(Error::UnsupportedSampleBits(24))));
+        assert!(matches!(SoundData::from(&wave(3, 2, 32, &[0; 8])), Err(Error::UnsupportedSoundFormat(3))));
+        assert!(matches!(SoundData::from(&wave(1, 0, 16, &[0; 2])), Err(Error::BadSoundHeader)));
+    }
+
+    #[test]
+    fn decode_pcm() {
+        let sound = SoundData::from(&wave_at(1, 2, 22050, 8, &[128; 4410])).unwrap();
+        assert_eq!((sound.channels, sound.sample_rate, sound.samples.len()), (2, 22050, 4410));
+        assert!(sound.samples.iter().all(|x| *x == 0));
+        assert_eq!(sound.duration_ms(), 100);
+
+        let data: Vec<u8> = [0i16, -1, 32767, -32768, 300].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let sound = SoundData::from(&wave_at(1, 1, 44100, 16, &data)).unwrap();
+        assert_eq!((sound.channels, sound.sample_rate), (1, 44100));
+        assert_eq!(&sound.samples[..], &[0, -1, 32767, -32768, 300]);
+
+        // data长度大于实际长度时截断, 16位时忽略多出的1字节
+        let mut bytes = wave_at(1, 1, 11025, 16, &data[..5]);
+        bytes[40..44].copy_from_slice(&100u32.to_le_bytes());
+        assert_eq!(&SoundData::from(&bytes).unwrap().samples[..], &[0, -1]);
+        assert!(matches!(SoundData::from(b"RIFF\0\0\0\0WAVE"), Err(Error::BadSoundHeader)));
+        assert!(matches!(SoundData::from(b"RIFF"), Err(Error::TruncatedHeader { .. })));
+    }
+
+    #[test]
+    fn sound_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("wav");
+        std::fs::create_dir_all(&wav).unwrap();
+        let list = "; 声音列表\r\n[sound]\r\n\r\n1 : wav\\logon.wav\r\n  2:wav/walk.wav  \nabc : wav/bad.wav\n3 :\nno separator\n-4 : wav/negative.wav\n; 5 : wav/comment.wav\n";
+        std::fs::write(wav.join("sound.lst"), list).unwrap();
+        std::fs::write(wav.join("Logon.wav"), wave(1, 1, 8, &[128, 255])).unwrap();
+        std::fs::write(wav.join("7.wav"), wave_at(1, 2, 22050, 16, &[0; 8])).unwrap();
+        std::fs::write(wav.join("walk.wav"), b"not a wave file").unwrap();
+
+        let mut asset = SoundAsset::new(dir.path().to_str().unwrap().to_string());
+        assert_eq!(asset.load_sound_list().unwrap(), 2);
+        // 列表中的文件名不区分大小写
+        let sound = asset.load_sound(1).unwrap();
+        assert_eq!((sound.channels, sound.sample_rate, &sound.samples[..]), (1, 8000, &[0, 127 << 8][..]));
+        assert!(matches!(asset.load_sound(2), Err(Error::BadSoundHeader)));
+        // 列表中没有的编号使用wav/{编号}.wav
+        let sound = asset.load_sound(7).unwrap();
+        assert_eq!((sound.channels, sound.sample_rate, sound.samples.len()), (2, 22050, 4));
+        assert!(matches!(asset.load_sound(3), Err(Error::Io(_))));
+        assert!(matches!(asset.load_sound(5), Err(Error::Io(_))));
+
+        // IO错误不缓存
+        std::fs::write(wav.join("3.wav"), wave(1, 1, 16, &[1, 0])).unwrap();
+        assert_eq!(&asset.load_sound(3).unwrap().samples[..], &[1]);
+        asset.put_sound_map(8, "WAV\\walk.wav");
+        assert!(matches!(asset.load_sound(8), Err(Error::BadSoundHeader)));
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use bytes::Bytes;
+use tracing::debug;
+use crate::error::Result;
+use crate::pack::Pack;
+
+/// 分层的资源目录, 补丁目录或资源包覆盖基础目录
+/// 查找时从最上层开始, 文件名不区分大小写(Map/map, smTiles/SmTiles)
+pub struct Vfs {
+    /// 按查找顺序排列, 0为最上层
+    layers: Vec<VfsLayer>,
+    /// 大小写不一致时读取的目录列表
+    listings: Mutex<HashMap<PathBuf, Arc<[OsString]>>>,
+}
+
+/// 一层资源, 目录或资源包
+pub enum VfsLayer {
+    Dir(PathBuf),
+    Pack(Arc<Pack>),
+}
+
+/// 找到的文件及其所在层, layer为`Vfs::layers`中的序号
+/// 目录中的文件path为实际路径, 资源包中的文件为包内名称
+#[derive(Debug, Clone, PartialEq)]
+pub struct VfsEntry {
+    pub layer: usize,
+    pub path: PathBuf,
+}
+
+impl Vfs {
+
+    pub fn new<P: AsRef<Path>>(base: P) -> Self {
+        Self { layers: vec![VfsLayer::Dir(base.as_ref().to_path_buf())], listings: Mutex::new(HashMap::new()) }
+    }
+
+    /// 只使用资源包, 不读取目录
+    pub fn from_pack(pack: Pack) -> Self {
+        Self { layers: vec![VfsLayer::Pack(Arc::new(pack))], listings: Mutex::new(HashMap::new()) }
+    }
+
+    /// 在最上层增加补丁目录, 后加入的目录优先
+    pub fn with_layer<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.push_layer(dir);
+        self
+    }
+
+    pub fn push_layer<P: AsRef<Path>>(&mut self, dir: P) {
+        self.layers.insert(0, VfsLayer::Dir(dir.as_ref().to_path_buf()));
+    }
+
+    /// 在最上层增加资源包
+    pub fn with_pack(mut self, pack: Pack) -> Self {
+        self.push_pack(pack);
+        self
+    }
+
+    pub fn push_pack(&mut self, pack: Pack) {
+        self.layers.insert(0, VfsLayer::Pack(Arc::new(pack)));
+    }
+
+    /// 按查找顺序返回所有层, 最后一项为基础目录
+    pub fn layers(&self) -> &[VfsLayer] {
+        &self.layers
+    }
+
+    /// 查找文件, 返回所在层和路径
+    pub fn locate<P: AsRef<Path>>(&self, name: P) -> Option<VfsEntry> {
+        let name = name.as_ref();
+        self.layers.iter().enumerate().find_map(|(layer, source)| {
+            let path = match source {
+                VfsLayer::Dir(_) => self.resolve_in(layer, name),
+                VfsLayer::Pack(pack) => pack.entry(name).map(|x| PathBuf::from(&x.name)),
+            };
+            path.map(|path| VfsEntry { layer, path })
+        })
+    }
+
+    /// 返回最上层匹配的实际路径, 最上层匹配在资源包中时返回None
+    pub fn resolve<P: AsRef<Path>>(&self, name: P) -> Option<PathBuf> {
+        self.locate(name).filter(|x| matches!(self.layers[x.layer], VfsLayer::Dir(_))).map(|x| x.path)
+    }
+
+    /// 只在指定层中查找, 用于同一图片库的索引和数据文件必须来自同一层
+    /// 只使用name中的普通路径部分, 不会访问层目录之外的文件, 资源包层返回None
+    pub fn resolve_in<P: AsRef<Path>>(&self, layer: usize, name: P) -> Option<PathBuf> {
+        let VfsLayer::Dir(dir) = self.layers.get(layer)? else { return None };
+        let mut path = dir.clone();
+        for component in name.as_ref().components() {
+            let Component::Normal(part) = component else { continue };
+            let exact = path.join(part);
+            path = if exact.exists() { exact } else {
+                let part = part.to_string_lossy();
+                let listing = self.listing(&path)?;
+                let found = listing.iter().find(|x| x.to_string_lossy().eq_ignore_ascii_case(&part))?;
+                path.join(found)
+            };
+        }
+        Some(path)
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, name: P) -> bool {
+        self.locate(name).is_some()
+    }
+
+    /// 读取最上层匹配的文件, 不存在时返回NotFound
+    pub fn read<P: AsRef<Path>>(&self, name: P) -> Result<Bytes> {
+        let name = name.as_ref();
+        let entry = self.locate(name).ok_or_else(|| not_found(name))?;
+        match &self.layers[entry.layer] {
+            VfsLayer::Dir(_) => Ok(Bytes::from(fs::read(entry.path)?)),
+            VfsLayer::Pack(pack) => pack.read(entry.path),
+        }
+    }
+
+    /// 清除目录列表缓存, 目录内容变化后调用
+    pub fn refresh(&self) {
+        self.listings.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    fn listing(&self, dir: &Path) -> Option<Arc<[OsString]>> {
+        let mut listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(x) = listings.get(dir) {
+            return Some(x.clone());
+        }
+        let entries: Arc<[OsString]> = fs::read_dir(dir).ok()?.filter_map(|x| x.ok().map(|x| x.file_name())).collect();
+        debug!("list: {:?}, {} entries", dir, entries.len());
+        listings.insert(dir.to_path_buf(), entries.clone());
+        Some(entries)
+    }
+}
+
+pub(crate) fn not_found(name: &Path) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, format!("{} not found", name.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pack::PackBuilder;
+    use super::*;
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn patch_overrides_base() {
+        let (base, patch) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        write(base.path(), "data/tiles.wzl", b"base");
+        write(base.path(), "data/objects.wzl", b"base");
+        write(patch.path(), "data/tiles.wzl", b"patch");
+        let vfs = Vfs::new(base.path()).with_layer(patch.path());
+        assert_eq!(vfs.layers().len(), 2);
+        assert_eq!(&vfs.read("data/tiles.wzl").unwrap()[..], b"patch");
+        assert_eq!(&vfs.read("data/objects.wzl").unwrap()[..], b"base");
+        assert_eq!(vfs.locate("data/tiles.wzl"), Some(VfsEntry { layer: 0, path: patch.path().join("data/tiles.wzl") }));
+        assert_eq!(vfs.locate("data/objects.wzl").unwrap().layer, 1);
+        assert_eq!(vfs.resolve_in(1, "data/tiles.wzl"), Some(base.path().join("data/tiles.wzl")));
+        assert!(vfs.locate("data/items.wzl").is_none());
+        assert!(matches!(vfs.read("data/items.wzl"), Err(crate::error::Error::Io(_))));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let base = tempfile::tempdir().unwrap();
+        write(base.path(), "map/0.map", b"map");
+        write(base.path(), "data/smtiles.wzl", b"wzl");
+        let vfs = Vfs::new(base.path());
+        assert_eq!(vfs.resolve("Map/0.map"), Some(base.path().join("map/0.map")));
+        assert_eq!(vfs.resolve("DATA/smTiles.wzl"), Some(base.path().join("data/smtiles.wzl")));
+        assert_eq!(&vfs.read("MAP/0.MAP").unwrap()[..], b"map");
+        assert!(vfs.exists("Data/SmTiles.WZL"));
+        assert!(!vfs.exists("Map/1.map"));
+    }
+
+    #[test]
+    fn pack_layer() {
+        let (base, out) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        write(base.path(), "data/tiles.wzl", b"base");
+        write(base.path(), "data/objects.wzl", b"base");
+        let path = out.path().join("patch.icmir");
+        let mut builder = PackBuilder::new();
+        builder.add_bytes("Data/Tiles.wzl", b"pack".to_vec());
+        builder.write(&path).unwrap();
+
+        let vfs = Vfs::new(base.path()).with_pack(Pack::open(&path).unwrap());
+        assert!(matches!(vfs.layers()[0], VfsLayer::Pack(_)));
+        assert_eq!(vfs.locate("data/tiles.WZL"), Some(VfsEntry { layer: 0, path: PathBuf::from("Data/Tiles.wzl") }));
+        assert_eq!(&vfs.read("data/tiles.wzl").unwrap()[..], b"pack");
+        // 最上层匹配在资源包中
+        assert_eq!(vfs.resolve("data/tiles.wzl"), None);
+        assert_eq!(vfs.resolve_in(0, "data/tiles.wzl"), None);
+        assert_eq!(vfs.locate("data/objects.wzl").unwrap().layer, 1);
+        assert_eq!(vfs.resolve("data/objects.wzl"), Some(base.path().join("data/objects.wzl")));
+    }
+
+    #[test]
+    fn resolve_in_ignores_parent() {
+        let root = tempfile::tempdir().unwrap();
+        let base = root.path().join("base");
+        write(root.path(), "secret.txt", b"secret");
+        write(&base, "data/a.wzl", b"a");
+        let vfs = Vfs::new(&base);
+        assert_eq!(vfs.resolve_in(0, "../secret.txt"), None);
+        assert_eq!(vfs.resolve_in(0, "../data/a.wzl"), Some(base.join("data/a.wzl")));
+        assert_eq!(vfs.resolve_in(0, "data/../a.wzl"), Some(base.join("data/a.wzl")));
+        assert_eq!(vfs.resolve_in(0, "/data/a.wzl"), Some(base.join("data/a.wzl")));
+        assert_eq!(vfs.resolve_in(1, "data/a.wzl"), None);
+    }
+
+    #[test]
+    fn refresh_listing() {
+        let base = tempfile::tempdir().unwrap();
+        write(base.path(), "Map/0.map", b"0");
+        let vfs = Vfs::new(base.path());
+        assert!(vfs.exists("map/0.MAP"));
+        // Map目录的列表已缓存, 新文件在刷新后才能按不同大小写找到
+        write(base.path(), "Map/1.map", b"1");
+        assert!(!vfs.exists("map/1.MAP"));
+        assert!(vfs.exists("map/1.map"));
+        vfs.refresh();
+        assert_eq!(&vfs.read("MAP/1.MAP").unwrap()[..], b"1");
+    }
+}
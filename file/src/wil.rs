@@ -0,0 +1,216 @@
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use bytes::{Buf, Bytes};
+use crate::library::{check_remaining, read_buffer, ImageData, ImageHeader, ImageLibrary, LibrarySource, SourceReader};
+use crate::pixel::PALETTE_RGBA;
+use crate::error::{Error, Result};
+use crate::pixel::PixelFormat;
+
+const WIL_SUFFIX: &str = "wil";
+const WIX_SUFFIX: &str = "wix";
+
+/// wil文件头
+/// 00-43: 描述, 44: 图片数量, 48: 颜色数(256: 8位, 65536: 16位), 52: 调色板字节数
+/// 新版本在56增加4字节版本号, 调色板随后, 每张图片头也多出4字节
+const WIL_HEADER_SIZE: usize = 56;
+/// wix文件头, 旧版本48字节, 新版本52字节, 之后为每张图片在wil中的位置
+const WIX_HEADER_SIZE: usize = 48;
+
+/// 原版wil/wix图片库, 图片头之后为未压缩的像素, 行按4字节对齐, 自下而上
+pub struct WilLibrary {
+    source: LibrarySource,
+    /// 资源包中的图片库没有索引文件
+    index: Option<PathBuf>,
+    offsets: Vec<u32>,
+    format: PixelFormat,
+    palette: Vec<u8>,
+    image_header_size: usize,
+}
+
+impl WilLibrary {
+
+    /// path不含扩展名, 同时读取wix索引和wil文件头
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::open_files(path.with_extension(WIL_SUFFIX), path.with_extension(WIX_SUFFIX))
+    }
+
+    /// 分别指定wil和wix文件, 用于文件名大小写不一致的情况
+    pub fn open_files<P: AsRef<Path>, I: AsRef<Path>>(wil: P, wix: I) -> Result<Self> {
+        let index = wix.as_ref().to_path_buf();
+        let wix = std::fs::read(&index)?;
+        let mut library = Self::from_source(LibrarySource::File(wil.as_ref().to_path_buf()), &wix)?;
+        library.index = Some(index);
+        Ok(library)
+    }
+
+    /// 使用已读取的wil和wix内容, 用于资源包中的图片库
+    pub fn from_bytes(wil: Bytes, wix: &[u8]) -> Result<Self> {
+        Self::from_source(LibrarySource::Bytes(wil), wix)
+    }
+
+    fn from_source(source: LibrarySource, wix: &[u8]) -> Result<Self> {
+        if wix.len() < WIX_HEADER_SIZE {
+            return Err(Error::TruncatedHeader { expected: WIX_HEADER_SIZE, actual: wix.len() });
+        }
+        let count = (&wix[44..48]).get_u32_le() as usize;
+        // 旧版本索引紧跟48字节文件头, 多出4字节时为新版本
+        let versioned = (wix.len() - WIX_HEADER_SIZE) / 4 > count;
+        let skip = if versioned { WIX_HEADER_SIZE + 4 } else { WIX_HEADER_SIZE };
+        let offsets: Vec<u32> = wix[skip..].chunks_exact(4).take(count).map(|mut x| x.get_u32_le()).collect();
+
+        let mut file = source.open()?;
+        let mut header = [0u8; WIL_HEADER_SIZE + 4];
+        let header_size = if versioned { WIL_HEADER_SIZE + 4 } else { WIL_HEADER_SIZE };
+        read_buffer(&mut file, &mut header[..header_size])?;
+        let mut buf = &header[48..];
+        let color_count = buf.get_u32_le();
+        let palette_size = buf.get_u32_le() as usize;
+        let format = match color_count {
+            256 => PixelFormat::Palette8,
+            65536 => PixelFormat::Rgb565,
+            _ => return Err(Error::UnsupportedColorCount(color_count)),
+        };
+
+        // 调色板为BGRA, 缺失时使用内置调色板
+        let palette = if format == PixelFormat::Palette8 && palette_size >= 1024 {
+            let mut bgra = vec![0u8; 1024];
+            read_buffer(&mut file, &mut bgra[..])?;
+            let mut palette = Vec::with_capacity(1024);
+            for (i, c) in bgra.chunks_exact(4).enumerate() {
+                palette.extend_from_slice(&[c[2], c[1], c[0], if i == 0 { 0 } else { 255 }]);
+            }
+            palette
+        } else {
+            PALETTE_RGBA.to_vec()
+        };
+
+        Ok(Self { source, index: None, offsets, format, palette, image_header_size: if versioned { 12 } else { 8 } })
+    }
+
+    /// 读取图片头, 返回定位到像素数据的reader
+    fn read_head(&self, index: u32) -> Result<(SourceReader, ImageHeader)> {
+        let seek = *self.offsets.get(index as usize).ok_or(Error::BadIndex { index, len: self.offsets.len() })?;
+        if seek == 0 { return Err(Error::EmptyImage) }
+
+        let mut reader = self.source.open()?;
+        reader.seek(SeekFrom::Start(seek as u64))?;
+        let mut head = [0u8; 12];
+        read_buffer(&mut reader, &mut head[..self.image_header_size])?;
+        let mut head = &head[..];
+        let width = head.get_u16_le();
+        let height = head.get_u16_le();
+        let offset_x = head.get_i16_le();
+        let offset_y = head.get_i16_le();
+        if width == 0 || height == 0 {
+            return Err(Error::EmptyImage);
+        }
+        Ok((reader, ImageHeader { format: self.format, width, height, offset_x, offset_y }))
+    }
+}
+
+impl ImageLibrary for WilLibrary {
+
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn header(&self, index: u32) -> Result<ImageHeader> {
+        self.read_head(index).map(|(_, head)| head)
+    }
+
+    fn load_image(&self, index: u32) -> Result<ImageData> {
+        let (mut reader, head) = self.read_head(index)?;
+        let (width, height) = (head.width as usize, head.height as usize);
+        let length = self.format.data_length(width, height);
+        check_remaining(&mut reader, length)?;
+        let mut data = vec![0u8; length];
+        read_buffer(&mut reader, &mut data[..])?;
+        let bytes = self.format.decode(width, height, &data, &self.palette)?;
+        Ok(ImageData { width: head.width, height: head.height, offset_x: head.offset_x, offset_y: head.offset_y, bytes: Bytes::from(bytes) })
+    }
+
+    fn sources(&self) -> Vec<PathBuf> {
+        self.source.path().into_iter().map(Path::to_path_buf).chain(self.index.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BufMut;
+    use super::*;
+
+    /// versioned为true时使用新版文件头和12字节图片头, 宽度为0的图片写为空条目
+    fn library(versioned: bool, color_count: u32, palette: Option<&[u8]>, images: &[(u16, u16, &[u8])]) -> Result<WilLibrary> {
+        let mut wil = vec![0u8; 44];
+        wil.put_u32_le(images.len() as u32);
+        wil.put_u32_le(color_count);
+        wil.put_u32_le(palette.map(|x| x.len()).unwrap_or(0) as u32);
+        if versioned { wil.put_u32_le(1) }
+        wil.put_slice(palette.unwrap_or_default());
+        let mut wix = vec![0u8; 44];
+        wix.put_u32_le(images.len() as u32);
+        if versioned { wix.put_u32_le(1) }
+        for (width, height, data) in images {
+            if *width == 0 {
+                wix.put_u32_le(0);
+                continue;
+            }
+            wix.put_u32_le(wil.len() as u32);
+            wil.put_u16_le(*width);
+            wil.put_u16_le(*height);
+            wil.put_i16_le(-2);
+            wil.put_i16_le(3);
+            if versioned { wil.put_u32_le(0) }
+            wil.put_slice(data);
+        }
+        WilLibrary::from_bytes(Bytes::from(wil), &wix)
+    }
+
+    #[test]
+    fn palette8() {
+        // 3x2, 每行补齐到4字节, 自下而上
+        let data = [1, 2, 3, 0, 4, 5, 6, 0];
+        for versioned in [false, true] {
+            let library = library(versioned, 256, None, &[(3, 2, &data), (0, 0, &[])]).unwrap();
+            assert_eq!(library.len(), 2);
+            let image = library.load_image(0).unwrap();
+            assert_eq!((image.width, image.height, image.offset_x, image.offset_y), (3, 2, -2, 3));
+            assert_eq!(&image.bytes[..4], &PALETTE_RGBA[16..20]);
+            assert_eq!(&image.bytes[12..16], &PALETTE_RGBA[4..8]);
+            assert_eq!(library.header(0).unwrap().format, PixelFormat::Palette8);
+            assert!(matches!(library.load_image(1), Err(Error::EmptyImage)));
+            assert!(matches!(library.load_image(2), Err(Error::BadIndex { index: 2, len: 2 })));
+        }
+    }
+
+    #[test]
+    fn file_palette() {
+        // BGRA调色板, 0号颜色透明
+        let mut palette = vec![0u8; 1024];
+        palette[..8].copy_from_slice(&[10, 20, 30, 0, 40, 50, 60, 0]);
+        for versioned in [false, true] {
+            let library = library(versioned, 256, Some(&palette), &[(2, 1, &[0, 1, 0, 0])]).unwrap();
+            assert_eq!(&library.load_image(0).unwrap().bytes[..], &[30, 20, 10, 0, 60, 50, 40, 255]);
+        }
+    }
+
+    #[test]
+    fn rgb565() {
+        for versioned in [false, true] {
+            let library = library(versioned, 65536, None, &[(2, 2, &[0x00, 0xF8, 0x00, 0x00, 0x1F, 0x00, 0xE0, 0x07])]).unwrap();
+            assert_eq!(library.header(0).unwrap().format, PixelFormat::Rgb565);
+            let image = library.load_image(0).unwrap();
+            assert_eq!(&image.bytes[..], &[0, 0, 248, 255, 0, 252, 0, 255, 248, 0, 0, 255, 0, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn bad_files() {
+        assert!(matches!(library(false, 65535, None, &[]), Err(Error::UnsupportedColorCount(65535))));
+        assert!(matches!(WilLibrary::from_bytes(Bytes::new(), &[0; 10]), Err(Error::TruncatedHeader { .. })));
+        // 像素数据不完整
+        let library = library(true, 65536, None, &[(2, 2, &[1, 2, 3])]).unwrap();
+        assert!(library.load_image(0).is_err());
+    }
+}
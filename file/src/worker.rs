@@ -0,0 +1,283 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use tracing::debug;
+use crate::asset::{FileDesc, FileDescType, ImageAsset};
+use crate::library::ImageData;
+use crate::error::{Error, Result};
+
+/// 后台解码线程池, ImageAsset可以同时在其他线程使用
+pub struct DecodePool {
+    asset: Arc<ImageAsset>,
+    sender: Option<mpsc::Sender<Request>>,
+    closed: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// slot为None时是预加载请求, 按批量请求加载
+struct Request {
+    desc: FileDesc,
+    typ: FileDescType,
+    slot: Option<SlotGuard>,
+}
+
+#[derive(Default)]
+struct Slot {
+    value: Mutex<Option<Result<ImageData>>>,
+    ready: Condvar,
+}
+
+impl Slot {
+    fn set(&self, value: Result<ImageData>) {
+        *lock(&self.value) = Some(value);
+        self.ready.notify_all();
+    }
+}
+
+/// 请求未完成就被丢弃时(如解码线程已退出)填入错误, 等待的handle不会一直阻塞
+struct SlotGuard(Arc<Slot>);
+
+impl SlotGuard {
+    fn finish(self, value: Result<ImageData>) {
+        self.0.set(value);
+    }
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        if lock(&self.0.value).is_none() {
+            self.0.set(Err(Error::Decode("decode request dropped".to_string())));
+        }
+    }
+}
+
+/// 异步加载的图片, 解码完成后可以多次读取
+#[derive(Clone)]
+pub struct ImageHandle {
+    slot: Arc<Slot>,
+}
+
+impl ImageHandle {
+
+    fn ready(value: Result<ImageData>) -> Self {
+        Self { slot: Arc::new(Slot { value: Mutex::new(Some(value)), ready: Condvar::new() }) }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        lock(&self.slot.value).is_some()
+    }
+
+    /// 未完成时返回None, 不阻塞
+    pub fn try_get(&self) -> Option<Result<ImageData>> {
+        lock(&self.slot.value).clone()
+    }
+
+    /// 阻塞到解码完成
+    pub fn wait(&self) -> Result<ImageData> {
+        let mut value = lock(&self.slot.value);
+        loop {
+            if let Some(x) = value.as_ref() {
+                return x.clone();
+            }
+            value = self.slot.ready.wait(value).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl DecodePool {
+
+    /// workers为解码线程数量, 至少为1, asset可以是已共享的`Arc<ImageAsset>`
+    pub fn new<A: Into<Arc<ImageAsset>>>(asset: A, workers: usize) -> Self {
+        let asset = asset.into();
+        let (sender, receiver) = mpsc::channel::<Request>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let closed = Arc::new(AtomicBool::new(false));
+        let workers = (0..workers.max(1)).map(|i| {
+            let asset = asset.clone();
+            let receiver = receiver.clone();
+            let closed = closed.clone();
+            thread::Builder::new().name(format!("image-decode-{}", i)).spawn(move || {
+                loop {
+                    // 取到请求后立即释放接收端, 其他线程可以继续取
+                    let request = match lock(&receiver).recv() {
+                        Ok(x) => x,
+                        Err(_) => break,
+                    };
+                    if request.slot.is_none() && closed.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    // 解码时panic不影响线程继续处理其他请求
+                    let (desc, typ) = (request.desc, request.typ);
+                    let Some(slot) = request.slot else {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(|| asset.load_images(desc, typ)));
+                        continue;
+                    };
+                    let value = panic::catch_unwind(AssertUnwindSafe(|| asset.load_image(desc, typ)))
+                        .unwrap_or_else(|_| Err(Error::Decode("decode panicked".to_string())));
+                    slot.finish(value);
+                }
+                debug!("image decode worker {} stopped", i);
+            }).expect("failed to spawn image decode worker")
+        }).collect();
+        Self { asset, sender: Some(sender), closed, workers }
+    }
+
+    /// 已缓存的图片直接返回完成的handle, 否则加入解码队列
+    pub fn request(&self, desc: FileDesc, typ: FileDescType) -> ImageHandle {
+        if let Some(value) = self.asset.get_cached(&desc, typ) {
+            return ImageHandle::ready(value);
+        }
+        let slot = Arc::new(Slot::default());
+        let handle = ImageHandle { slot: slot.clone() };
+        let request = Request { desc, typ, slot: Some(SlotGuard(slot)) };
+        match self.sender.as_ref().map(|x| x.send(request)) {
+            Some(Err(mpsc::SendError(request))) => ImageHandle::ready(self.asset.load_image(request.desc, request.typ)),
+            _ => handle,
+        }
+    }
+
+    /// 预先解码一批图片放入缓存, 不关心结果
+    /// 每个请求由一个线程处理, 批量请求使用`ImageAsset::load_images`, 每个图片库只读取一遍
+    pub fn prefetch<I: IntoIterator<Item = FileDesc>>(&self, descs: I, typ: FileDescType) {
+        let Some(sender) = self.sender.as_ref() else { return };
+        for desc in descs {
+            if sender.send(Request { desc, typ, slot: None }).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// 在当前线程同步加载
+    pub fn load_image(&self, desc: FileDesc, typ: FileDescType) -> Result<ImageData> {
+        self.asset.load_image(desc, typ)
+    }
+
+    pub fn asset(&self) -> &Arc<ImageAsset> {
+        &self.asset
+    }
+}
+
+impl Drop for DecodePool {
+    fn drop(&mut self) {
+        // 关闭队列后跳过剩余的预加载, 已返回handle的请求仍然会完成
+        self.closed.store(true, Ordering::Relaxed);
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use crate::library::{ImageHeader, ImageLibrary};
+    use super::*;
+
+    /// 序号1解码时panic
+    struct PanicLibrary;
+
+    impl ImageLibrary for PanicLibrary {
+        fn len(&self) -> usize {
+            2
+        }
+
+        fn header(&self, _: u32) -> Result<ImageHeader> {
+            Err(Error::EmptyImage)
+        }
+
+        fn load_image(&self, index: u32) -> Result<ImageData> {
+            if index == 1 {
+                panic!("bad image");
+            }
+            Ok(ImageData { width: 1, height: 1, offset_x: 0, offset_y: 0, bytes: Bytes::from_static(&[1, 2, 3, 4]) })
+        }
+    }
+
+    #[test]
+    fn worker_panic_fills_handle() {
+        let asset = ImageAsset::new(String::new());
+        asset.put_library(1, FileDescType::WZL, Arc::new(PanicLibrary));
+        let pool = DecodePool::new(asset, 1);
+        let handle = pool.request(FileDesc::ZONE { file: 1, number: 0, index: 1 }, FileDescType::WZL);
+        assert!(matches!(handle.wait(), Err(Error::Decode(_))));
+        let handle = pool.request(FileDesc::ZONE { file: 1, number: 0, index: 0 }, FileDescType::WZL);
+        assert_eq!(&handle.wait().unwrap().bytes[..], &[1, 2, 3, 4]);
+    }
+
+    /// 记录每次批量读取的序号, 图片内容为(库编号, 序号), 序号超出len时返回错误
+    struct BatchLibrary {
+        id: u8,
+        batches: Mutex<Vec<Vec<u32>>>,
+    }
+
+    impl ImageLibrary for BatchLibrary {
+        fn len(&self) -> usize {
+            10
+        }
+
+        fn header(&self, _: u32) -> Result<ImageHeader> {
+            Err(Error::EmptyImage)
+        }
+
+        fn load_image(&self, index: u32) -> Result<ImageData> {
+            if index as usize >= self.len() {
+                return Err(Error::BadIndex { index, len: self.len() });
+            }
+            Ok(ImageData { width: 1, height: 1, offset_x: 0, offset_y: 0, bytes: Bytes::from(vec![self.id, index as u8, 0, 255]) })
+        }
+
+        fn load_images(&self, indices: &[u32]) -> Vec<Result<ImageData>> {
+            lock(&self.batches).push(indices.to_vec());
+            indices.iter().map(|x| self.load_image(*x)).collect()
+        }
+    }
+
+    #[test]
+    fn prefetch_keeps_batches() {
+        let asset = ImageAsset::new(String::new());
+        let libraries: Vec<Arc<BatchLibrary>> = (1..=2).map(|id| Arc::new(BatchLibrary { id, batches: Mutex::new(Vec::new()) })).collect();
+        for library in &libraries {
+            asset.put_library(library.id as u32, FileDescType::WZL, library.clone());
+        }
+        let pool = DecodePool::new(asset, 1);
+        pool.prefetch([
+            FileDesc::ZONES { file: 1, number: 0, index: vec![5, 1, 3] },
+            FileDesc::RANGE { file: 2, number: 0, start: 2, count: 3 },
+        ], FileDescType::WZL);
+        // 只有一个线程, 之后的请求完成时预加载已完成
+        pool.request(FileDesc::ZONE { file: 1, number: 0, index: 0 }, FileDescType::WZL).wait().unwrap();
+        assert_eq!(*lock(&libraries[0].batches), vec![vec![5, 1, 3]]);
+        assert_eq!(*lock(&libraries[1].batches), vec![vec![2, 3, 4]]);
+
+        // 部分已缓存的批量请求, 结果与get_cache_keys的顺序一致
+        let key = |file: u64, index: u64| file << 32 | index;
+        let desc = FileDesc::KEYS(vec![key(2, 9), key(1, 3), key(2, 7), key(1, 12), key(1, 6), key(2, 3)]);
+        let keys = desc.get_cache_keys();
+        let values = pool.asset().load_images(desc, FileDescType::WZL);
+        assert_eq!(values.len(), keys.len());
+        for (key, value) in keys.iter().zip(&values) {
+            let (file, index) = ((key >> 32) as u8, *key as u8);
+            match value {
+                Ok(image) => assert_eq!(&image.bytes[..2], &[file, index]),
+                Err(e) => assert!(matches!(e, Error::BadIndex { index: 12, .. })),
+            }
+        }
+        assert!(values[3].is_err());
+        assert_eq!(lock(&libraries[0].batches)[1..], [vec![12, 6]]);
+        assert_eq!(lock(&libraries[1].batches)[1..], [vec![9, 7]]);
+    }
+
+    #[test]
+    fn dropped_request_fills_handle() {
+        let slot = Arc::new(Slot::default());
+        let handle = ImageHandle { slot: slot.clone() };
+        drop(SlotGuard(slot));
+        assert!(matches!(handle.wait(), Err(Error::Decode(_))));
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use crate::data::read_wzx;
+use crate::pixel::PALETTE_RGBA;
+use crate::error::{Error, Result};
+pub use crate::pixel::PixelFormat;
+
+const WZL_SUFFIX: &str = "wzl";
+const WZX_SUFFIX: &str = "wzx";
+const IDX_SUFFIX: &str = "idx";
+
+/// wzl/wzx文件头: 44字节描述 + 4字节图片数量
+const FILE_HEADER_SIZE: usize = 48;
+const FILE_DESCRIPTION: &[u8] = b"www.shandagames.com";
+const IMAGE_HEADER_SIZE: usize = 16;
+
+pub enum Pixels {
+    /// RGBA, 每像素4字节, alpha小于128视为透明
+    Rgba(Vec<u8>),
+    /// 调色板索引, 每像素1字节, 0为透明
+    Indexed(Vec<u8>),
+}
+
+pub struct SourceImage {
+    pub width: u16,
+    pub height: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub pixels: Pixels,
+}
+
+/// wzl图片库写入, 同时生成wzx和idx索引
+/// 每个条目保存编码后的图片头和压缩数据, 空条目为空Bytes
+#[derive(Default)]
+pub struct WzlWriter {
+    entries: Vec<Bytes>,
+}
+
+impl WzlWriter {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 读取已有的wzl/wzx, 用于追加或替换图片, 未修改的图片不重新编码
+    pub fn open(path: &str) -> Result<Self> {
+        let path = Path::new(path);
+        let offsets = read_wzx(path.with_extension(WZX_SUFFIX).to_str().unwrap_or_default())?;
+        let wzl = fs::read(path.with_extension(WZL_SUFFIX))?;
+        let mut entries = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.iter().enumerate() {
+            if *offset == 0 {
+                entries.push(Bytes::new());
+                continue;
+            }
+            let start = *offset as usize;
+            let end = start + IMAGE_HEADER_SIZE;
+            if end > wzl.len() {
+                return Err(Error::BadIndex { index: i as u32, len: offsets.len() });
+            }
+            let length = raw_body_length(&wzl[start..end])?;
+            if length == 0 {
+                entries.push(Bytes::new());
+                continue;
+            }
+            if end + length > wzl.len() {
+                return Err(Error::TruncatedData { expected: end + length, actual: wzl.len() });
+            }
+            entries.push(Bytes::copy_from_slice(&wzl[start..end + length]));
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 追加图片, 返回图片索引
+    pub fn push(&mut self, image: &SourceImage, format: PixelFormat) -> Result<u32> {
+        self.entries.push(encode_image(image, format)?);
+        Ok(self.entries.len() as u32 - 1)
+    }
+
+    /// 追加空图片占位, 返回图片索引
+    pub fn push_empty(&mut self) -> u32 {
+        self.entries.push(Bytes::new());
+        self.entries.len() as u32 - 1
+    }
+
+    pub fn replace(&mut self, index: u32, image: &SourceImage, format: PixelFormat) -> Result<()> {
+        let len = self.entries.len();
+        let entry = self.entries.get_mut(index as usize).ok_or(Error::BadIndex { index, len })?;
+        *entry = encode_image(image, format)?;
+        Ok(())
+    }
+
+    /// 写入path对应的wzl, wzx, idx三个文件
+    /// wzx中空图片为0, idx连续记录每张图片头的位置并以文件长度结尾
+    pub fn write(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+        let count = self.entries.len() as u32;
+        let body: usize = self.entries.iter().map(|x| x.len().max(IMAGE_HEADER_SIZE)).sum();
+
+        let mut wzl = BytesMut::with_capacity(FILE_HEADER_SIZE + body);
+        let mut wzx = BytesMut::with_capacity(FILE_HEADER_SIZE + self.entries.len() * 4);
+        let mut idx = BytesMut::with_capacity((self.entries.len() + 1) * 4);
+        put_file_header(&mut wzl, count);
+        put_file_header(&mut wzx, count);
+
+        for entry in &self.entries {
+            let pos = wzl.len() as u32;
+            idx.put_u32_le(pos);
+            if entry.is_empty() {
+                wzx.put_u32_le(0);
+                wzl.put_bytes(0, IMAGE_HEADER_SIZE);
+            } else {
+                wzx.put_u32_le(pos);
+                wzl.put_slice(entry);
+            }
+        }
+        idx.put_u32_le(wzl.len() as u32);
+
+        fs::write(path.with_extension(WZL_SUFFIX), &wzl)?;
+        fs::write(path.with_extension(WZX_SUFFIX), &wzx)?;
+        fs::write(path.with_extension(IDX_SUFFIX), &idx)?;
+        Ok(())
+    }
+}
+
+fn put_file_header(buf: &mut BytesMut, count: u32) {
+    buf.put_slice(FILE_DESCRIPTION);
+    buf.put_bytes(0, FILE_HEADER_SIZE - 4 - FILE_DESCRIPTION.len());
+    buf.put_u32_le(count);
+}
+
+/// 图片头之后的数据长度, 未压缩时按行4字节对齐计算
+pub(crate) fn raw_body_length(head: &[u8]) -> Result<usize> {
+    let mut head = head;
+    let pixel = head.get_u8();
+    head.advance(3);
+    let width = head.get_u16_le() as usize;
+    let height = head.get_u16_le() as usize;
+    head.advance(4);
+    let length = head.get_u32_le() as usize;
+    if length > 0 || width == 0 || height == 0 {
+        return Ok(length);
+    }
+    Ok(PixelFormat::from_value(pixel)?.data_length(width, height))
+}
+
+/// 编码为图片头 + zlib压缩数据, 行序自下而上
+fn encode_image(image: &SourceImage, format: PixelFormat) -> Result<Bytes> {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    if width == 0 || height == 0 {
+        return Ok(Bytes::new());
+    }
+    let (bpp, actual) = match &image.pixels {
+        Pixels::Rgba(x) => (4, x.len()),
+        Pixels::Indexed(x) => (1, x.len()),
+    };
+    if actual < width * height * bpp {
+        return Err(Error::TruncatedData { expected: width * height * bpp, actual });
+    }
+
+    let stride = format.stride(width);
+    let size = format.bytes_per_pixel();
+    let mut raw = vec![0u8; stride * height];
+    let mut nearest: HashMap<u32, u8> = HashMap::new();
+    for y in 0..height {
+        let row = &mut raw[(height - y - 1) * stride..(height - y) * stride];
+        for x in 0..width {
+            let i = y * width + x;
+            let out = &mut row[x * size..(x + 1) * size];
+            match (&image.pixels, format) {
+                (Pixels::Indexed(p), PixelFormat::Palette8) => out[0] = p[i],
+                (Pixels::Rgba(p), PixelFormat::Palette8) => {
+                    out[0] = rgba_to_palette(&p[i * 4..i * 4 + 4], &mut nearest);
+                }
+                (Pixels::Indexed(p), _) => {
+                    let c = p[i] as usize * 4;
+                    format.encode_pixel(&PALETTE_RGBA[c..c + 4], out);
+                }
+                (Pixels::Rgba(p), _) => format.encode_pixel(&p[i * 4..i * 4 + 4], out),
+            }
+        }
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(raw.len() / 2), Compression::best());
+    encoder.write_all(&raw)?;
+    let body = encoder.finish()?;
+
+    let mut buf = BytesMut::with_capacity(IMAGE_HEADER_SIZE + body.len());
+    buf.put_u8(format.value());
+    buf.put_u8(1);
+    buf.put_u8(0);
+    buf.put_u8(9);
+    buf.put_u16_le(image.width);
+    buf.put_u16_le(image.height);
+    buf.put_i16_le(image.offset_x);
+    buf.put_i16_le(image.offset_y);
+    buf.put_u32_le(body.len() as u32);
+    buf.put_slice(&body);
+    Ok(buf.freeze())
+}
+
+/// 查找最接近的调色板颜色, 索引0保留给透明色
+fn rgba_to_palette(c: &[u8], nearest: &mut HashMap<u32, u8>) -> u8 {
+    if c[3] < 128 {
+        return 0;
+    }
+    let key = u32::from_le_bytes([c[0], c[1], c[2], 0]);
+    *nearest.entry(key).or_insert_with(|| {
+        let mut best = (1u8, u32::MAX);
+        for i in 1..256 {
+            let p = &PALETTE_RGBA[i * 4..i * 4 + 3];
+            let d: u32 = (0..3).map(|j| (p[j] as i32 - c[j] as i32).pow(2) as u32).sum();
+            if d < best.1 {
+                best = (i as u8, d);
+            }
+        }
+        best.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::library::{ImageLibrary, IndexKind, WzlLibrary};
+    use super::*;
+
+    fn rgba(width: u16, height: u16, seed: u8) -> SourceImage {
+        let pixels = (0..width as usize * height as usize * 4).map(|x| (x as u8).wrapping_mul(seed) | 1).collect();
+        SourceImage { width, height, offset_x: -3, offset_y: 7, pixels: Pixels::Rgba(pixels) }
+    }
+
+    #[test]
+    fn write_then_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test");
+        let path = path.to_str().unwrap();
+
+        let mut writer = WzlWriter::new();
+        assert_eq!(writer.push(&rgba(2, 2, 3), PixelFormat::Argb32).unwrap(), 0);
+        assert_eq!(writer.push_empty(), 1);
+        let indexed = SourceImage { width: 5, height: 3, offset_x: 0, offset_y: 0, pixels: Pixels::Indexed((0..15).collect()) };
+        assert_eq!(writer.push(&indexed, PixelFormat::Palette8).unwrap(), 2);
+        let first = rgba(3, 5, 7);
+        writer.replace(0, &first, PixelFormat::Argb32).unwrap();
+        writer.write(path).unwrap();
+
+        for kind in [IndexKind::Wzx, IndexKind::Idx] {
+            let library = WzlLibrary::open(path, kind).unwrap();
+            assert_eq!(library.len(), 3);
+            let image = library.load_image(0).unwrap();
+            assert_eq!((image.width, image.height, image.offset_x, image.offset_y), (3, 5, -3, 7));
+            let Pixels::Rgba(pixels) = &first.pixels else { unreachable!() };
+            assert_eq!(&image.bytes[..], &pixels[..]);
+            assert!(matches!(library.load_image(1), Err(Error::EmptyImage)));
+            let image = library.load_image(2).unwrap();
+            assert_eq!(&image.bytes[4..8], &PALETTE_RGBA[4..8]);
+            assert_eq!(&image.bytes[14 * 4..15 * 4], &PALETTE_RGBA[14 * 4..15 * 4]);
+        }
+
+        let mut writer = WzlWriter::open(path).unwrap();
+        assert_eq!(writer.len(), 3);
+        writer.push(&rgba(1, 1, 9), PixelFormat::Rgb565).unwrap();
+        writer.write(path).unwrap();
+        let library = WzlLibrary::open(path, IndexKind::Wzx).unwrap();
+        assert_eq!(library.len(), 4);
+        assert_eq!(library.load_image(0).unwrap().bytes, WzlLibrary::open(path, IndexKind::Idx).unwrap().load_image(0).unwrap().bytes);
+        assert_eq!(library.header(3).unwrap().format, PixelFormat::Rgb565);
+    }
+}